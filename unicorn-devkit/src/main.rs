@@ -9,6 +9,8 @@ extern crate fern;
 extern crate time;
 
 use std::env;
+use std::process;
+use std::path::Path;
 use getopts::Options;
 
 use unicorn::gfx;
@@ -16,7 +18,7 @@ use unicorn::gfx::Scale;
 use unicorn::cartridge::Cartridge;
 
 fn print_usage(program: &str, opts: &Options) {
-    let brief = format!("Usage: {} FILE [options]", program);
+    let brief = format!("Usage: {} FILE|DIRECTORY [options]", program);
     print!("{}", opts.usage(&brief));
 }
 
@@ -43,10 +45,23 @@ fn main() {
     opts.optflagopt("d", "dump", "dump the cartridge", "FILE");
     opts.optflagopt("t",
                     "transform",
-                    "transform the PNG/Unicorn cartridge in P8",
+                    "transform the cartridge in a PNG cartridge",
+                    "FILE");
+    opts.optflagopt("",
+                    "convert",
+                    "convert the cartridge in text (.uni), binary (.unb) or PNG (.png) format",
+                    "FILE");
+    opts.optmulti("",
+                  "ttf",
+                  "embed a TTF/OTF font in the converted cartridge",
+                  "FILE");
+    opts.optflagopt("",
+                    "replay",
+                    "replay an input movie recorded with F11",
                     "FILE");
     opts.optflagopt("s", "scale", "scale the display", "VALUE");
     opts.optflagopt("b", "bind", "bind a server on a specific address", "ADDR");
+    opts.optflagopt("", "savedir", "directory of the cartdata saves", "DIR");
     opts.optflag("v", "verbose", "Debug mode level");
     opts.optflag("h", "help", "print this help menu");
 
@@ -69,23 +84,30 @@ fn main() {
         }
     }
 
-    let mut scale = Scale::Scale4x;
+    // The display options given here replace the saved settings
+    let mut scale = None;
     if matches.opt_present("s") {
         let value = matches.opt_str("s").unwrap().parse::<i32>().unwrap();
-        match value {
-            2 => scale = Scale::Scale2x,
-            3 => scale = Scale::Scale3x,
-            4 => scale = Scale::Scale4x,
-            5 => scale = Scale::Scale5x,
-            6 => scale = Scale::Scale6x,
-            8 => scale = Scale::Scale8x,
-            10 => scale = Scale::Scale10x,
-            _ => scale = Scale::Scale1x,
-        }
+        scale = Some(match value {
+            2 => Scale::Scale2x,
+            3 => Scale::Scale3x,
+            4 => Scale::Scale4x,
+            5 => Scale::Scale5x,
+            6 => Scale::Scale6x,
+            8 => Scale::Scale8x,
+            10 => Scale::Scale10x,
+            _ => Scale::Scale1x,
+        });
     }
 
-    let fullscreen = matches.opt_present("f");
+    let fullscreen = if matches.opt_present("f") { Some(true) } else { None };
     let opengl = matches.opt_present("o");
+    let savedir = matches.opt_str("savedir");
+
+    if let Some(movie) = matches.opt_str("replay") {
+        run_movie(scale, fullscreen, opengl, savedir, &movie);
+        return;
+    }
 
     let input = if !matches.free.is_empty() {
         matches.free[0].clone()
@@ -93,81 +115,167 @@ fn main() {
         run_cartridge_raw(scale,
                           fullscreen,
                           opengl,
+                          savedir,
                           "unicorn.uni",
                           include_bytes!("../../unicorn/sys/unicorn.uni").to_vec(),
                           matches.opt_present("e"));
         return;
     };
 
-    if matches.opt_present("c") {
-        if input.contains(".uni") {
-            match Cartridge::from_unicorn_file(&input) {
-                Ok(c) => {
-                    println!("{:?}", c);
-                }
-                Err(e) => panic!(e),
-            }
-        } else if input.contains(".uc") {
-            match Cartridge::from_unicorn_splitted_file(&input) {
-                Ok(c) => {
-                    println!("{:?}", c);
-                }
-                Err(e) => panic!(e),
+    if Path::new(&input).is_dir() {
+        run_interactive(scale, fullscreen, opengl, savedir, &input);
+    } else if matches.opt_present("c") {
+        match Cartridge::from_file(&input) {
+            Ok(c) => {
+                println!("{:?}", c);
             }
-        } else if input.contains(".duc") {
-            match Cartridge::from_dunicorn_file(&input) {
-                Ok(c) => {
-                    println!("{:?}", c);
-                }
-                Err(e) => panic!(e),
+            Err(e) => panic!(e),
+        }
+    } else if let Some(output) = matches.opt_str("convert") {
+        convert_cartridge(&input, &output, &matches.opt_strs("ttf"));
+    } else if let Some(output) = matches.opt_str("t") {
+        if !output.ends_with(".png") {
+            println!("The transformed cartridge {:?} must be a .png file", output);
+            process::exit(1);
+        }
+        convert_cartridge(&input, &output, &matches.opt_strs("ttf"));
+    } else {
+        run_cartridge(scale, fullscreen, opengl, savedir, &input, matches.opt_present("e"));
+    }
+}
+
+pub fn convert_cartridge(input: &str, output: &str, ttf_files: &[String]) {
+    let mut cartridge = match Cartridge::from_file(input) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("Impossible to load the cartridge {:?}: {:?}", input, e);
+            process::exit(1);
+        }
+    };
+
+    for filename in ttf_files {
+        match cartridge.ttf.add_file(filename) {
+            Ok(name) => println!("Font {:?} embedded from {:?}", name, filename),
+            Err(e) => {
+                println!("Impossible to embed the font {:?}: {:?}", filename, e);
+                process::exit(1);
             }
         }
+    }
+
+    if output.ends_with(".uni") {
+        let version = format!("{:?}.{:?}.{:?}",
+                              unicorn::unicorn::VERSION,
+                              unicorn::unicorn::MAJOR_VERSION,
+                              unicorn::unicorn::MINOR_VERSION);
+        cartridge.save_in_unicorn(output, &version);
+    } else if output.ends_with(".unb") {
+        if let Err(e) = cartridge.save_in_binary(output) {
+            println!("Impossible to save the cartridge {:?}: {:?}", output, e);
+            process::exit(1);
+        }
+    } else if output.ends_with(".png") {
+        if let Err(e) = cartridge.save_in_png(output, None) {
+            println!("Impossible to save the cartridge {:?}: {:?}", output, e);
+            process::exit(1);
+        }
     } else {
-        run_cartridge(scale, fullscreen, opengl, &input, matches.opt_present("e"));
+        println!("Unknown output format {:?}, expected .uni, .unb or .png", output);
+        process::exit(1);
     }
 }
 
-pub fn run_cartridge(scale: gfx::Scale,
-                     fullscreen: bool,
+pub fn run_cartridge(scale: Option<gfx::Scale>,
+                     fullscreen: Option<bool>,
                      opengl: bool,
+                     savedir: Option<String>,
                      filename: &str,
                      editor: bool) {
-    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale, fullscreen, opengl, false) {
+    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale.unwrap_or(Scale::Scale4x),
+                                                                    fullscreen.unwrap_or(false),
+                                                                    opengl,
+                                                                    false) {
         Err(error) => panic!("{:?}", error),
         Ok(frontend) => frontend,
     };
 
     frontend.start();
+    frontend.force_display(scale, fullscreen);
     frontend.init_controllers("../unicorn-sdl/sys/config/gamecontrollerdb.txt".to_string());
+    if let Some(dir) = savedir {
+        frontend.uc.set_save_directory(&dir);
+    }
 
     frontend.run_cartridge(filename, editor);
 }
 
-pub fn run_cartridge_raw(scale: gfx::Scale,
-                         fullscreen: bool,
+pub fn run_movie(scale: Option<gfx::Scale>,
+                 fullscreen: Option<bool>,
+                 opengl: bool,
+                 savedir: Option<String>,
+                 filename: &str) {
+    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale.unwrap_or(Scale::Scale4x),
+                                                                    fullscreen.unwrap_or(false),
+                                                                    opengl,
+                                                                    false) {
+        Err(error) => panic!("{:?}", error),
+        Ok(frontend) => frontend,
+    };
+
+    frontend.start();
+    frontend.force_display(scale, fullscreen);
+    frontend.init_controllers("../unicorn-sdl/sys/config/gamecontrollerdb.txt".to_string());
+    if let Some(dir) = savedir {
+        frontend.uc.set_save_directory(&dir);
+    }
+
+    frontend.run_movie(filename);
+}
+
+pub fn run_cartridge_raw(scale: Option<gfx::Scale>,
+                         fullscreen: Option<bool>,
                          opengl: bool,
+                         savedir: Option<String>,
                          filename: &str,
                          data: Vec<u8>,
                          editor: bool) {
-    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale, fullscreen, opengl, false) {
+    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale.unwrap_or(Scale::Scale4x),
+                                                                    fullscreen.unwrap_or(false),
+                                                                    opengl,
+                                                                    false) {
         Err(error) => panic!("{:?}", error),
         Ok(frontend) => frontend,
     };
-    
+
     frontend.start();
+    frontend.force_display(scale, fullscreen);
     frontend.init_controllers("../unicorn-sdl/sys/config/gamecontrollerdb.txt".to_string());
+    if let Some(dir) = savedir {
+        frontend.uc.set_save_directory(&dir);
+    }
 
     frontend.run_cartridge_raw(filename, data, editor);
 }
 
-pub fn run_interactive(scale: gfx::Scale, fullscreen: bool, opengl: bool) {
-    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale, fullscreen, opengl, false) {
+pub fn run_interactive(scale: Option<gfx::Scale>,
+                       fullscreen: Option<bool>,
+                       opengl: bool,
+                       savedir: Option<String>,
+                       directory: &str) {
+    let mut frontend = match unicorn_sdl::frontend::Frontend::init(scale.unwrap_or(Scale::Scale4x),
+                                                                    fullscreen.unwrap_or(false),
+                                                                    opengl,
+                                                                    false) {
         Err(error) => panic!("{:?}", error),
         Ok(frontend) => frontend,
     };
 
     frontend.start();
+    frontend.force_display(scale, fullscreen);
     frontend.init_controllers("../unicorn-sdl/sys/config/gamecontrollerdb.txt".to_string());
+    if let Some(dir) = savedir {
+        frontend.uc.set_save_directory(&dir);
+    }
 
-    frontend.run_interactive();
+    frontend.run_interactive(directory);
 }
\ No newline at end of file
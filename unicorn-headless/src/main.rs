@@ -0,0 +1,213 @@
+extern crate unicorn;
+
+extern crate getopts;
+
+extern crate log;
+extern crate fern;
+extern crate time;
+
+use std::env;
+use std::process;
+use std::time::Duration;
+use std::str::FromStr;
+use getopts::Options;
+
+use unicorn::unicorn::headless::Headless;
+use unicorn::unicorn::golden;
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} FILE [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+fn parse_number<T: FromStr>(program: &str, opts: &Options, name: &str, value: &str) -> T {
+    match value.parse::<T>() {
+        Ok(v) => v,
+        Err(_) => {
+            println!("Bad {} value {:?}, expected a number", name, value);
+            print_usage(program, opts);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let logger_config = fern::DispatchConfig {
+        format: Box::new(|msg: &str, level: &log::LogLevel, _location: &log::LogLocation| {
+            format!("[{}][{}] {}",
+                    time::now().strftime("%Y-%m-%d][%H:%M:%S").unwrap(),
+                    level,
+                    msg)
+        }),
+        output: vec![fern::OutputConfig::stderr()],
+        level: log::LogLevelFilter::Trace,
+    };
+
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optflagopt("n", "frames", "number of frames to run (default 60)", "VALUE");
+    opts.optflagopt("t", "dt", "duration of one frame in milliseconds (default 1/60s)", "VALUE");
+    opts.optflagopt("s",
+                    "seed",
+                    "seed of the random generators (default 0)",
+                    "VALUE");
+    opts.optflagopt("o", "output", "save the last frame in a .ppm or .png file", "FILE");
+    opts.optflagopt("i", "inputs", "scripted input timeline", "FILE");
+    opts.optflagopt("m",
+                    "movie",
+                    "replay an input movie, on the cartridge it was recorded with",
+                    "FILE");
+    opts.optmulti("g",
+                  "golden",
+                  "compare the frame with a reference image (.ppm or .png)",
+                  "FRAME=FILE");
+    opts.optflag("u", "update", "write the golden images instead of comparing them");
+    opts.optflagopt("", "savedir", "directory of the cartdata saves", "DIR");
+    opts.optflag("v", "verbose", "Debug mode level");
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            println!("{}", f);
+            print_usage(&program, &opts);
+            process::exit(1);
+        }
+    };
+    if matches.opt_present("h") || (matches.free.is_empty() && !matches.opt_present("m")) {
+        print_usage(&program, &opts);
+        return;
+    }
+
+    let level = if matches.opt_present("v") {
+        log::LogLevelFilter::Debug
+    } else {
+        log::LogLevelFilter::Warn
+    };
+    if let Err(e) = fern::init_global_logger(logger_config, level) {
+        panic!("Failed to initialize global logger: {}", e);
+    }
+
+    let movie = matches.opt_str("m");
+
+    let mut frames = match matches.opt_str("n") {
+        Some(value) => parse_number(&program, &opts, "frames", &value),
+        None => 60,
+    };
+
+    let dt = match matches.opt_str("t") {
+        Some(value) => Duration::from_millis(parse_number(&program, &opts, "dt", &value)),
+        None => Duration::from_secs(1) / 60,
+    };
+
+    let mut headless = Headless::new(dt);
+    if let Some(dir) = matches.opt_str("savedir") {
+        headless.uc.set_save_directory(&dir);
+    }
+    if let Some(value) = matches.opt_str("s") {
+        headless.uc.set_seed(parse_number(&program, &opts, "seed", &value));
+    }
+
+    if let Some(filename) = movie {
+        if !headless.replay_movie(&filename) {
+            println!("Failed to replay the movie {:?}", filename);
+            process::exit(1);
+        }
+
+        // The whole movie, unless a number of frames is given
+        if !matches.opt_present("n") {
+            frames = headless.uc.movie.movie.frames.len() as u64;
+        }
+    } else {
+        let input = matches.free[0].clone();
+        if !headless.load_cartridge(&input) {
+            println!("Failed to load the cartridge {:?}", input);
+            process::exit(1);
+        }
+    }
+
+    if !matches.opt_strs("g").is_empty() {
+        run_golden(&mut headless, &matches);
+        return;
+    }
+
+    let done = headless.run(frames);
+    headless.uc.stop();
+
+    let frame_buffer = headless.frame_buffer();
+    let checksum = frame_buffer.iter()
+        .fold(0u64, |acc, &pixel| acc.wrapping_mul(31).wrapping_add(pixel as u64));
+
+    println!("frames={} size={}x{} checksum={:016x}",
+             done,
+             headless.width(),
+             headless.height(),
+             checksum);
+
+    if let Some(filename) = matches.opt_str("o") {
+        if filename.ends_with(".png") {
+            headless.uc.screenshot(&filename);
+        } else if let Err(e) = headless.save_ppm(&filename) {
+            println!("Failed to save the frame in {:?}: {:?}", filename, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_golden(headless: &mut Headless, matches: &getopts::Matches) {
+    let timeline = match matches.opt_str("i") {
+        Some(filename) => {
+            match golden::Timeline::from_file(&filename) {
+                Ok(t) => t,
+                Err(e) => {
+                    println!("Failed to load the inputs: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => golden::Timeline::new(),
+    };
+
+    let mut checkpoints = Vec::new();
+    for value in matches.opt_strs("g") {
+        match golden::parse_checkpoint(&value) {
+            Ok(checkpoint) => checkpoints.push(checkpoint),
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let result = golden::run(headless, &timeline, &checkpoints, matches.opt_present("u"));
+    headless.uc.stop();
+
+    let diffs = match result {
+        Ok(diffs) => diffs,
+        Err(e) => {
+            println!("Golden run failed: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for (frame, diff) in diffs {
+        if diff.is_empty() {
+            println!("frame {}: ok", frame);
+        } else {
+            failed = true;
+            println!("frame {}: {}", frame, diff.report());
+
+            let filename = format!("diff-{}.ppm", frame);
+            if diff.image.save(&filename).is_ok() {
+                println!("frame {}: diff image saved in {}", frame, filename);
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
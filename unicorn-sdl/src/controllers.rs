@@ -1,12 +1,18 @@
+use sdl2::Sdl;
 use sdl2::controller::GameController;
 use sdl2::joystick::Joystick;
 
+use unicorn::config::MAX_PLAYERS;
+
+/// Opened controllers and joysticks, each one is given the first free
+/// player slot and keeps it until it is unplugged.
+///
+/// They are opened by device index but known by their SDL instance id, the
+/// `which` of all the other events.
 pub struct Controllers {
-    controllers: Vec<GameController>,
-    joysticks: Vec<Joystick>,
-    ids: Vec<u32>,
-    j_ids: Vec<u32>,
-    g_ids: Vec<u32>,
+    controllers: Vec<(u32, GameController)>,
+    joysticks: Vec<(u32, Joystick)>,
+    slots: Vec<Option<u32>>,
 }
 
 impl Controllers {
@@ -14,37 +20,121 @@ impl Controllers {
         Controllers {
             controllers: Vec::new(),
             joysticks: Vec::new(),
-            ids: Vec::new(),
-            j_ids: Vec::new(),
-            g_ids: Vec::new(),
+            slots: vec![None; MAX_PLAYERS as usize],
+        }
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        self.slots.contains(&Some(id))
+    }
+
+    fn assign(&mut self, id: u32) -> bool {
+        match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(player) => {
+                info!("[Frontend][CONTROLLER] {:?} -> player {:?}", id, player);
+                self.slots[player] = Some(id);
+                true
+            }
+            None => {
+                warn!("[Frontend][CONTROLLER] No player slot left for {:?}", id);
+                false
+            }
+        }
+    }
+
+    /// Open the game controller of the device index `id`, at startup or
+    /// when it is plugged
+    pub fn open_controller(&mut self, sdl: &Sdl, id: u32) {
+        info!("[Frontend][CONTROLLER] Attempting to open controller {}", id);
+
+        let game_controller_subsystem = match sdl.game_controller() {
+            Ok(subsystem) => subsystem,
+            Err(e) => {
+                error!("[Frontend][CONTROLLER] failed: {:?}", e);
+                return;
+            }
+        };
+
+        match game_controller_subsystem.open(id) {
+            Ok(c) => {
+                info!("[Frontend][CONTROLLER] Success: opened \"{}\"", c.name());
+                info!("[Frontend][CONTROLLER] Success: opened \"{}\"", c.mapping());
+
+                self.push_controller(c);
+            }
+            Err(e) => error!("[Frontend][CONTROLLER] failed: {:?}", e),
+        }
+    }
+
+    pub fn open_joystick(&mut self, sdl: &Sdl, id: u32) {
+        let joystick_subsystem = match sdl.joystick() {
+            Ok(subsystem) => subsystem,
+            Err(e) => {
+                error!("[Frontend][JOYSTICK] failed: {:?}", e);
+                return;
+            }
+        };
+
+        match joystick_subsystem.open(id) {
+            Ok(c) => {
+                info!("[Frontend][JOYSTICK] Success: opened \"{}\"", c.name());
+                self.push_joystick(c);
+            }
+            Err(e) => error!("[Frontend][JOYSTICK] failed: {:?}", e),
+        }
+    }
+
+    pub fn push_controller(&mut self, controller: GameController) {
+        let id = controller.instance_id() as u32;
+
+        if !self.contains(id) && self.assign(id) {
+            info!("[Frontend][CONTROLLER] REGISTERED CONTROLLER {:?}", id);
+
+            self.controllers.push((id, controller));
         }
     }
 
-    pub fn push_controller(&mut self, id: u32, controller: GameController) {
-        if !self.ids.contains(&id) {
-            info!("REGISTERED CONTROLLER {:?}", id);
+    pub fn push_joystick(&mut self, joystick: Joystick) {
+        let id = joystick.instance_id() as u32;
 
-            self.controllers.push(controller);
-            self.ids.push(id);
-            self.g_ids.push(id);
+        if !self.contains(id) && self.assign(id) {
+            info!("[Frontend][JOYSTICK] REGISTERED JOYSTICK {:?}", id);
+
+            self.joysticks.push((id, joystick));
         }
     }
 
-    pub fn push_joystick(&mut self, id: u32, joystick: Joystick) {
-        if !self.ids.contains(&id) {
-            info!("REGISTERED JOYSTICK {:?}", id);
+    /// Close an unplugged controller or joystick by instance id, its player
+    /// slot is free again
+    pub fn remove(&mut self, id: u32) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+
+        info!("[Frontend][CONTROLLER] UNREGISTERED {:?}", id);
+
+        self.controllers.retain(|&(c_id, _)| c_id != id);
+        self.joysticks.retain(|&(j_id, _)| j_id != id);
 
-            self.joysticks.push(joystick);
-            self.ids.push(id);
-            self.j_ids.push(id);
+        for slot in self.slots.iter_mut() {
+            if *slot == Some(id) {
+                *slot = None;
+            }
         }
+
+        true
+    }
+
+    /// Player slot of a controller or joystick, by instance id
+    pub fn player(&self, id: u32) -> Option<u8> {
+        self.slots.iter().position(|slot| *slot == Some(id)).map(|p| p as u8)
     }
 
     pub fn is_controller(&self, id: u32) -> bool {
-        self.g_ids.contains(&id)
+        self.controllers.iter().any(|&(c_id, _)| c_id == id)
     }
 
     pub fn is_joystick(&self, id: u32) -> bool {
-        self.j_ids.contains(&id)
+        self.joysticks.iter().any(|&(j_id, _)| j_id == id)
     }
 }
@@ -17,6 +17,13 @@ impl FrameTimes {
         }
     }
 
+    pub fn set_frame_duration(&mut self, frame_duration: Duration) {
+        if frame_duration != self.frame_duration {
+            self.target_time = self.last_time + frame_duration;
+            self.frame_duration = frame_duration;
+        }
+    }
+
     pub fn reset(&mut self) {
         let now = Instant::now();
         self.last_time = now;
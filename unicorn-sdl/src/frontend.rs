@@ -9,7 +9,6 @@ use std::path::Path;
 use chrono::prelude::*;
 use std::time::Instant;
 
-use sdl2::controller::Axis;
 use sdl2::keyboard::Scancode;
 use sdl2::mouse::MouseButton;
 
@@ -17,7 +16,7 @@ use renderer;
 use fps;
 use frametimes;
 use controllers;
-use input::{map_axis, map_button, map_button_joystick, map_axis_joystick};
+use input::{axis_index, axis_name, button_name, joystick_axis_name, joystick_button_name};
 
 #[cfg(target_os = "emscripten")]
 use emscripten;
@@ -25,6 +24,8 @@ use emscripten;
 use unicorn;
 use unicorn::gfx::Scale;
 use unicorn::config::scancode;
+use unicorn::config::bindings;
+use unicorn::config::settings;
 
 
 pub fn map_sdlscancode(code: Scancode) -> scancode::Scancode {
@@ -319,8 +320,12 @@ pub struct Frontend {
     pub uc: unicorn::unicorn::Unicorn,
     scale: Scale,
     fps_counter: fps::FpsCounter,
+    state_slot: u32,
 }
 
+// Number of quick save slots, F9 cycles between them
+const STATE_SLOTS: u32 = 4;
+
 
 impl Frontend {
     pub fn init(scale: Scale,
@@ -361,6 +366,7 @@ impl Frontend {
             uc: uc,
             scale: scale,
             fps_counter: fps::FpsCounter::new(),
+            state_slot: 0,
         })
     }
 
@@ -371,6 +377,17 @@ impl Frontend {
 
         info!("[Frontend] initialise Unicorn");
         self.uc.setup();
+        self.uc.load_bindings(&bindings::default_filename());
+        self.uc.load_settings(&settings::default_filename());
+
+        let (scale, fullscreen) = self.renderer.get_display();
+        self.uc.init_display(scale.factor(), fullscreen);
+    }
+
+    /// Scale and fullscreen given on the command line, they are used instead
+    /// of the saved settings
+    pub fn force_display(&mut self, scale: Option<Scale>, fullscreen: Option<bool>) {
+        self.uc.force_display(scale.map(|scale| scale.factor()), fullscreen);
     }
 
     pub fn init_controllers(&mut self, pathdb: String) {
@@ -389,47 +406,17 @@ impl Frontend {
 
         info!("[Frontend][CONTROLLER] {} joysticks available", available);
 
+        // Game controllers first, the other devices are opened as joysticks
         for id in 0..available {
             if game_controller_subsystem.is_game_controller(id) {
-                info!("[Frontend][CONTROLLER] Attempting to open controller {}",
-                      id);
-
-                match game_controller_subsystem.open(id) {
-                    Ok(c) => {
-                        // We managed to find and open a game controller,
-                        // exit the loop
-                        info!("[Frontend][CONTROLLER] Success: opened \"{}\"", c.name());
-                        info!("[Frontend][CONTROLLER] Success: opened \"{}\"", c.mapping());
-
-                        self.controllers.push_controller(id, Some(c).unwrap());
-                        break;
-                    }
-                    Err(e) => error!("[Frontend][CONTROLLER] failed: {:?}", e),
-                }
+                self.controllers.open_controller(&self.sdl, id);
             } else {
                 info!("[Frontend][CONTROLLER] {} is not a game controller", id);
             }
         }
 
-        let joystick_subsystem = self.sdl.joystick().unwrap();
-
-        let available = match joystick_subsystem.num_joysticks() {
-            Ok(n) => n,
-            Err(e) => panic!("[Frontend][JOYSTICK] can't enumerate joysticks: {}", e),
-        };
-
-        info!("[Frontend][JOYSTICK] {} joysticks available", available);
-
-        // Iterate over all available joysticks and stop once we manage to
-        // open one.
         for id in 0..available {
-            match joystick_subsystem.open(id) {
-                Ok(c) => {
-                    info!("[Frontend][JOYSTICK] Success: opened \"{}\"", c.name());
-                    self.controllers.push_joystick(id, Some(c).unwrap());
-                }
-                Err(e) => error!("[Frontend][JOYSTICK] failed: {:?}", e),
-            }
+            self.controllers.open_joystick(&self.sdl, id);
         }
     }
 
@@ -450,6 +437,8 @@ impl Frontend {
             self.handle_event();
         } else {
             error!("[Frontend] Failed to load the cartridge");
+            // Display the error until the window is closed
+            self.handle_event();
         }
     }
 
@@ -462,11 +451,25 @@ impl Frontend {
             self.handle_event();
         } else {
             error!("[Frontend] Failed to load the cartridge");
+            // Display the error until the window is closed
+            self.handle_event();
+        }
+    }
+
+    /// Replay an input movie recorded with F11, F11 gives the control back
+    pub fn run_movie(&mut self, filename: &str) {
+        if self.uc.replay_movie(filename) {
+            info!("[Frontend] Replaying the movie {:?}", filename);
+        } else {
+            error!("[Frontend] Failed to replay the movie {:?}", filename);
         }
+
+        self.handle_event();
     }
 
-    pub fn run_interactive(&mut self) {
-        self.uc.init_interactive();
+    /// Browse the cartridges of a directory with the launcher
+    pub fn run_interactive(&mut self, directory: &str) {
+        self.uc.init_interactive(directory);
         self.handle_event();
     }
 
@@ -484,9 +487,13 @@ impl Frontend {
 
             let mouse_state = self.event_pump.mouse_state();
             let (width, height) = self.renderer.get_dimensions();
+            let (screen_width, screen_height) = {
+                let screen = self.uc.screen.lock().unwrap();
+                (screen.width, screen.height)
+            };
 
-            let mouse_state_x = (mouse_state.x() as f32 * (400.0 / width as f32)) as i32;
-            let mouse_state_y = (mouse_state.y() as f32 * (240.0 / height as f32)) as i32;
+            let mouse_state_x = (mouse_state.x() as f32 * (screen_width as f32 / width as f32)) as i32;
+            let mouse_state_y = (mouse_state.y() as f32 * (screen_height as f32 / height as f32)) as i32;
 
             self.uc
                 .players
@@ -504,14 +511,15 @@ impl Frontend {
 
             for event in self.event_pump.poll_iter() {
                 match event {
-                    Event::Quit { .. } => break 'main,
+                    Event::Quit { .. } |
+                    Event::Window { win_event: WindowEvent::Close, .. } => {
+                        self.uc.stop();
+                        break 'main;
+                    }
                     Event::Window { win_event: WindowEvent::SizeChanged(_, _), .. } => {
                         // self.renderer
                         //    .update_viewport(&self.uc.screen.lock().unwrap());
                     }
-                    Event::Window { win_event: WindowEvent::Close, .. } => {
-                        break 'main;
-                    }
                     Event::MouseButtonDown { mouse_btn, .. } => {
                         let mut left = false;
                         let mut right = false;
@@ -582,6 +590,27 @@ impl Frontend {
                             self.uc.save_current_cartridge();
                         } else if scancode == Scancode::F6 || scancode == Scancode::AcBack {
                             self.uc.switch_code();
+                        } else if scancode == Scancode::F7 {
+                            self.uc.save_state(self.state_slot);
+                        } else if scancode == Scancode::F8 {
+                            self.uc.restore_state(self.state_slot);
+                        } else if scancode == Scancode::F9 {
+                            self.state_slot = (self.state_slot + 1) % STATE_SLOTS;
+                            info!("[Frontend] Quick save slot {:?}", self.state_slot);
+                        } else if scancode == Scancode::F10 {
+                            let dt = Utc::now();
+                            self.uc
+                                .save_png_cartridge(&("cartridge-".to_string() +
+                                                      &dt.format("%Y-%m-%d-%H-%M-%S.png").to_string()));
+                        } else if scancode == Scancode::F11 {
+                            if self.uc.is_recording_movie() || self.uc.is_replaying_movie() {
+                                self.uc.stop_movie();
+                            } else {
+                                let dt = Utc::now();
+                                self.uc
+                                    .start_movie(&("movie-".to_string() +
+                                                   &dt.format("%Y-%m-%d-%H-%M-%S.movie").to_string()));
+                            }
                         }
 
                         if self.uc.players.lock().unwrap().get_value_quick(0, 7) {
@@ -596,18 +625,49 @@ impl Frontend {
                             .key_up(map_sdlmod(keymod), map_sdlscancode(scancode));
                     }
 
+                    Event::ControllerDeviceAdded { which: id, .. } => {
+                        self.controllers.open_controller(&self.sdl, id as u32);
+                    }
+
+                    Event::ControllerDeviceRemoved { which: id, .. } => {
+                        if self.controllers.remove(id as u32) {
+                            self.uc.players.lock().unwrap().release_keys();
+                        }
+                    }
+
+                    Event::JoyDeviceAdded { which: id, .. } => {
+                        let game_controller = self.sdl
+                            .game_controller()
+                            .map(|subsystem| subsystem.is_game_controller(id as u32))
+                            .unwrap_or(false);
+
+                        // Game controllers are opened by ControllerDeviceAdded
+                        if !game_controller {
+                            self.controllers.open_joystick(&self.sdl, id as u32);
+                        }
+                    }
+
+                    Event::JoyDeviceRemoved { which: id, .. } => {
+                        if self.controllers.remove(id as u32) {
+                            self.uc.players.lock().unwrap().release_keys();
+                        }
+                    }
+
                     Event::ControllerButtonDown { which: id, button, .. } => {
                         if !self.controllers.is_controller(id as u32) {
                             break;
                         }
 
-                        if let Some(key) = map_button(button) {
+                        if let Some(player) = self.controllers.player(id as u32) {
                             self.uc
                                 .players
                                 .lock()
                                 .unwrap()
-                                .key_down_direct(0,
-                                                 key)
+                                .pad_button(player, button_name(button), true);
+
+                            if self.uc.players.lock().unwrap().get_value_quick(player, 7) {
+                                self.uc.switch_pause();
+                            }
                         }
                     }
 
@@ -616,8 +676,12 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button(button) {
-                            self.uc.players.lock().unwrap().key_up_direct(0, key);
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            self.uc
+                                .players
+                                .lock()
+                                .unwrap()
+                                .pad_button(player, button_name(button), false);
                         }
                     }
 
@@ -626,23 +690,10 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some((key, state)) = map_axis(axis, value) {
-                            if axis == Axis::LeftX && value == 128 {
-                                self.uc.players.lock().unwrap().key_direc_hor_up(0);
-                            } else if axis == Axis::LeftY && value == -129 {
-                                self.uc.players.lock().unwrap().key_direc_ver_up(0);
-                            } else {
-                                if state {
-                                    self.uc
-                                        .players
-                                        .lock()
-                                        .unwrap()
-                                        .key_down_direct(0,
-                                                         key);
-                                } else {
-                                    self.uc.players.lock().unwrap().key_up_direct(0, key);
-                                }
-                            }
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            let players = &mut self.uc.players.lock().unwrap();
+                            players.pad_axis(player, axis_name(axis), value);
+                            players.set_axis(player, axis_index(axis), value as f64 / 32767.0);
                         }
                     }
 
@@ -651,23 +702,10 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some((key, state)) = map_axis_joystick(axis_idx, value) {
-                            if axis_idx == 0 && value == 128 {
-                                self.uc.players.lock().unwrap().key_direc_hor_up(0);
-                            } else if axis_idx == 1 && value == -129 {
-                                self.uc.players.lock().unwrap().key_direc_ver_up(0);
-                            } else {
-                                if state {
-                                    self.uc
-                                        .players
-                                        .lock()
-                                        .unwrap()
-                                        .key_down_direct(0,
-                                                         key);
-                                } else {
-                                    self.uc.players.lock().unwrap().key_up_direct(0, key);
-                                }
-                            }
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            let players = &mut self.uc.players.lock().unwrap();
+                            players.pad_axis(player, &joystick_axis_name(axis_idx), value);
+                            players.set_axis(player, axis_idx as usize, value as f64 / 32767.0);
                         }
                     }
 
@@ -676,13 +714,16 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button_joystick(button_idx) {
+                        if let Some(player) = self.controllers.player(id as u32) {
                             self.uc
                                 .players
                                 .lock()
                                 .unwrap()
-                                .key_down_direct(0,
-                                                 key);
+                                .pad_button(player, &joystick_button_name(button_idx), true);
+
+                            if self.uc.players.lock().unwrap().get_value_quick(player, 7) {
+                                self.uc.switch_pause();
+                            }
                         }
                     }
 
@@ -691,8 +732,12 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button_joystick(button_idx) {
-                            self.uc.players.lock().unwrap().key_up_direct(0, key);
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            self.uc
+                                .players
+                                .lock()
+                                .unwrap()
+                                .pad_button(player, &joystick_button_name(button_idx), false);
                         }
                     }
 
@@ -731,9 +776,13 @@ impl Frontend {
 
             let mouse_state = self.event_pump.mouse_state();
             let (width, height) = self.renderer.get_dimensions();
+            let (screen_width, screen_height) = {
+                let screen = self.uc.screen.lock().unwrap();
+                (screen.width, screen.height)
+            };
 
-            let mouse_state_x = (mouse_state.x() as f32 * (400.0 / width as f32)) as i32;
-            let mouse_state_y = (mouse_state.y() as f32 * (240.0 / height as f32)) as i32;
+            let mouse_state_x = (mouse_state.x() as f32 * (screen_width as f32 / width as f32)) as i32;
+            let mouse_state_y = (mouse_state.y() as f32 * (screen_height as f32 / height as f32)) as i32;
 
             self.uc
                 .players
@@ -751,8 +800,9 @@ impl Frontend {
 
             for event in self.event_pump.poll_iter() {
                 match event {
-                    Event::Quit { .. } => return,
+                    Event::Quit { .. } |
                     Event::Window { win_event: WindowEvent::Close, .. } => {
+                        self.uc.stop();
                         return;
                     }
                     Event::MouseButtonDown { mouse_btn, .. } => {
@@ -824,6 +874,27 @@ impl Frontend {
                             self.uc.save_current_cartridge();
                         } else if scancode == Scancode::F6 || scancode == Scancode::AcBack {
                             self.uc.switch_code();
+                        } else if scancode == Scancode::F7 {
+                            self.uc.save_state(self.state_slot);
+                        } else if scancode == Scancode::F8 {
+                            self.uc.restore_state(self.state_slot);
+                        } else if scancode == Scancode::F9 {
+                            self.state_slot = (self.state_slot + 1) % STATE_SLOTS;
+                            info!("[Frontend] Quick save slot {:?}", self.state_slot);
+                        } else if scancode == Scancode::F10 {
+                            let dt = Utc::now();
+                            self.uc
+                                .save_png_cartridge(&("cartridge-".to_string() +
+                                                      &dt.format("%Y-%m-%d-%H-%M-%S.png").to_string()));
+                        } else if scancode == Scancode::F11 {
+                            if self.uc.is_recording_movie() || self.uc.is_replaying_movie() {
+                                self.uc.stop_movie();
+                            } else {
+                                let dt = Utc::now();
+                                self.uc
+                                    .start_movie(&("movie-".to_string() +
+                                                   &dt.format("%Y-%m-%d-%H-%M-%S.movie").to_string()));
+                            }
                         }
 
                         if self.uc.players.lock().unwrap().get_value_quick(0, 7) == 1 {
@@ -838,20 +909,49 @@ impl Frontend {
                             .key_up(map_sdlmod(keymod), map_sdlscancode(scancode));
                     }
 
+                    Event::ControllerDeviceAdded { which: id, .. } => {
+                        self.controllers.open_controller(&self.sdl, id as u32);
+                    }
+
+                    Event::ControllerDeviceRemoved { which: id, .. } => {
+                        if self.controllers.remove(id as u32) {
+                            self.uc.players.lock().unwrap().release_keys();
+                        }
+                    }
+
+                    Event::JoyDeviceAdded { which: id, .. } => {
+                        let game_controller = self.sdl
+                            .game_controller()
+                            .map(|subsystem| subsystem.is_game_controller(id as u32))
+                            .unwrap_or(false);
+
+                        // Game controllers are opened by ControllerDeviceAdded
+                        if !game_controller {
+                            self.controllers.open_joystick(&self.sdl, id as u32);
+                        }
+                    }
+
+                    Event::JoyDeviceRemoved { which: id, .. } => {
+                        if self.controllers.remove(id as u32) {
+                            self.uc.players.lock().unwrap().release_keys();
+                        }
+                    }
+
                     Event::ControllerButtonDown { which: id, button, .. } => {
                         if !self.controllers.is_controller(id as u32) {
                             break;
                         }
 
-                        if let Some(key) = map_button(button) {
+                        if let Some(player) = self.controllers.player(id as u32) {
                             self.uc
                                 .players
                                 .lock()
                                 .unwrap()
-                                .key_down_direct(0,
-                                                 key,
-                                                 false,
-                                                 self.uc.info.lock().unwrap().elapsed_time)
+                                .pad_button(player, button_name(button), true);
+
+                            if self.uc.players.lock().unwrap().get_value_quick(player, 7) {
+                                self.uc.switch_pause();
+                            }
                         }
                     }
 
@@ -860,8 +960,12 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button(button) {
-                            self.uc.players.lock().unwrap().key_up_direct(0, key);
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            self.uc
+                                .players
+                                .lock()
+                                .unwrap()
+                                .pad_button(player, button_name(button), false);
                         }
                     }
 
@@ -870,25 +974,10 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some((key, state)) = map_axis(axis, value) {
-                            if axis == Axis::LeftX && value == 128 {
-                                self.uc.players.lock().unwrap().key_direc_hor_up(0);
-                            } else if axis == Axis::LeftY && value == -129 {
-                                self.uc.players.lock().unwrap().key_direc_ver_up(0);
-                            } else {
-                                if state {
-                                    self.uc
-                                        .players
-                                        .lock()
-                                        .unwrap()
-                                        .key_down_direct(0,
-                                                         key,
-                                                         false,
-                                                         self.uc.info.lock().unwrap().elapsed_time);
-                                } else {
-                                    self.uc.players.lock().unwrap().key_up_direct(0, key);
-                                }
-                            }
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            let players = &mut self.uc.players.lock().unwrap();
+                            players.pad_axis(player, axis_name(axis), value);
+                            players.set_axis(player, axis_index(axis), value as f64 / 32767.0);
                         }
                     }
 
@@ -897,25 +986,10 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some((key, state)) = map_axis_joystick(axis_idx, value) {
-                            if axis_idx == 0 && value == 128 {
-                                self.uc.players.lock().unwrap().key_direc_hor_up(0);
-                            } else if axis_idx == 1 && value == -129 {
-                                self.uc.players.lock().unwrap().key_direc_ver_up(0);
-                            } else {
-                                if state {
-                                    self.uc
-                                        .players
-                                        .lock()
-                                        .unwrap()
-                                        .key_down_direct(0,
-                                                         key,
-                                                         false,
-                                                         self.uc.info.lock().unwrap().elapsed_time);
-                                } else {
-                                    self.uc.players.lock().unwrap().key_up_direct(0, key);
-                                }
-                            }
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            let players = &mut self.uc.players.lock().unwrap();
+                            players.pad_axis(player, &joystick_axis_name(axis_idx), value);
+                            players.set_axis(player, axis_idx as usize, value as f64 / 32767.0);
                         }
                     }
 
@@ -924,15 +998,16 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button_joystick(button_idx) {
+                        if let Some(player) = self.controllers.player(id as u32) {
                             self.uc
                                 .players
                                 .lock()
                                 .unwrap()
-                                .key_down_direct(0,
-                                                 key,
-                                                 false,
-                                                 self.uc.info.lock().unwrap().elapsed_time);
+                                .pad_button(player, &joystick_button_name(button_idx), true);
+
+                            if self.uc.players.lock().unwrap().get_value_quick(player, 7) {
+                                self.uc.switch_pause();
+                            }
                         }
                     }
 
@@ -941,8 +1016,12 @@ impl Frontend {
                             break;
                         }
 
-                        if let Some(key) = map_button_joystick(button_idx) {
-                            self.uc.players.lock().unwrap().key_up_direct(0, key);
+                        if let Some(player) = self.controllers.player(id as u32) {
+                            self.uc
+                                .players
+                                .lock()
+                                .unwrap()
+                                .pad_button(player, &joystick_button_name(button_idx), false);
                         }
                     }
 
@@ -968,7 +1047,13 @@ impl Frontend {
     }
 
     pub fn blit(&mut self) {
+        let (scale, fullscreen) = self.uc.get_display();
+        self.renderer.set_display(scale.and_then(Scale::from_factor), fullscreen);
+
         self.renderer.blit(&mut self.uc.screen.lock().unwrap());
+
+        // The cartridge can ask for another frame rate than 60 FPS
+        self.times.set_frame_duration(Duration::from_secs(1) / self.uc.target_fps());
         self.times.limit();
     }
 }
\ No newline at end of file
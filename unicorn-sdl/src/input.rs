@@ -1,73 +1,54 @@
-use unicorn::config::keys::PX8Key;
-
 use sdl2::controller::{Axis, Button};
 
-pub fn map_button(button: Button) -> Option<PX8Key> {
+/// Names of the controller buttons and axes in the bindings file, the same
+/// as the SDL game controller mappings.
+pub fn button_name(button: Button) -> &'static str {
     match button {
-        Button::DPadRight => Some(PX8Key::Right),
-        Button::DPadLeft => Some(PX8Key::Left),
-        Button::DPadUp => Some(PX8Key::Up),
-        Button::DPadDown => Some(PX8Key::Down),
-        Button::A => Some(PX8Key::A),
-        Button::B => Some(PX8Key::B),
-        _ => None,
+        Button::A => "a",
+        Button::B => "b",
+        Button::X => "x",
+        Button::Y => "y",
+        Button::Back => "back",
+        Button::Guide => "guide",
+        Button::Start => "start",
+        Button::LeftStick => "leftstick",
+        Button::RightStick => "rightstick",
+        Button::LeftShoulder => "leftshoulder",
+        Button::RightShoulder => "rightshoulder",
+        Button::DPadUp => "dpup",
+        Button::DPadDown => "dpdown",
+        Button::DPadLeft => "dpleft",
+        Button::DPadRight => "dpright",
     }
 }
 
-pub fn map_button_joystick(button: u8) -> Option<PX8Key> {
-    match button {
-        0 => Some(PX8Key::A),
-        1 => Some(PX8Key::B),
-        _ => None,
+pub fn axis_name(axis: Axis) -> &'static str {
+    match axis {
+        Axis::LeftX => "leftx",
+        Axis::LeftY => "lefty",
+        Axis::RightX => "rightx",
+        Axis::RightY => "righty",
+        Axis::TriggerLeft => "lefttrigger",
+        Axis::TriggerRight => "righttrigger",
     }
 }
 
-pub fn map_axis(axis: Axis, value: i16) -> Option<(PX8Key, bool)> {
-    match axis {
-        Axis::LeftX => {
-            match value {
-                -32768...-16384 => Some((PX8Key::Left, true)),
-                -16383...-1 => Some((PX8Key::Left, false)),
-                0...16383 => Some((PX8Key::Right, false)),
-                16384...32767 => Some((PX8Key::Right, true)),
-                _ => None,
-            }
-        }
+pub fn joystick_button_name(button: u8) -> String {
+    format!("button{}", button)
+}
 
-        Axis::LeftY => {
-            match value {
-                -32768...-16384 => Some((PX8Key::Up, true)),
-                -16383...-1 => Some((PX8Key::Up, false)),
-                0...16383 => Some((PX8Key::Down, false)),
-                16384...32767 => Some((PX8Key::Down, true)),
-                _ => None,
-            }
-        }
-        _ => None,
-    }
+pub fn joystick_axis_name(axis: u8) -> String {
+    format!("axis{}", axis)
 }
 
-pub fn map_axis_joystick(axis: u8, value: i16) -> Option<(PX8Key, bool)> {
+/// Index of an axis in the axis API, see unicorn::config::NB_AXES
+pub fn axis_index(axis: Axis) -> usize {
     match axis {
-        0 => {
-            match value {
-                -32768...-16384 => Some((PX8Key::Left, true)),
-                -16383...-1 => Some((PX8Key::Left, false)),
-                1...16383 => Some((PX8Key::Right, false)),
-                16384...32767 => Some((PX8Key::Right, true)),
-                _ => None,
-            }
-        }
-
-        1 => {
-            match value {
-                -32768...-16384 => Some((PX8Key::Up, true)),
-                -16383...-1 => Some((PX8Key::Up, false)),
-                1...16383 => Some((PX8Key::Down, false)),
-                16384...32767 => Some((PX8Key::Down, true)),
-                _ => None,
-            }
-        }
-        _ => None,
+        Axis::LeftX => 0,
+        Axis::LeftY => 1,
+        Axis::RightX => 2,
+        Axis::RightY => 3,
+        Axis::TriggerLeft => 4,
+        Axis::TriggerRight => 5,
     }
 }
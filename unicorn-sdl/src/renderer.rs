@@ -5,6 +5,7 @@ pub mod renderer {
     use sdl2::surface::Surface;
 
     use sdl2::VideoSubsystem;
+    use sdl2::video::FullscreenType;
     use sdl2::render;
     use sdl2::pixels::PixelFormatEnum;
     use time::PreciseTime;
@@ -24,6 +25,11 @@ pub mod renderer {
         pub texture: render::Texture,
         buffer_rgb: Vec<u8>,
         frame: u32,
+        // Size of the texture, it follows the resolution of the cartridge
+        width: usize,
+        height: usize,
+        fullscreen: bool,
+        scale: Scale,
     }
 
     impl Renderer {
@@ -77,10 +83,88 @@ pub mod renderer {
                 texture: texture,
                 buffer_rgb: vec![0; 0],
                 frame: 0,
+                width: screen.width,
+                height: screen.height,
+                fullscreen: fullscreen,
+                scale: scale,
             })
         }
 
+        /// Recreate the texture for a new resolution of the screen, the
+        /// window keeps the same scale factor.
+        fn resize(&mut self, screen: &Screen) {
+            info!("[SDL] Resize the texture to {:?}x{:?}", screen.width, screen.height);
+
+            match self.renderer.create_texture(PixelFormatEnum::RGB24,
+                                               render::TextureAccess::Streaming,
+                                               screen.width as u32,
+                                               screen.height as u32) {
+                Ok(texture) => self.texture = texture,
+                Err(e) => {
+                    error!("[SDL] Impossible to create the texture {:?}", e);
+                    return;
+                }
+            }
+
+            self.width = screen.width;
+            self.height = screen.height;
+
+            self.resize_window();
+        }
+
+        fn resize_window(&mut self) {
+            if !self.fullscreen {
+                let width = (self.width * self.scale.factor()) as u32;
+                let height = (self.height * self.scale.factor()) as u32;
+
+                if let Some(window) = self.renderer.window_mut() {
+                    if let Err(e) = window.set_size(width, height) {
+                        warn!("[SDL] Impossible to resize the window {:?}", e);
+                    }
+                }
+            }
+        }
+
+        pub fn get_display(&self) -> (Scale, bool) {
+            (self.scale, self.fullscreen)
+        }
+
+        /// Apply the scale and fullscreen chosen in the options of the
+        /// pause menu, None keeps the current value.
+        pub fn set_display(&mut self, scale: Option<Scale>, fullscreen: Option<bool>) {
+            let scale = scale.unwrap_or(self.scale);
+            let fullscreen = fullscreen.unwrap_or(self.fullscreen);
+
+            if scale.factor() == self.scale.factor() && fullscreen == self.fullscreen {
+                return;
+            }
+
+            info!("[SDL] Display scale={:?} fullscreen={:?}", scale.factor(), fullscreen);
+
+            if fullscreen != self.fullscreen {
+                let fullscreen_type = if fullscreen {
+                    FullscreenType::Desktop
+                } else {
+                    FullscreenType::Off
+                };
+
+                if let Some(window) = self.renderer.window_mut() {
+                    if let Err(e) = window.set_fullscreen(fullscreen_type) {
+                        warn!("[SDL] Impossible to switch the fullscreen {:?}", e);
+                    }
+                }
+            }
+
+            self.scale = scale;
+            self.fullscreen = fullscreen;
+            self.resize_window();
+        }
+
         pub fn blit(&mut self, screen: &mut Screen) {
+            if screen.width != self.width || screen.height != self.height {
+                self.resize(screen);
+            }
+
             // Translate the pixel values to RGB colors.
             let src_buffer = &screen.frame_buffer;
             let rgb_buffer_len = src_buffer.len() * 3;
@@ -88,24 +172,13 @@ pub mod renderer {
                 self.buffer_rgb = vec![0; rgb_buffer_len];
             }
             let rgb_buffer = &mut self.buffer_rgb;
-            let mut palette = unicorn::unicorn::PALETTE.lock().unwrap();
-
-            let mut j = 0;
-            let mut cached_pixel: u32 = 0;
-            let mut rgb = palette.get_rgb(cached_pixel as u32);
 
             let start = PreciseTime::now();
 
-            for pixel in src_buffer.iter() {
-                if *pixel != cached_pixel {
-                    rgb = palette.get_rgb(*pixel as u32);
-                    cached_pixel = *pixel;
-                }
-                rgb_buffer[j] = rgb.r;
-                rgb_buffer[j + 1] = rgb.g;
-                rgb_buffer[j + 2] = rgb.b;
-                j += 3;
-            }
+            unicorn::unicorn::PALETTE
+                .lock()
+                .unwrap()
+                .to_rgb(src_buffer, rgb_buffer);
 
             let t1 = PreciseTime::now();
 
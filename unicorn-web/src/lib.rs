@@ -23,8 +23,6 @@ use std::error::Error;
 
 static mut PREVIOUS_FRAME: f64 = 0.;
 
-static mut FRAMEBUFFER: [u32; 400 * 240] = [0; 400 * 240];
-
 use stdweb::web::{
     self,
     IEventTarget,
@@ -68,9 +66,27 @@ pub struct UnicornWeb {
     audio_underrun: Option< usize >,
     paused: bool,
     busy: bool,
+    // RGBA copy of the screen, and its size, which follows the cartridge resolution
+    framebuffer: Vec< u32 >,
+    width: usize,
+    height: usize,
     js_ctx: Value
 }
 
+fn ortho( left: f64, right: f64, bottom: f64, top: f64 ) -> Vec< f64 > {
+    let mut m = vec![ 1.0, 0.0, 0.0, 0.0,
+                      0.0, 1.0, 0.0, 0.0,
+                      0.0, 0.0, 1.0, 0.0,
+                      0.0, 0.0, 0.0, 1.0 ];
+
+    m[ 0 * 4 + 0 ] = 2.0 / (right - left);
+    m[ 1 * 4 + 1 ] = 2.0 / (top - bottom);
+    m[ 3 * 4 + 0 ] = (right + left) / (right - left) * -1.0;
+    m[ 3 * 4 + 1 ] = (top + bottom) / (top - bottom) * -1.0;
+
+    return m;
+}
+
 // This creates a really basic WebGL context for blitting a single texture.
 // On some web browsers this is faster than using a 2d canvas.
 fn setup_webgl( canvas: &Element ) -> Value {
@@ -94,20 +110,6 @@ fn setup_webgl( canvas: &Element ) -> Value {
         }
     "#;
 
-    fn ortho( left: f64, right: f64, bottom: f64, top: f64 ) -> Vec< f64 > {
-        let mut m = vec![ 1.0, 0.0, 0.0, 0.0,
-                          0.0, 1.0, 0.0, 0.0,
-                          0.0, 0.0, 1.0, 0.0,
-                          0.0, 0.0, 0.0, 1.0 ];
-
-        m[ 0 * 4 + 0 ] = 2.0 / (right - left);
-        m[ 1 * 4 + 1 ] = 2.0 / (top - bottom);
-        m[ 3 * 4 + 0 ] = (right + left) / (right - left) * -1.0;
-        m[ 3 * 4 + 1 ] = (top + bottom) / (top - bottom) * -1.0;
-
-        return m;
-    }
-
     js!(
         var gl;
         var webgl_names = ["webgl", "experimental-webgl", "webkit-3d", "moz-webgl"];
@@ -163,6 +165,7 @@ fn setup_webgl( canvas: &Element ) -> Value {
         var matrix = @{ortho( 0.0, 400.0, 240.0, 0.0 )};
         var matrix_uniform = gl.getUniformLocation( program, "u_matrix" );
         gl.uniformMatrix4fv( matrix_uniform, false, matrix );
+        gl.matrix_uniform = matrix_uniform;
 
         var texture = gl.createTexture();
         gl.bindTexture( gl.TEXTURE_2D, texture );
@@ -180,6 +183,7 @@ fn setup_webgl( canvas: &Element ) -> Value {
         ];
         gl.bufferData( gl.ARRAY_BUFFER, new Float32Array( vertices ), gl.STATIC_DRAW );
         gl.vertexAttribPointer( vertex_attr, 2, gl.FLOAT, false, 0, 0 );
+        gl.vertex_buffer = vertex_buffer;
 
         var texcoord_buffer = gl.createBuffer();
         gl.bindBuffer( gl.ARRAY_BUFFER, texcoord_buffer );
@@ -191,6 +195,7 @@ fn setup_webgl( canvas: &Element ) -> Value {
         ];
         gl.bufferData( gl.ARRAY_BUFFER, new Float32Array( texcoords ), gl.STATIC_DRAW );
         gl.vertexAttribPointer( texcoord_attr, 2, gl.FLOAT, false, 0, 0 );
+        gl.texcoord_buffer = texcoord_buffer;
 
         var index_buffer = gl.createBuffer();
         gl.bindBuffer( gl.ELEMENT_ARRAY_BUFFER, index_buffer );
@@ -247,6 +252,9 @@ impl UnicornWeb {
             audio_underrun: None,
             paused: false,
             busy: false,
+            framebuffer: Vec::new(),
+            width: 0,
+            height: 0,
             js_ctx
         }
     }
@@ -297,17 +305,69 @@ impl UnicornWeb {
         Ok(true)
     }
 
+    // The texture (or the image of the canvas) and the geometry follow the
+    // resolution of the cartridge, the texture keeps a power of two size.
+    fn resize( &mut self, width: usize, height: usize ) {
+        js!( console.log( "Resize the screen to", @{width as u32}, @{height as u32} ); );
+
+        self.width = width;
+        self.height = height;
+        self.framebuffer = vec![0; width * height];
+
+        let texture_size = max( max( width, height ).next_power_of_two(), 512 ) as u32;
+
+        js! {
+            var h = @{&self.js_ctx};
+            var width = @{width as u32};
+            var height = @{height as u32};
+            var size = @{texture_size};
+            if( h.gl ) {
+                var gl = h.gl;
+                gl.texImage2D( gl.TEXTURE_2D, 0, gl.RGBA, size, size, 0, gl.RGBA, gl.UNSIGNED_BYTE, new Uint8Array( size * size * 4 ) );
+                gl.uniformMatrix4fv( gl.matrix_uniform, false, @{ortho( 0.0, width as f64, height as f64, 0.0 )} );
+
+                gl.bindBuffer( gl.ARRAY_BUFFER, gl.vertex_buffer );
+                var vertices = [
+                    0.0, 0.0,
+                    0.0, height,
+                    width, 0.0,
+                    width, height
+                ];
+                gl.bufferData( gl.ARRAY_BUFFER, new Float32Array( vertices ), gl.STATIC_DRAW );
+
+                gl.bindBuffer( gl.ARRAY_BUFFER, gl.texcoord_buffer );
+                var texcoords = [
+                    0.0, 0.0,
+                    0.0, height / size,
+                    width / size, 0.0,
+                    width / size, height / size
+                ];
+                gl.bufferData( gl.ARRAY_BUFFER, new Float32Array( texcoords ), gl.STATIC_DRAW );
+            } else {
+                h.img = h.ctx.createImageData( width, height );
+                h.buffer = new Uint32Array( h.img.data.buffer );
+            }
+        }
+    }
+
     fn draw( &mut self ) {
-        let mut palette = unicorn::unicorn::PALETTE.lock().unwrap();
-        let framebuffer = &self.state.screen.lock().unwrap().frame_buffer;
-
-        let mut i = 0;
-        for pixel_in in framebuffer.iter() {
-            let rgb = palette.get_rgb(*pixel_in as u32);
-            unsafe {
-                FRAMEBUFFER[i] = ((rgb.b as u32) << 16) | ((rgb.g as u32) << 8) | ((rgb.r as u32)) | 0xFF000000;
+        let (width, height) = {
+            let screen = self.state.screen.lock().unwrap();
+            (screen.width, screen.height)
+        };
+
+        if width != self.width || height != self.height {
+            self.resize( width, height );
+        }
+
+        {
+            let palette = unicorn::unicorn::PALETTE.lock().unwrap();
+            let screen = self.state.screen.lock().unwrap();
+
+            for (pixel_in, pixel_out) in screen.frame_buffer.iter().zip( self.framebuffer.iter_mut() ) {
+                let rgb = palette.get_rgb(*pixel_in);
+                *pixel_out = ((rgb.b as u32) << 16) | ((rgb.g as u32) << 8) | ((rgb.r as u32)) | 0xFF000000;
             }
-            i += 1;
         }
 
         if !self.paused {
@@ -318,10 +378,10 @@ impl UnicornWeb {
 
         js! {
             var h = @{&self.js_ctx};
-            var framebuffer = @{unsafe { UnsafeTypedArray::new( &FRAMEBUFFER ) }};
+            var framebuffer = @{unsafe { UnsafeTypedArray::new( &self.framebuffer ) }};
             if( h.gl ) {
                 var data = new Uint8Array( framebuffer.buffer, framebuffer.byteOffset, framebuffer.byteLength );
-                h.gl.texSubImage2D( h.gl.TEXTURE_2D, 0, 0, 0, 400, 240, h.gl.RGBA, h.gl.UNSIGNED_BYTE, data );
+                h.gl.texSubImage2D( h.gl.TEXTURE_2D, 0, 0, 0, @{width as u32}, @{height as u32}, h.gl.RGBA, h.gl.UNSIGNED_BYTE, data );
                 h.gl.drawElements( h.gl.TRIANGLES, 6, h.gl.UNSIGNED_SHORT, 0 );
             } else {
                 h.buffer.set( framebuffer );
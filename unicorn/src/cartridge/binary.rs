@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use unicorn;
+
+use gfx::{to_color, Sprite, MAP_LAYERS, MAP_MAX_VALUE};
+
+use cartridge::{Cartridge, CartridgeCode, CartridgeConfig, CartridgeFont, CartridgeFormat,
+                CartridgeGFF, CartridgeGFX, CartridgeMap, CartridgeMusic, CartridgePalette, CartridgeTTF,
+                Error, MAX_FONTS};
+
+/* BINARY CART FORMAT (little endian)
+
+magic "UNB\0"
+version u16
+number of sections u16
+
+section table, one entry per section:
+    tag [u8; 4]
+    encoding u8    => 0: raw bytes, 1: RLE of u16 values
+    size u32       => size of the decoded data in bytes
+    length u32     => size of the stored data in bytes
+    offset u32     => from the start of the file
+
+section data
+
+HEAD VERS LANG CODE PAL CONF: UTF-8 text, same content as the .uni sections
+GFX : 64 u16 colors per sprite
+GFF : one u8 of flags per sprite
+MAP0 MAP1 ...: MAP_WIDTH * MAP_HEIGHT u16 per map layer
+FNT0 FNT1 ...: UTF-8 text, BDF font of the .uni __font__ sections
+TTF0 TTF1 ...: name of the font, '\n', content of the TTF/OTF file
+
+*/
+
+pub static MAGIC: &'static [u8; 4] = b"UNB\0";
+pub const VERSION: u16 = 1;
+
+const ENCODING_RAW: u8 = 0;
+const ENCODING_RLE: u8 = 1;
+
+const HEADER_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 17;
+
+struct Section {
+    tag: [u8; 4],
+    encoding: u8,
+    size: u32,
+    data: Vec<u8>,
+}
+
+impl Section {
+    fn raw(tag: &[u8; 4], data: Vec<u8>) -> Section {
+        Section {
+            tag: *tag,
+            encoding: ENCODING_RAW,
+            size: data.len() as u32,
+            data: data,
+        }
+    }
+
+    fn rle(tag: &[u8; 4], values: &[u16]) -> Section {
+        Section {
+            tag: *tag,
+            encoding: ENCODING_RLE,
+            size: (values.len() * 2) as u32,
+            data: rle_encode(values),
+        }
+    }
+}
+
+fn map_tag(layer: usize) -> [u8; 4] {
+    [b'M', b'A', b'P', b'0' + layer as u8]
+}
+
+fn font_tag(idx: usize) -> [u8; 4] {
+    [b'F', b'N', b'T', b'0' + idx as u8]
+}
+
+fn ttf_tag(idx: usize) -> [u8; 4] {
+    [b'T', b'T', b'F', b'0' + idx as u8]
+}
+
+/// Runs of identical values, stored as (count u16, value u16) pairs
+fn rle_encode(values: &[u16]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    let mut idx = 0;
+    while idx < values.len() {
+        let value = values[idx];
+
+        let mut count = 1;
+        while idx + count < values.len() && values[idx + count] == value && count < 0xFFFF {
+            count += 1;
+        }
+
+        data.write_u16::<LittleEndian>(count as u16).unwrap();
+        data.write_u16::<LittleEndian>(value).unwrap();
+
+        idx += count;
+    }
+
+    data
+}
+
+/// The size comes from the file, it can't be bigger than `max_size`, the
+/// size expected for the content of the section
+fn rle_decode(data: &[u8], size: usize, max_size: usize) -> Result<Vec<u8>, Error> {
+    if size > max_size {
+        return Err(Error::Err(format!("RLE section size {:?} bigger than {:?}", size, max_size)));
+    }
+
+    let mut output = Vec::with_capacity(size);
+    let mut cursor = Cursor::new(data);
+
+    while (cursor.position() as usize) < data.len() {
+        let count = try!(cursor.read_u16::<LittleEndian>());
+        let value = try!(cursor.read_u16::<LittleEndian>());
+
+        for _ in 0..count {
+            try!(output.write_u16::<LittleEndian>(value));
+        }
+
+        if output.len() > size {
+            return Err(Error::Err("RLE section bigger than expected".to_string()));
+        }
+    }
+
+    if output.len() != size {
+        return Err(Error::Err(format!("RLE section size {:?} instead of {:?}",
+                                      output.len(),
+                                      size)));
+    }
+
+    Ok(output)
+}
+
+fn to_u16(data: &[u8]) -> Vec<u16> {
+    let mut values = Vec::with_capacity(data.len() / 2);
+    let mut cursor = Cursor::new(data);
+
+    while let Ok(value) = cursor.read_u16::<LittleEndian>() {
+        values.push(value);
+    }
+
+    values
+}
+
+fn to_lines(data: &[u8]) -> Result<Vec<String>, Error> {
+    match String::from_utf8(data.to_vec()) {
+        Ok(s) => Ok(s.lines().map(|l| l.to_string()).collect()),
+        Err(e) => Err(Error::Err(format!("Invalid UTF-8 section {:?}", e))),
+    }
+}
+
+pub fn is_binary(data: &[u8]) -> bool {
+    data.len() >= MAGIC.len() && &data[..MAGIC.len()] == &MAGIC[..]
+}
+
+pub fn write<W: Write>(cartridge: &mut Cartridge, output: &mut W) -> io::Result<()> {
+    let mut sections = Vec::new();
+
+    sections.push(Section::raw(b"HEAD", cartridge.header.clone().into_bytes()));
+    sections.push(Section::raw(b"VERS", cartridge.version.clone().into_bytes()));
+    sections.push(Section::raw(b"LANG", cartridge.code.code_type.clone().into_bytes()));
+    sections.push(Section::raw(b"CODE", cartridge.code.get_data().into_bytes()));
+    sections.push(Section::raw(b"PAL ", cartridge.palette.get_data().into_bytes()));
+    sections.push(Section::raw(b"CONF", cartridge.config.get_data().into_bytes()));
+
+    let mut gfx = Vec::with_capacity(cartridge.gfx.sprites.len() * 64);
+    for sprite in &cartridge.gfx.sprites {
+        gfx.extend(sprite.data.iter().map(|c| *c as u16));
+    }
+    sections.push(Section::rle(b"GFX ", &gfx));
+
+    sections.push(Section::raw(b"GFF ", cartridge.gff.flags.clone()));
+
+    for (layer, map) in cartridge.map.map.iter().take(MAP_LAYERS).enumerate() {
+        let values: Vec<u16> = map.iter().map(|v| *v as u16).collect();
+        sections.push(Section::rle(&map_tag(layer), &values));
+    }
+
+    for (idx, lines) in cartridge.font.fonts.iter().take(MAX_FONTS).enumerate() {
+        sections.push(Section::raw(&font_tag(idx), lines.join("\n").into_bytes()));
+    }
+
+    for (idx, &(ref name, ref bytes)) in cartridge.ttf.fonts.iter().take(MAX_FONTS).enumerate() {
+        let mut data = name.clone().into_bytes();
+        data.push(b'\n');
+        data.extend(bytes);
+        sections.push(Section::raw(&ttf_tag(idx), data));
+    }
+
+    try!(output.write_all(&MAGIC[..]));
+    try!(output.write_u16::<LittleEndian>(VERSION));
+    try!(output.write_u16::<LittleEndian>(sections.len() as u16));
+
+    let mut offset = HEADER_SIZE + sections.len() * ENTRY_SIZE;
+    for section in &sections {
+        try!(output.write_all(&section.tag));
+        try!(output.write_u8(section.encoding));
+        try!(output.write_u32::<LittleEndian>(section.size));
+        try!(output.write_u32::<LittleEndian>(section.data.len() as u32));
+        try!(output.write_u32::<LittleEndian>(offset as u32));
+
+        offset += section.data.len();
+    }
+
+    for section in &sections {
+        try!(output.write_all(&section.data));
+    }
+
+    Ok(())
+}
+
+pub fn read(filename: &str, data: &[u8]) -> Result<Cartridge, Error> {
+    if !is_binary(data) {
+        return Err(Error::Err("Not a binary cartridge".to_string()));
+    }
+
+    let mut cursor = Cursor::new(data);
+    let mut magic = [0; 4];
+    try!(cursor.read_exact(&mut magic));
+
+    let version = try!(cursor.read_u16::<LittleEndian>());
+    if version > VERSION {
+        return Err(Error::Err(format!("Unsupported binary cartridge version {:?}", version)));
+    }
+
+    let nb_sections = try!(cursor.read_u16::<LittleEndian>());
+
+    let mut sections: HashMap<[u8; 4], (u8, usize, &[u8])> = HashMap::new();
+    for _ in 0..nb_sections {
+        let mut tag = [0; 4];
+        try!(cursor.read_exact(&mut tag));
+        let encoding = try!(cursor.read_u8());
+        let size = try!(cursor.read_u32::<LittleEndian>()) as usize;
+        let length = try!(cursor.read_u32::<LittleEndian>()) as usize;
+        let offset = try!(cursor.read_u32::<LittleEndian>()) as usize;
+
+        let end = match offset.checked_add(length) {
+            Some(end) if end <= data.len() => end,
+            _ => {
+                return Err(Error::Err(format!("Section {:?} out of the file",
+                                              String::from_utf8_lossy(&tag))))
+            }
+        };
+
+        if encoding != ENCODING_RAW && encoding != ENCODING_RLE {
+            return Err(Error::Err(format!("Unknown encoding {:?} for section {:?}",
+                                          encoding,
+                                          String::from_utf8_lossy(&tag))));
+        }
+
+        sections.insert(tag, (encoding, size, &data[offset..end]));
+    }
+
+    // The RLE sections (u16 values) are decoded up to `max_size` bytes, the
+    // size expected for their content, the other ones are raw
+    let decode = |tag: &[u8; 4], max_size: usize| -> Result<Option<Vec<u8>>, Error> {
+        let (encoding, size, stored) = match sections.get(tag) {
+            Some(section) => *section,
+            None => return Ok(None),
+        };
+
+        let decoded = if encoding == ENCODING_RLE {
+            try!(rle_decode(stored, size, max_size))
+        } else {
+            stored.to_vec()
+        };
+
+        debug!("[CARTRIDGE][BINARY] {:?}: {:?} -> {:?}",
+               String::from_utf8_lossy(tag),
+               stored.len(),
+               decoded.len());
+
+        Ok(Some(decoded))
+    };
+
+    let text = |tag: &[u8; 4]| -> Result<String, Error> {
+        match try!(decode(tag, 0)) {
+            Some(data) => Ok(String::from_utf8_lossy(&data).to_string()),
+            None => Ok("".to_string()),
+        }
+    };
+
+    let code_type = try!(text(b"LANG"));
+    if code_type.is_empty() {
+        return Err(Error::MissingSection("LANG".to_string()));
+    }
+
+    let mut cartridge_code = match try!(decode(b"CODE", 0)) {
+        Some(data) => CartridgeCode::new(code_type, &try!(to_lines(&data))),
+        None => CartridgeCode::new(code_type, &Vec::new()),
+    };
+    cartridge_code.set_filename(filename);
+
+    let cartridge_palette = match try!(decode(b"PAL ", 0)) {
+        Some(data) => try!(CartridgePalette::new(&try!(to_lines(&data)))),
+        None => CartridgePalette::empty(),
+    };
+
+    let cartridge_config = match try!(decode(b"CONF", 0)) {
+        Some(data) => try!(CartridgeConfig::new(&try!(to_lines(&data)))),
+        None => CartridgeConfig::empty(),
+    };
+
+    let mut sprites = Vec::new();
+    if let Some(data) = try!(decode(b"GFX ", cartridge_config.nb_sprites() * 64 * 2)) {
+        for chunk in to_u16(&data).chunks(64) {
+            let mut sprite_data = [0; 64];
+            for (idx, c) in chunk.iter().enumerate() {
+                sprite_data[idx] = match to_color(*c as u32) {
+                    Some(color) => color,
+                    None => {
+                        return Err(Error::Err(format!("Color {:?} of the sprite {:?} outside of the palette",
+                                                      c,
+                                                      sprites.len())))
+                    }
+                };
+            }
+            sprites.push(Sprite::new(sprite_data));
+        }
+    }
+
+    let cartridge_gff = match try!(decode(b"GFF ", 0)) {
+        Some(data) => CartridgeGFF { flags: data },
+        None => CartridgeGFF::empty(),
+    };
+
+    let mut map = Vec::new();
+    let map_size = unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT * 2;
+    for layer in 0..MAP_LAYERS {
+        if let Some(data) = try!(decode(&map_tag(layer), map_size)) {
+            let mut values: Vec<u32> = to_u16(&data).iter().map(|v| *v as u32).collect();
+            if let Some(value) = values.iter().find(|v| **v > MAP_MAX_VALUE) {
+                return Err(Error::Err(format!("Map value {:?} bigger than {:?}", value, MAP_MAX_VALUE)));
+            }
+            values.resize(unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT, 0);
+
+            map.resize(layer, vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT]);
+            map.push(values);
+        }
+    }
+
+    let cartridge_map = if map.is_empty() {
+        CartridgeMap::empty()
+    } else {
+        CartridgeMap { map: map }
+    };
+
+    let mut fonts = Vec::new();
+    for idx in 0..MAX_FONTS {
+        if let Some(data) = try!(decode(&font_tag(idx), 0)) {
+            fonts.push(try!(to_lines(&data)));
+        }
+    }
+
+    let mut ttf_fonts = Vec::new();
+    for idx in 0..MAX_FONTS {
+        if let Some(data) = try!(decode(&ttf_tag(idx), 0)) {
+            match data.iter().position(|c| *c == b'\n') {
+                Some(end) => {
+                    let name = String::from_utf8_lossy(&data[..end]).to_string();
+                    ttf_fonts.push((name, data[end + 1..].to_vec()));
+                }
+                None => return Err(Error::Err("Missing name of the TTF font".to_string())),
+            }
+        }
+    }
+
+    Ok(Cartridge {
+           filename: filename.to_string(),
+           data_filename: "".to_string(),
+           header: try!(text(b"HEAD")),
+           version: try!(text(b"VERS")),
+           config: cartridge_config,
+           gfx: CartridgeGFX { sprites: sprites },
+           code: cartridge_code,
+           palette: cartridge_palette,
+           map: cartridge_map,
+           gff: cartridge_gff,
+           music: CartridgeMusic::empty(),
+           font: CartridgeFont { fonts: fonts },
+           ttf: CartridgeTTF { fonts: ttf_fonts },
+           format: CartridgeFormat::UnicornBinaryFormat,
+       })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfx::map_index;
+
+    #[test]
+    fn test_rle_decode_size() {
+        let data = rle_encode(&[7, 7, 7, 1]);
+        assert_eq!(to_u16(&rle_decode(&data, 8, 8).unwrap()), vec![7, 7, 7, 1]);
+
+        assert!(rle_decode(&data, 6, 8).is_err());
+        assert!(rle_decode(&data, 10, 16).is_err());
+        assert!(rle_decode(&data, 8, 6).is_err());
+        assert!(rle_decode(&data, 0xFFFF_FFFF, 8).is_err());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut cartridge = Cartridge::empty();
+        cartridge.header = "Saved by unicorn\n".to_string();
+        cartridge.code = CartridgeCode::new("lua".to_string(),
+                                            &vec!["function _draw()".to_string(),
+                                                  "  cls()".to_string(),
+                                                  "end".to_string()]);
+
+        let mut sprite_data = [0; 64];
+        sprite_data[9] = 0xab;
+        cartridge.gfx.sprites = vec![Sprite::new([0; 64]), Sprite::new(sprite_data)];
+        cartridge.gff.flags = vec![0, 131];
+        cartridge.config.width = 128;
+
+        let mut layer = vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT];
+        layer[map_index(5, 2)] = 1;
+        cartridge.map.map = vec![vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT], layer];
+        cartridge.font.fonts = vec![vec!["STARTFONT 2.1".to_string(), "ENDFONT".to_string()]];
+        cartridge.ttf.fonts = vec![("sans".to_string(), vec![0, 1, 10, 255])];
+
+        let mut data = Vec::new();
+        write(&mut cartridge, &mut data).unwrap();
+        assert!(is_binary(&data));
+        assert!(data.len() < 1024);
+
+        let mut loaded = read("test.unb", &data).unwrap();
+        assert_eq!(loaded.header, cartridge.header);
+        assert_eq!(loaded.code.get_data(), cartridge.code.get_data());
+        assert_eq!(loaded.config.width, 128);
+        assert_eq!(loaded.config.height, 240);
+        assert_eq!(loaded.gfx.sprites.len(), 2);
+        assert_eq!(loaded.gfx.sprites[1].data[9], 0xab);
+        assert_eq!(loaded.gff.flags, vec![0, 131]);
+        assert_eq!(loaded.map.map.len(), 2);
+        assert_eq!(loaded.map.map[1][map_index(5, 2)], 1);
+        assert_eq!(loaded.font.fonts, cartridge.font.fonts);
+        assert_eq!(loaded.ttf.fonts, cartridge.ttf.fonts);
+
+        assert!(read("test.unb", &data[..20]).is_err());
+
+        // Sizes of the section table not matching the content
+        let entry = HEADER_SIZE + 6 * ENTRY_SIZE;
+        assert_eq!(&data[entry..entry + 4], b"GFX ");
+
+        let mut bad = data.clone();
+        bad[entry + 5..entry + 9].copy_from_slice(&[0xFF; 4]);
+        assert!(read("test.unb", &bad).is_err());
+
+        let mut bad = data.clone();
+        bad[entry + 9..entry + 17].copy_from_slice(&[0xFF; 8]);
+        assert!(read("test.unb", &bad).is_err());
+    }
+}
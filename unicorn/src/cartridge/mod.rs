@@ -1,3 +1,7 @@
+pub mod binary;
+pub mod png;
+pub mod p8;
+
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Cursor;
@@ -11,6 +15,7 @@ use std::io::Write;
 
 use std::result::Result;
 use std::collections::HashMap;
+use std::path::Path;
 use std::u32;
 use std::str;
 
@@ -19,9 +24,10 @@ use serde_json;
 use regex::Regex;
 
 use unicorn;
-use unicorn::RGB;
+use unicorn::{RGB, SCREEN_WIDTH, SCREEN_HEIGHT};
 
-use gfx::Sprite;
+use gfx::{to_color, Color, Sprite, TtfFont};
+use gfx::{map_index, MAP_LAYERS};
 
 /* CART FORMAT
 
@@ -29,9 +35,14 @@ RANDOM COMMENT
 version XX
 __python__ __javascript__ __lua__
 
+__config__
+
+width 320       <- optional, resolution of the screen (400x240 by default),
+height 200         multiples of 8 between 64 and 1024
+
 __palette__
 
-__gfx__ 400x240 -> 1500
+__gfx__ 400x240 -> 1500, the sprite sheet has the resolution of the screen
 
 XXXXXXXX
 XXXXXXXX
@@ -47,6 +58,19 @@ __gff__
 
 __map__ 400*60 -> 24.000
 
+__map1__ __map2__ __map3__: optional extra map layers, same layout
+
+__font__ __font1__ ... __font7__: optional BDF bitmap fonts, selected with
+font() or "font" in __config__ by their FAMILY_NAME (FONT otherwise)
+
+__ttf__ __ttf1__ ... __ttf7__: optional TTF/OTF fonts used by print_ttf
+
+name            <- used by font_ttf()
+XXXXXXXX...     => the bytes of the file, 64 per line in hexadecimal
+
+See binary.rs for the compact binary version of this format, and png.rs to
+hide it in an image. PICO-8 cartridges are imported by p8.rs.
+
 __sfx__
 
 __music__
@@ -122,26 +146,31 @@ impl CartridgePalette {
         CartridgePalette { colors: HashMap::new() }
     }
 
-    pub fn new(lines: &[String]) -> CartridgePalette {
+    pub fn new(lines: &[String]) -> Result<CartridgePalette, Error> {
         let mut colors = HashMap::new();
 
-        let data = "".to_string();
-
-        for line in lines {
+        for (y, line) in lines.iter().enumerate() {
             let split_line = line.split(" ");
             let vec: Vec<&str> = split_line.collect();
 
             if vec.len() == 4 {
-                let color = vec[0].parse::<u32>().unwrap();
-                let r = vec[1].parse::<u8>().unwrap();
-                let g = vec[2].parse::<u8>().unwrap();
-                let b = vec[3].parse::<u8>().unwrap();
+                let bad_value = || {
+                    Error::BadValue {
+                        section: "__palette__".to_string(),
+                        line: y + 1,
+                    }
+                };
+
+                let color = try!(vec[0].parse::<u32>().map_err(|_| bad_value()));
+                let r = try!(vec[1].parse::<u8>().map_err(|_| bad_value()));
+                let g = try!(vec[2].parse::<u8>().map_err(|_| bad_value()));
+                let b = try!(vec[3].parse::<u8>().map_err(|_| bad_value()));
 
                 colors.insert(color, RGB::new(r, g, b));
             }
         }
 
-        CartridgePalette { colors: colors }
+        Ok(CartridgePalette { colors: colors })
     }
 
     pub fn get_data(&mut self) -> String {
@@ -255,6 +284,167 @@ impl CartridgeCode {
     }
 }
 
+/// Description of the cartridge and console settings it wants, from the
+/// `__config__` section. The sprite sheet has the resolution of the screen,
+/// so it gives the number of sprites per row too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeConfig {
+    pub title: String,
+    pub author: String,
+    /// Several `description` lines are joined by '\n'
+    pub description: String,
+    pub license: String,
+    /// Frames per second wanted by the cartridge, applied by the frontends
+    pub fps: u32,
+    pub width: usize,
+    pub height: usize,
+    /// Palette and font selected when the cartridge starts
+    pub palette: String,
+    pub font: String,
+}
+
+const MIN_RESOLUTION: usize = 64;
+const MAX_RESOLUTION: usize = 1024;
+const MAX_FPS: u32 = 240;
+
+/// Settings that can be given in `__config__`, the order used to save them
+pub const CONFIG_KEYS: [&'static str; 9] = ["title",
+                                             "author",
+                                             "description",
+                                             "license",
+                                             "fps",
+                                             "width",
+                                             "height",
+                                             "palette",
+                                             "font"];
+
+impl CartridgeConfig {
+    pub fn empty() -> CartridgeConfig {
+        CartridgeConfig {
+            title: "".to_string(),
+            author: "".to_string(),
+            description: "".to_string(),
+            license: "".to_string(),
+            fps: 60,
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            palette: "pico-8".to_string(),
+            font: "pico-8".to_string(),
+        }
+    }
+
+    pub fn new(lines: &[String]) -> Result<CartridgeConfig, Error> {
+        info!("[CARTRIDGE] CartridgeConfig");
+
+        let mut config = CartridgeConfig::empty();
+
+        for (y, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            let key = match line.split_whitespace().next() {
+                Some(key) => key,
+                None => continue,
+            };
+            let value = line[key.len()..].trim();
+
+            let bad_value = || {
+                Error::BadValue {
+                    section: "__config__".to_string(),
+                    line: y + 1,
+                }
+            };
+
+            match key {
+                "title" => config.title = value.to_string(),
+                "author" => config.author = value.to_string(),
+                "description" => {
+                    if !config.description.is_empty() {
+                        config.description.push('\n');
+                    }
+                    config.description.push_str(value);
+                }
+                "license" => config.license = value.to_string(),
+                "fps" => {
+                    config.fps = match value.parse::<u32>() {
+                        Ok(fps) if fps > 0 && fps <= MAX_FPS => fps,
+                        _ => return Err(bad_value()),
+                    }
+                }
+                "width" | "height" => {
+                    let size = match value.parse::<usize>() {
+                        Ok(size) if size % 8 == 0 && size >= MIN_RESOLUTION &&
+                                    size <= MAX_RESOLUTION => size,
+                        _ => return Err(bad_value()),
+                    };
+
+                    if key == "width" {
+                        config.width = size;
+                    } else {
+                        config.height = size;
+                    }
+                }
+                "palette" if !value.is_empty() => config.palette = value.to_string(),
+                "font" if !value.is_empty() => config.font = value.to_string(),
+                _ => warn!("[CARTRIDGE] Unknown setting {:?} in __config__", line),
+            }
+        }
+
+        info!("[CARTRIDGE] {:?} by {:?}, {:?}x{:?}",
+              config.title,
+              config.author,
+              config.width,
+              config.height);
+
+        Ok(config)
+    }
+
+    pub fn sprites_per_row(&self) -> usize {
+        self.width / 8
+    }
+
+    pub fn nb_sprites(&self) -> usize {
+        (self.width / 8) * (self.height / 8)
+    }
+
+    /// The section is only saved when something is different from the default
+    pub fn is_default(&self) -> bool {
+        *self == CartridgeConfig::empty()
+    }
+
+    /// Value of a setting as text, for the scripts
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "title" => Some(self.title.clone()),
+            "author" => Some(self.author.clone()),
+            "description" => Some(self.description.clone()),
+            "license" => Some(self.license.clone()),
+            "fps" => Some(self.fps.to_string()),
+            "width" => Some(self.width.to_string()),
+            "height" => Some(self.height.to_string()),
+            "palette" => Some(self.palette.clone()),
+            "font" => Some(self.font.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_data(&self) -> String {
+        let mut data = String::new();
+
+        let default = CartridgeConfig::empty();
+        for key in CONFIG_KEYS.iter() {
+            let value = self.get(key).unwrap();
+            if value.is_empty() || Some(value.clone()) == default.get(key) {
+                continue;
+            }
+
+            for line in value.lines() {
+                data.push_str(&format!("{} {}\n", key, line));
+            }
+        }
+
+        data
+    }
+}
+
 pub struct CartridgeGFX {
     pub sprites: Vec<Sprite>,
 }
@@ -276,44 +466,57 @@ impl CartridgeGFX {
         CartridgeGFX { sprites:  Vec::new() }
     }
 
-    pub fn new(lines: &[String]) -> CartridgeGFX {
+    pub fn new(lines: &[String], config: &CartridgeConfig) -> Result<CartridgeGFX, Error> {
         info!("[CARTRIDGE][CartridgeGFX]");
 
         let mut sprites: Vec<Sprite> = Vec::new();
 
         if !lines.is_empty() {
-            let mut nbsprites = 0;
             let mut v = Vec::new();
 
-            for line in lines {
-                if line.len() > 1200 {
+            // 3 hexadecimal digits per pixel
+            let line_len = config.width * 3;
+
+            for (y, line) in lines.iter().enumerate() {
+                if line.len() > line_len {
                     continue;
                 }
 
                 let mut i = 0;
-                while i < 1200 {
-                    let value = u32::from_str_radix(&line[i..i + 3], 16).unwrap();
-                    //info!("VAL {:?} {:?}", v, z);
+                while i < line_len {
+                    let value = try!(parse_hex("__gfx__", line, y, i, 3));
+
+                    // Only the colors of the palette
+                    match to_color(value) {
+                        Some(color) => v.push(color),
+                        None => {
+                            return Err(Error::BadHex {
+                                           section: "__gfx__".to_string(),
+                                           line: y + 1,
+                                           column: i + 1,
+                                       })
+                        }
+                    }
 
-                    v.push(value);
-                    
                     i += 3;
-                    nbsprites += 1;
                 }
             }
 
             info!("[CARTRIDGE][CartridgeGFX] {:?}", v.len());
 
+            // Missing lines are empty
+            v.resize(config.width * config.height, 0);
+
             let mut g_off = 0;
 
             // Fill all sprites
-            for idx in 0..1500 {
-                let mut data: [u32; 8 * 8] = [0; 8 * 8];
+            for idx in 0..config.nb_sprites() {
+                let mut data: [Color; 8 * 8] = [0; 8 * 8];
 
                 let mut idx_vec = 0;
 
                 if idx > 0 {
-                    if idx % 50 == 0 {
+                    if idx % config.sprites_per_row() == 0 {
                         g_off = idx * 8 * 8;
                     } else {
                         g_off += 8;
@@ -322,7 +525,7 @@ impl CartridgeGFX {
 
                 for y in 0..8 {
                     for x in 0..8 {
-                        let offset = g_off + y * 400 + x;
+                        let offset = g_off + y * config.width + x;
 
                         data[idx_vec] = v[offset];
                         idx_vec += 1;
@@ -335,30 +538,32 @@ impl CartridgeGFX {
             info!("[CARTRIDGE][CartridgeGFX] {:?}", sprites.len());
         }
 
-        CartridgeGFX { sprites: sprites }
+        Ok(CartridgeGFX { sprites: sprites })
     }
 
     pub fn set_sprites(&mut self, sprites: Vec<Sprite>) {
         self.sprites = sprites;
     }
 
-    pub fn get_data(&mut self) -> String {
+    pub fn get_data(&mut self, config: &CartridgeConfig) -> String {
         let mut data = String::new();
 
+        let sprites_per_row = config.sprites_per_row();
         let mut idx_sprites = 0;
         let mut line;
 
-        for y in 0..240 {
-            line = y % 8;
+        for y in 0..config.height {
+            line = (y % 8) as u32;
 
             if y > 0 && (y % 8) == 0 {
-                idx_sprites += 50;
+                idx_sprites += sprites_per_row;
             }
 
-            for idx in idx_sprites..idx_sprites + 50 {
-                let mut gfx_sprites = self.sprites[idx].clone();
-
-                data.push_str(&gfx_sprites.get_line(line));
+            for idx in idx_sprites..idx_sprites + sprites_per_row {
+                match self.sprites.get(idx) {
+                    Some(sprite) => data.push_str(&sprite.clone().get_line(line)),
+                    None => data.push_str(&"000".repeat(8)),
+                }
             }
 
             data.push('\n');
@@ -377,27 +582,25 @@ impl CartridgeGFF {
         CartridgeGFF { flags: Vec::new() }
     }
 
-    pub fn new(lines: &[String]) -> CartridgeGFF {
+    pub fn new(lines: &[String]) -> Result<CartridgeGFF, Error> {
         info!("[CARTRIDGE] CartridgeGFF");
 
         let mut v = Vec::new();
 
-        for line in lines {
-            for c in line.as_bytes() {
-                v.push((*c as char).to_digit(16).unwrap() as u8);
-            }
-        }
+        for (y, line) in lines.iter().enumerate() {
+            let mut i = 0;
+            while i < line.len() {
+                let flag = try!(parse_hex("__gff__", line, y, i, 2));
 
-        let mut v_order = Vec::new();
-        let mut idx = 0;
-        while idx < v.len() {
-            v_order.push(v[idx + 1]);
-            v_order.push(v[idx]);
+                // new_from_bytes wants the low digit first
+                v.push((flag & 0x0f) as u8);
+                v.push((flag >> 4) as u8);
 
-            idx += 2;
+                i += 2;
+            }
         }
 
-        CartridgeGFF::new_from_bytes(&v_order)
+        Ok(CartridgeGFF::new_from_bytes(&v))
     }
 
     pub fn new_from_bytes(v: &[u8]) -> CartridgeGFF {
@@ -468,64 +671,260 @@ impl CartridgeMusic {
 }
 
 pub struct CartridgeMap {
-    pub map: Vec<u32>,
+    /// One map per layer, stored in the `__map__`, `__map1__`, `__map2__`... sections
+    pub map: Vec<Vec<u32>>,
 }
 
 impl CartridgeMap {
     pub fn empty() -> CartridgeMap {
-        CartridgeMap { map: Vec::new() }
+        CartridgeMap { map: vec![CartridgeMap::empty_layer()] }
     }
 
-    pub fn new(lines: &[String]) -> CartridgeMap {
-        info!("[CARTRIDGE] CartridgeMap");
-
-        let mut map = Vec::new();
-        let mut y = 0;
+    fn empty_layer() -> Vec<u32> {
+        vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT]
+    }
 
-        for line in lines {
-            let mut i = 0;
+    /// Name of the section containing the map layer `layer`
+    pub fn section_name(layer: usize) -> String {
+        if layer == 0 {
+            "__map__".to_string()
+        } else {
+            format!("__map{}__", layer)
+        }
+    }
 
-            while i < unicorn::MAP_WIDTH*3 {
-                let idx_sprite = u32::from_str_radix(&line[i..i + 3], 16).unwrap();
-                //info!("VAL {:?} {:?}", v, z);
+    pub fn new(sections: &HashMap<String, Vec<String>>) -> Result<CartridgeMap, Error> {
+        let mut map = vec![CartridgeMap::empty_layer(); MAP_LAYERS];
+        let mut layers = 1;
 
-                map.push(idx_sprite);
-                
-                i += 3;
+        for layer in 0..MAP_LAYERS {
+            if let Some(lines) = sections.get(&CartridgeMap::section_name(layer)) {
+                map[layer] = try!(CartridgeMap::parse_layer(layer, lines));
+                layers = layer + 1;
             }
+        }
+
+        map.truncate(layers);
+
+        Ok(CartridgeMap { map: map })
+    }
+
+    fn parse_layer(layer: usize, lines: &[String]) -> Result<Vec<u32>, Error> {
+        info!("[CARTRIDGE] CartridgeMap layer {:?}", layer);
+
+        let mut map = CartridgeMap::empty_layer();
 
-            y += 1;
+        for (y, line) in lines.iter().take(unicorn::MAP_HEIGHT).enumerate() {
+            let line = line.trim();
 
-            if y == 60 {
-                break;
+            for x in 0..unicorn::MAP_WIDTH {
+                let i = x * 3;
+                if i + 3 > line.len() {
+                    break;
+                }
+
+                map[map_index(x, y)] = try!(parse_hex(&CartridgeMap::section_name(layer), line, y, i, 3));
             }
         }
 
-        CartridgeMap { map: map }
+        Ok(map)
     }
 
+    /// Sections of all the layers, with their headers. The first layer is
+    /// always written, the other ones only up to the last non empty layer.
     pub fn get_data(&mut self) -> String {
         let mut data = String::new();
 
-        for y in 0..unicorn::MAP_HEIGHT {
-            for x in 0..unicorn::MAP_WIDTH {
-                let idx_sprite = *self.map.get(x * unicorn::MAP_WIDTH + y).unwrap_or(&0);
-                data.push_str(&format!("{:03x}", idx_sprite));
-            }
+        let layers = match self.map.iter().rposition(|layer| layer.iter().any(|v| *v != 0)) {
+            Some(last) => last + 1,
+            None => 1,
+        };
+
+        for layer in 0..layers {
+            data.push_str(&CartridgeMap::section_name(layer));
             data.push('\n');
+
+            for y in 0..unicorn::MAP_HEIGHT {
+                for x in 0..unicorn::MAP_WIDTH {
+                    let idx_sprite = match self.map.get(layer) {
+                        Some(map) => *map.get(map_index(x, y)).unwrap_or(&0),
+                        None => 0,
+                    };
+                    data.push_str(&format!("{:03x}", idx_sprite));
+                }
+                data.push('\n');
+            }
         }
 
         data
     }
 
-    pub fn set_map(&mut self, map: Vec<u32>) {
+    pub fn set_map(&mut self, map: Vec<Vec<u32>>) {
         self.map = map;
     }
 }
 
+/// Maximum number of `__font__` sections
+pub const MAX_FONTS: usize = 8;
+
+pub struct CartridgeFont {
+    /// BDF text of each font, from the `__font__`, `__font1__`... sections
+    pub fonts: Vec<Vec<String>>,
+}
+
+impl CartridgeFont {
+    pub fn empty() -> CartridgeFont {
+        CartridgeFont { fonts: Vec::new() }
+    }
+
+    /// Name of the section containing the font `idx`
+    pub fn section_name(idx: usize) -> String {
+        if idx == 0 {
+            "__font__".to_string()
+        } else {
+            format!("__font{}__", idx)
+        }
+    }
+
+    /// The fonts are parsed by the screen, when the cartridge is loaded
+    pub fn new(sections: &HashMap<String, Vec<String>>) -> CartridgeFont {
+        let mut fonts = Vec::new();
+
+        for idx in 0..MAX_FONTS {
+            if let Some(lines) = sections.get(&CartridgeFont::section_name(idx)) {
+                info!("[CARTRIDGE] CartridgeFont {:?}", idx);
+                fonts.push(lines.clone());
+            }
+        }
+
+        CartridgeFont { fonts: fonts }
+    }
+
+    /// Sections of all the fonts, with their headers
+    pub fn get_data(&self) -> String {
+        let mut data = String::new();
+
+        for (idx, lines) in self.fonts.iter().take(MAX_FONTS).enumerate() {
+            data.push_str(&CartridgeFont::section_name(idx));
+            data.push('\n');
+
+            for line in lines {
+                data.push_str(line);
+                data.push('\n');
+            }
+        }
+
+        data
+    }
+}
+
+pub struct CartridgeTTF {
+    /// Name and content of the file of each font, from the `__ttf__`,
+    /// `__ttf1__`... sections
+    pub fonts: Vec<(String, Vec<u8>)>,
+}
+
+impl CartridgeTTF {
+    pub fn empty() -> CartridgeTTF {
+        CartridgeTTF { fonts: Vec::new() }
+    }
+
+    /// Name of the section containing the font `idx`
+    pub fn section_name(idx: usize) -> String {
+        if idx == 0 {
+            "__ttf__".to_string()
+        } else {
+            format!("__ttf{}__", idx)
+        }
+    }
+
+    pub fn new(sections: &HashMap<String, Vec<String>>) -> Result<CartridgeTTF, Error> {
+        let mut fonts = Vec::new();
+
+        for idx in 0..MAX_FONTS {
+            let section = CartridgeTTF::section_name(idx);
+            if let Some(lines) = sections.get(&section) {
+                info!("[CARTRIDGE] CartridgeTTF {:?}", idx);
+
+                let name = match lines.first() {
+                    Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+                    _ => return Err(Error::BadValue { section: section, line: 1 }),
+                };
+
+                let mut data = Vec::new();
+                for (y, line) in lines.iter().enumerate().skip(1) {
+                    let line = line.trim();
+
+                    let mut i = 0;
+                    while i < line.len() {
+                        data.push(try!(parse_hex(&section, line, y, i, 2)) as u8);
+                        i += 2;
+                    }
+                }
+
+                fonts.push((name, data));
+            }
+        }
+
+        Ok(CartridgeTTF { fonts: fonts })
+    }
+
+    /// Embed the TTF/OTF file `filename`, under the name of the file without
+    /// its extension (replacing the font with the same name)
+    pub fn add_file(&mut self, filename: &str) -> Result<String, Error> {
+        let name = match Path::new(filename).file_stem() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(Error::Err(format!("No font name in {:?}", filename))),
+        };
+
+        let mut data = Vec::new();
+        try!(File::open(filename).and_then(|mut f| f.read_to_end(&mut data)));
+
+        if let Err(e) = TtfFont::from_bytes(&name, data.clone()) {
+            return Err(Error::Err(e));
+        }
+
+        match self.fonts.iter().position(|&(ref font, _)| *font == name) {
+            Some(idx) => self.fonts[idx].1 = data,
+            None => {
+                if self.fonts.len() >= MAX_FONTS {
+                    return Err(Error::Err(format!("No more than {:?} TTF fonts", MAX_FONTS)));
+                }
+                self.fonts.push((name.clone(), data));
+            }
+        }
+
+        Ok(name)
+    }
+
+    /// Sections of all the fonts, with their headers
+    pub fn get_data(&self) -> String {
+        let mut data = String::new();
+
+        for (idx, &(ref name, ref bytes)) in self.fonts.iter().take(MAX_FONTS).enumerate() {
+            data.push_str(&CartridgeTTF::section_name(idx));
+            data.push('\n');
+            data.push_str(name);
+            data.push('\n');
+
+            for chunk in bytes.chunks(64) {
+                for byte in chunk {
+                    data.push_str(&format!("{:02x}", byte));
+                }
+                data.push('\n');
+            }
+        }
+
+        data
+    }
+}
+
 pub enum CartridgeFormat {
     UnicornSplittedFormat = 0,
     UnicornFormat = 1,
+    UnicornBinaryFormat = 2,
+    UnicornPngFormat = 3,
+    Pico8Format = 4,
 }
 
 pub struct Cartridge {
@@ -533,12 +932,15 @@ pub struct Cartridge {
     pub data_filename: String,
     pub header: String,
     pub version: String,
+    pub config: CartridgeConfig,
     pub gfx: CartridgeGFX,
     pub map: CartridgeMap,
     pub gff: CartridgeGFF,
     pub code: CartridgeCode,
     pub palette: CartridgePalette,
     pub music: CartridgeMusic,
+    pub font: CartridgeFont,
+    pub ttf: CartridgeTTF,
     pub format: CartridgeFormat,
 }
 
@@ -548,6 +950,22 @@ pub static SECTION_DELIM_RE: &'static str = r"^__(\w+)__$";
 pub enum Error {
     Err(String),
     IOError(io::Error),
+    /// A section needed by the cartridge is not present
+    MissingSection(String),
+    /// Invalid hexadecimal data, the line (in the section) and column start at 1
+    BadHex {
+        section: String,
+        line: usize,
+        column: usize,
+    },
+    /// Invalid decimal value, the line (in the section) starts at 1
+    BadValue { section: String, line: usize },
+    /// Invalid JSON description of a splitted cartridge
+    BadJson(String),
+    /// File referenced by the cartridge that can't be opened
+    MissingFile(String),
+    /// Code file or section of an unknown language
+    UnsupportedCodeType(String),
 }
 
 impl convert::From<io::Error> for Error {
@@ -556,37 +974,61 @@ impl convert::From<io::Error> for Error {
     }
 }
 
-fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Cartridge, Error> {
-    let mut header = String::new();
-    try!(buf.read_line(&mut header));
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Err(ref e) => write!(f, "{}", e),
+            Error::IOError(ref e) => write!(f, "IO error: {}", e),
+            Error::MissingSection(ref section) => write!(f, "Missing section {}", section),
+            Error::BadHex { ref section, line, column } => {
+                write!(f,
+                       "Bad hexadecimal value in {} at line {} column {}",
+                       section,
+                       line,
+                       column)
+            }
+            Error::BadValue { ref section, line } => {
+                write!(f, "Bad value in {} at line {}", section, line)
+            }
+            Error::BadJson(ref e) => write!(f, "Bad JSON: {}", e),
+            Error::MissingFile(ref filename) => write!(f, "Missing file {}", filename),
+            Error::UnsupportedCodeType(ref code) => write!(f, "Unsupported code type {}", code),
+        }
+    }
+}
 
-    let mut version = String::new();
-    try!(buf.read_line(&mut version));
+/// Parse `width` hexadecimal digits of the line `y` of a section, starting at `column`.
+fn parse_hex(section: &str, line: &str, y: usize, column: usize, width: usize) -> Result<u32, Error> {
+    match line.get(column..column + width).and_then(|v| u32::from_str_radix(v, 16).ok()) {
+        Some(value) => Ok(value),
+        None => {
+            Err(Error::BadHex {
+                    section: section.to_string(),
+                    line: y + 1,
+                    column: column + 1,
+                })
+        }
+    }
+}
 
+/// Split the lines of a cartridge in sections (`__gfx__`, `__map__`...)
+fn read_sections<R: io::BufRead>(buf: &mut R) -> Result<HashMap<String, Vec<String>>, Error> {
     let re_delim_section = Regex::new(SECTION_DELIM_RE).unwrap();
 
-    let mut sections: HashMap<String, Vec<(String)>> = HashMap::new();
+    let mut sections: HashMap<String, Vec<String>> = HashMap::new();
 
     let mut section_name = "".to_string();
 
-    let mut new_section;
-
     for line in buf.lines() {
-        let l = line.unwrap();
+        let l = try!(line);
         if re_delim_section.is_match(l.as_str()) {
             debug!("NEW SECTION {:?}", l);
             section_name = l.clone();
 
-            let vec_section = Vec::new();
-            sections.insert(section_name.clone(), vec_section);
-            new_section = false;
+            sections.insert(section_name.clone(), Vec::new());
         } else {
-            new_section = true;
-        }
-
-        if new_section {
             match sections.get_mut(&section_name) {
-                Some(vec_section2) => vec_section2.push(l),
+                Some(vec_section) => vec_section.push(l),
                 _ => debug!("Impossible to find section {:?}", section_name),
             }
         }
@@ -596,6 +1038,19 @@ fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Ca
         debug!("{}: \"{}\"", section_name, section.len());
     }
 
+    Ok(sections)
+}
+
+fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Cartridge, Error> {
+    let mut header = String::new();
+    try!(buf.read_line(&mut header));
+
+    let mut version = String::new();
+    try!(buf.read_line(&mut version));
+
+    let sections = try!(read_sections(buf));
+
+    let cartridge_config;
     let cartridge_gfx;
     let mut cartridge_code;
     let cartridge_palette;
@@ -604,40 +1059,39 @@ fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Ca
     let cartridge_music;
 
 
-    if sections.contains_key("__lua__") {
-        cartridge_code = CartridgeCode::new("lua".to_string(),
-                                            sections.get_mut("__lua__").unwrap());
-    } else if sections.contains_key("__python__") {
-        cartridge_code = CartridgeCode::new("python".to_string(),
-                                            sections.get_mut("__python__").unwrap());
-    } else if sections.contains_key("__javascript__") {
-        cartridge_code = CartridgeCode::new("javascript".to_string(),
-                                            sections.get_mut("__javascript__").unwrap());
+    if let Some(lines) = sections.get("__lua__") {
+        cartridge_code = CartridgeCode::new("lua".to_string(), lines);
+    } else if let Some(lines) = sections.get("__python__") {
+        cartridge_code = CartridgeCode::new("python".to_string(), lines);
+    } else if let Some(lines) = sections.get("__javascript__") {
+        cartridge_code = CartridgeCode::new("javascript".to_string(), lines);
     } else {
-        return Err(Error::Err("NO CODE DATA".to_string()));
+        return Err(Error::MissingSection("__lua__, __python__ or __javascript__".to_string()));
     }
 
-    match sections.get_mut("__palette__") {
-        Some(vec_section) => cartridge_palette = CartridgePalette::new(vec_section),
+    match sections.get("__palette__") {
+        Some(vec_section) => cartridge_palette = try!(CartridgePalette::new(vec_section)),
         _ => cartridge_palette = CartridgePalette::empty(),
     }
 
-    match sections.get_mut("__gfx__") {
-        Some(vec_section) => cartridge_gfx = CartridgeGFX::new(vec_section),
-        _ => cartridge_gfx = CartridgeGFX::empty(),
+    match sections.get("__config__") {
+        Some(vec_section) => cartridge_config = try!(CartridgeConfig::new(vec_section)),
+        _ => cartridge_config = CartridgeConfig::empty(),
     }
 
-    match sections.get_mut("__map__") {
-        Some(vec_section) => cartridge_map = CartridgeMap::new(vec_section),
-        _ => cartridge_map = CartridgeMap::empty(),
+    match sections.get("__gfx__") {
+        Some(vec_section) => cartridge_gfx = try!(CartridgeGFX::new(vec_section, &cartridge_config)),
+        _ => cartridge_gfx = CartridgeGFX::empty(),
     }
 
-    match sections.get_mut("__gff__") {
-        Some(vec_section) => cartridge_gff = CartridgeGFF::new(vec_section),
+    cartridge_map = try!(CartridgeMap::new(&sections));
+
+    match sections.get("__gff__") {
+        Some(vec_section) => cartridge_gff = try!(CartridgeGFF::new(vec_section)),
         _ => cartridge_gff = CartridgeGFF::empty(),
     }
 
-    match sections.get_mut("__music__") {
+    match sections.get("__music__") {
         Some(vec_section) => cartridge_music = CartridgeMusic::new(vec_section),
         _ => cartridge_music = CartridgeMusic::empty(),
     }
@@ -650,12 +1104,15 @@ fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Ca
            data_filename: "".to_string(),
            header: header.clone(),
            version: version.clone(),
+           config: cartridge_config,
            gfx: cartridge_gfx,
            code: cartridge_code,
            palette: cartridge_palette,
            map: cartridge_map,
            gff: cartridge_gff,
            music: cartridge_music,
+           font: CartridgeFont::new(&sections),
+           ttf: try!(CartridgeTTF::new(&sections)),
            format: CartridgeFormat::UnicornFormat,
        })
 }
@@ -663,39 +1120,9 @@ fn read_from_uniformat<R: io::BufRead>(filename: &str, buf: &mut R) -> Result<Ca
 pub fn from_dunicorn_file_raw<R: io::BufRead>(buf_reader: &mut R) -> Result<Cartridge, Error> {
     let code_section = Vec::new();
 
-    let re_delim_section = Regex::new(SECTION_DELIM_RE).unwrap();
-
-    let mut sections: HashMap<String, Vec<(String)>> = HashMap::new();
-
-    let mut section_name = "".to_string();
-
-    let mut new_section;
-
-    for line in buf_reader.lines() {
-        let l = line.unwrap();
-        if re_delim_section.is_match(l.as_str()) {
-            debug!("NEW SECTION {:?}", l);
-            section_name = l.clone();
-
-            let vec_section = Vec::new();
-            sections.insert(section_name.clone(), vec_section);
-            new_section = false;
-        } else {
-            new_section = true;
-        }
-
-        if new_section {
-            match sections.get_mut(&section_name) {
-                Some(vec_section2) => vec_section2.push(l),
-                _ => debug!("Impossible to find section {:?}", section_name),
-            }
-        }
-    }
-
-    for (section_name, section) in &sections {
-        debug!("{}: \"{}\"", section_name, section.len());
-    }
+    let sections = try!(read_sections(buf_reader));
 
+    let cartridge_config;
     let cartridge_gfx;
     let cartridge_gff;
     let cartridge_palette;
@@ -706,28 +1133,30 @@ pub fn from_dunicorn_file_raw<R: io::BufRead>(buf_reader: &mut R) -> Result<Cart
     cartridge_code = CartridgeCode::new("javascript".to_string(), &code_section);
     cartridge_code.set_filename("empty.js");
 
-    match sections.get_mut("__palette__") {
-        Some(vec_section) => cartridge_palette = CartridgePalette::new(vec_section),
+    match sections.get("__palette__") {
+        Some(vec_section) => cartridge_palette = try!(CartridgePalette::new(vec_section)),
         _ => cartridge_palette = CartridgePalette::empty(),
     }
 
 
-    match sections.get_mut("__gfx__") {
-        Some(vec_section) => cartridge_gfx = CartridgeGFX::new(vec_section),
-        _ => cartridge_gfx = CartridgeGFX::empty(),
+    match sections.get("__config__") {
+        Some(vec_section) => cartridge_config = try!(CartridgeConfig::new(vec_section)),
+        _ => cartridge_config = CartridgeConfig::empty(),
     }
 
-    match sections.get_mut("__map__") {
-        Some(vec_section) => cartridge_map = CartridgeMap::new(vec_section),
-        _ => cartridge_map = CartridgeMap::empty(),
+    match sections.get("__gfx__") {
+        Some(vec_section) => cartridge_gfx = try!(CartridgeGFX::new(vec_section, &cartridge_config)),
+        _ => cartridge_gfx = CartridgeGFX::empty(),
     }
 
-    match sections.get_mut("__gff__") {
-        Some(vec_section) => cartridge_gff = CartridgeGFF::new(vec_section),
+    cartridge_map = try!(CartridgeMap::new(&sections));
+
+    match sections.get("__gff__") {
+        Some(vec_section) => cartridge_gff = try!(CartridgeGFF::new(vec_section)),
         _ => cartridge_gff = CartridgeGFF::empty(),
     }
 
-    match sections.get_mut("__music__") {
+    match sections.get("__music__") {
         Some(vec_section) => cartridge_music = CartridgeMusic::new(vec_section),
         _ => cartridge_music = CartridgeMusic::empty(),
     }
@@ -738,12 +1167,15 @@ pub fn from_dunicorn_file_raw<R: io::BufRead>(buf_reader: &mut R) -> Result<Cart
            data_filename: "empty.duc".to_string(),
            header: "".to_string(),
            version: "".to_string(),
+           config: cartridge_config,
            gfx: cartridge_gfx,
            code: cartridge_code,
            palette: cartridge_palette,
            map: cartridge_map,
            gff: cartridge_gff,
            music: cartridge_music,
+           font: CartridgeFont::new(&sections),
+           ttf: try!(CartridgeTTF::new(&sections)),
            format: CartridgeFormat::UnicornSplittedFormat,
        })
 }
@@ -762,16 +1194,69 @@ impl Cartridge {
             data_filename: "".to_string(),
             header: "".to_string(),
             version: "".to_string(),
+            config: CartridgeConfig::empty(),
             gfx: CartridgeGFX::empty(),
             map: CartridgeMap::empty(),
             gff: CartridgeGFF::empty(),
             code: CartridgeCode::empty(),
             palette: CartridgePalette::empty(),
             music: CartridgeMusic::empty(),
+            font: CartridgeFont::empty(),
+            ttf: CartridgeTTF::empty(),
             format: CartridgeFormat::UnicornFormat,
         }
     }
 
+    /// Load a cartridge of any format, detected from the first bytes of the file.
+    pub fn from_file(filename: &str) -> Result<Cartridge, Error> {
+        let mut f = try!(File::open(filename));
+
+        let mut data = Vec::new();
+        try!(f.read_to_end(&mut data));
+
+        let first = data.iter().cloned().find(|c| !(*c as char).is_whitespace());
+        if !binary::is_binary(&data) && !png::is_png(&data) && first == Some(b'{') {
+            return Cartridge::from_unicorn_splitted_file(filename);
+        }
+
+        Cartridge::from_data(filename, data)
+    }
+
+    /// Load a cartridge from memory, the splitted format is not available
+    /// because it refers to other files.
+    pub fn from_data(filename: &str, data: Vec<u8>) -> Result<Cartridge, Error> {
+        if binary::is_binary(&data) {
+            return binary::read(filename, &data);
+        }
+
+        if png::is_png(&data) {
+            return png::read(filename, &data);
+        }
+
+        if p8::is_p8(filename, &data) {
+            return p8::read(filename, &data);
+        }
+
+        if data.starts_with(b"__") {
+            return Cartridge::from_dunicorn_string(data);
+        }
+
+        Cartridge::from_uni_raw(filename, data)
+    }
+
+    pub fn from_binary_file(filename: &str) -> Result<Cartridge, Error> {
+        let mut f = try!(File::open(filename));
+
+        let mut data = Vec::new();
+        try!(f.read_to_end(&mut data));
+
+        binary::read(filename, &data)
+    }
+
+    pub fn from_binary_raw(filename: &str, data: Vec<u8>) -> Result<Cartridge, Error> {
+        binary::read(filename, &data)
+    }
+
     pub fn from_uni_raw(filename: &str, data: Vec<u8>) -> Result<Cartridge, Error> {
         let mut buf_reader = Cursor::new(data);
         let cartridge = try!(read_from_uniformat(filename, &mut buf_reader));
@@ -789,59 +1274,38 @@ impl Cartridge {
         let mut f = try!(File::open(filename));
 
         let mut data = String::new();
-        f.read_to_string(&mut data).unwrap();
+        try!(f.read_to_string(&mut data));
 
-        let json: UnicornSplittedFormat = serde_json::from_str(&data).unwrap();
+        let json: UnicornSplittedFormat = match serde_json::from_str(&data) {
+            Ok(json) => json,
+            Err(e) => return Err(Error::BadJson(format!("{}: {}", filename, e))),
+        };
 
         let code_file = json.code.as_str();
 
-        let f1 = try!(File::open(code_file));
+        let f1 = match File::open(code_file) {
+            Ok(f) => f,
+            Err(_) => return Err(Error::MissingFile(code_file.to_string())),
+        };
         let buf_reader = BufReader::new(f1);
 
         let mut code_section = Vec::new();
 
         for line in buf_reader.lines() {
-            let l = line.unwrap();
+            let l = try!(line);
             code_section.push(l);
         }
 
         let data_file = json.data.as_str();
-        let f2 = try!(File::open(data_file));
-        let buf_reader = BufReader::new(f2);
-
-        let re_delim_section = Regex::new(SECTION_DELIM_RE).unwrap();
-
-        let mut sections: HashMap<String, Vec<(String)>> = HashMap::new();
-
-        let mut section_name = "".to_string();
-
-        let mut new_section;
-
-        for line in buf_reader.lines() {
-            let l = line.unwrap();
-            if re_delim_section.is_match(l.as_str()) {
-                debug!("NEW SECTION {:?}", l);
-                section_name = l.clone();
-
-                let vec_section = Vec::new();
-                sections.insert(section_name.clone(), vec_section);
-                new_section = false;
-            } else {
-                new_section = true;
-            }
+        let f2 = match File::open(data_file) {
+            Ok(f) => f,
+            Err(_) => return Err(Error::MissingFile(data_file.to_string())),
+        };
+        let mut buf_reader = BufReader::new(f2);
 
-            if new_section {
-                match sections.get_mut(&section_name) {
-                    Some(vec_section2) => vec_section2.push(l),
-                    _ => debug!("Impossible to find section {:?}", section_name),
-                }
-            }
-        }
-
-        for (section_name, section) in &sections {
-            debug!("{}: \"{}\"", section_name, section.len());
-        }
+        let sections = try!(read_sections(&mut buf_reader));
 
+        let cartridge_config;
         let cartridge_gfx;
         let cartridge_gff;
         let mut cartridge_code;
@@ -856,32 +1320,34 @@ impl Cartridge {
         } else if code_file.contains(".lua") {
             cartridge_code = CartridgeCode::new("lua".to_string(), &code_section);
         } else {
-            panic!("Unknown file to load the code {:?}", code_file);
+            return Err(Error::UnsupportedCodeType(code_file.to_string()));
         }
 
         cartridge_code.set_filename(code_file);
 
-        match sections.get_mut("__palette__") {
-            Some(vec_section) => cartridge_palette = CartridgePalette::new(vec_section),
+        match sections.get("__palette__") {
+            Some(vec_section) => cartridge_palette = try!(CartridgePalette::new(vec_section)),
             _ => cartridge_palette = CartridgePalette::empty(),
         }
 
-        match sections.get_mut("__gfx__") {
-            Some(vec_section) => cartridge_gfx = CartridgeGFX::new(vec_section),
-            _ => cartridge_gfx = CartridgeGFX::empty(),
+        match sections.get("__config__") {
+            Some(vec_section) => cartridge_config = try!(CartridgeConfig::new(vec_section)),
+            _ => cartridge_config = CartridgeConfig::empty(),
         }
 
-        match sections.get_mut("__map__") {
-            Some(vec_section) => cartridge_map = CartridgeMap::new(vec_section),
-            _ => cartridge_map = CartridgeMap::empty(),
+        match sections.get("__gfx__") {
+            Some(vec_section) => cartridge_gfx = try!(CartridgeGFX::new(vec_section, &cartridge_config)),
+            _ => cartridge_gfx = CartridgeGFX::empty(),
         }
 
-        match sections.get_mut("__gff__") {
-            Some(vec_section) => cartridge_gff = CartridgeGFF::new(vec_section),
+        cartridge_map = try!(CartridgeMap::new(&sections));
+
+        match sections.get("__gff__") {
+            Some(vec_section) => cartridge_gff = try!(CartridgeGFF::new(vec_section)),
             _ => cartridge_gff = CartridgeGFF::empty(),
         }
 
-        match sections.get_mut("__music__") {
+        match sections.get("__music__") {
             Some(vec_section) => cartridge_music = CartridgeMusic::new(vec_section),
             _ => cartridge_music = CartridgeMusic::empty(),
         }
@@ -892,12 +1358,15 @@ impl Cartridge {
                data_filename: data_file.to_string(),
                header: "".to_string(),
                version: "".to_string(),
+               config: cartridge_config,
                gfx: cartridge_gfx,
                code: cartridge_code,
                palette: cartridge_palette,
                map: cartridge_map,
                gff: cartridge_gff,
                music: cartridge_music,
+               font: CartridgeFont::new(&sections),
+               ttf: try!(CartridgeTTF::new(&sections)),
                format: CartridgeFormat::UnicornSplittedFormat,
            })
     }
@@ -906,7 +1375,7 @@ impl Cartridge {
         let mut f = try!(File::open(filename));
 
         let mut data = String::new();
-        f.read_to_string(&mut data).unwrap();
+        try!(f.read_to_string(&mut data));
 
         let mut buf_reader = Cursor::new(data);
 
@@ -934,23 +1403,61 @@ impl Cartridge {
         f.write_all(self.code.get_data().clone().as_bytes())
             .unwrap();
 
+        if !self.config.is_default() {
+            f.write_all(b"__config__\n").unwrap();
+            f.write_all(self.config.get_data().as_bytes()).unwrap();
+        }
+
         f.write_all(b"__palette__\n").unwrap();
         f.write_all(self.palette.get_data().clone().as_bytes()).unwrap();
 
         f.write_all(b"__gfx__\n").unwrap();
-        f.write_all(self.gfx.get_data().clone().as_bytes()).unwrap();
+        f.write_all(self.gfx.get_data(&self.config).clone().as_bytes()).unwrap();
 
         f.write_all(b"__gff__\n").unwrap();
         f.write_all(self.gff.get_data().clone().as_bytes()).unwrap();
 
-        f.write_all(b"__map__\n").unwrap();
         f.write_all(self.map.get_data().clone().as_bytes()).unwrap();
 
+        f.write_all(self.font.get_data().as_bytes()).unwrap();
+        f.write_all(self.ttf.get_data().as_bytes()).unwrap();
+
         f.write_all(b"__sfx__\n").unwrap();
 
         f.write_all(b"__music__\n").unwrap();
     }
 
+    pub fn save_in_binary(&mut self, filename: &str) -> io::Result<()> {
+        info!("Save the modified cartridge in Unicorn binary format {:?}", filename);
+
+        let mut f = try!(File::create(filename));
+        binary::write(self, &mut f)
+    }
+
+    /// Save the cartridge hidden in a PNG image of the label. Without a new
+    /// label, the picture of the existing PNG cartridge is kept.
+    pub fn save_in_png(&mut self, filename: &str, label: Option<png::Label>) -> Result<(), Error> {
+        info!("Save the modified cartridge in Unicorn PNG format {:?}", filename);
+
+        let label = match label {
+            Some(label) => label,
+            None => {
+                let mut data = Vec::new();
+                if let Ok(mut f) = File::open(filename) {
+                    try!(f.read_to_end(&mut data));
+                }
+
+                match png::read_label(&data) {
+                    Ok(label) => label,
+                    Err(_) => png::Label::empty(128, 128),
+                }
+            }
+        };
+
+        let mut f = try!(File::create(filename));
+        png::write(self, &label, &mut f)
+    }
+
     pub fn save_in_unicorn_splitted(&mut self) {
         info!("Save the date of the Unicorn Splitted file in {:?}", self.data_filename);
 
@@ -958,15 +1465,22 @@ impl Cartridge {
             CartridgeFormat::UnicornFormat => {
                 let mut f = File::create(self.data_filename.clone()).unwrap();
 
+                if !self.config.is_default() {
+                    f.write_all(b"__config__\n").unwrap();
+                    f.write_all(self.config.get_data().as_bytes()).unwrap();
+                }
+
                 f.write_all(b"__gfx__\n").unwrap();
-                f.write_all(self.gfx.get_data().clone().as_bytes()).unwrap();
+                f.write_all(self.gfx.get_data(&self.config).clone().as_bytes()).unwrap();
 
                 f.write_all(b"__gff__\n").unwrap();
                 f.write_all(self.gff.get_data().clone().as_bytes()).unwrap();
 
-                f.write_all(b"__map__\n").unwrap();
                 f.write_all(self.map.get_data().clone().as_bytes()).unwrap();
 
+                f.write_all(self.font.get_data().as_bytes()).unwrap();
+                f.write_all(self.ttf.get_data().as_bytes()).unwrap();
+
                 f.write_all(b"__sfx__\n").unwrap();
 
                 f.write_all(b"__music__\n").unwrap();
@@ -993,3 +1507,195 @@ impl fmt::Debug for Cartridge {
                self.gfx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+    use gfx::Screen;
+
+    #[test]
+    fn test_map_layers_round_trip() {
+        let mut layer = CartridgeMap::empty_layer();
+        layer[map_index(2, 1)] = 0x1ab;
+
+        let mut map = CartridgeMap::empty();
+        map.set_map(vec![CartridgeMap::empty_layer(), layer, CartridgeMap::empty_layer()]);
+
+        let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+        let mut section_name = "".to_string();
+        for line in map.get_data().lines() {
+            if line.starts_with("__") {
+                section_name = line.to_string();
+                sections.insert(section_name.clone(), Vec::new());
+            } else {
+                sections.get_mut(&section_name).unwrap().push(line.to_string());
+            }
+        }
+
+        assert!(sections.contains_key("__map__"));
+        assert!(sections.contains_key("__map1__"));
+        assert!(!sections.contains_key("__map2__"));
+
+        let map = CartridgeMap::new(&sections).unwrap();
+        assert_eq!(map.map.len(), 2);
+        assert_eq!(map.map[0][map_index(2, 1)], 0);
+        assert_eq!(map.map[1][map_index(2, 1)], 0x1ab);
+    }
+
+    #[test]
+    fn test_map_edit_round_trip() {
+        let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+        sections.insert("__map__".to_string(), vec!["000001002".to_string()]);
+        let mut map = CartridgeMap::new(&sections).unwrap();
+
+        // Load in the screen, edit two layers and save back
+        let mut screen = Screen::new(128, 128);
+        screen.set_map(map.map.clone());
+        screen.mset(0, 0, 5, 0);
+        screen.mset(unicorn::MAP_WIDTH as i32 - 1, unicorn::MAP_HEIGHT as i32 - 1, 0x3ff, 2);
+        map.set_map(screen.map.clone());
+
+        let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+        let mut section_name = "".to_string();
+        for line in map.get_data().lines() {
+            if line.starts_with("__") {
+                section_name = line.to_string();
+                sections.insert(section_name.clone(), Vec::new());
+            } else {
+                sections.get_mut(&section_name).unwrap().push(line.to_string());
+            }
+        }
+
+        let map = CartridgeMap::new(&sections).unwrap();
+        let mut screen = Screen::new(128, 128);
+        screen.set_map(map.map.clone());
+
+        assert_eq!(map.map.len(), 3);
+        assert_eq!(screen.mget(0, 0, 0), 5);
+        assert_eq!(screen.mget(1, 0, 0), 1);
+        assert_eq!(screen.mget(2, 0, 0), 2);
+        assert_eq!(screen.mget(unicorn::MAP_WIDTH as i32 - 1, unicorn::MAP_HEIGHT as i32 - 1, 2),
+                   0x3ff);
+        assert_eq!(screen.mget(0, 0, 1), 0);
+    }
+
+    #[test]
+    fn test_ttf_sections() {
+        let ttf = CartridgeTTF { fonts: vec![("sans".to_string(), (0..100).collect())] };
+        let data = format!("header\nversion\n__lua__\ncls()\n{}", ttf.get_data());
+
+        let cartridge = Cartridge::from_uni_raw("test.uni", data.into_bytes()).unwrap();
+        assert_eq!(cartridge.ttf.fonts, ttf.fonts);
+
+        let data = b"header\nversion\n__lua__\ncls()\n__ttf__\nsans\n0g\n".to_vec();
+        assert!(Cartridge::from_uni_raw("test.uni", data).is_err());
+    }
+
+    #[test]
+    fn test_ttf_add_file() {
+        let mut ttf = CartridgeTTF::empty();
+
+        let filename = env::temp_dir().join("unicorn_test_font.ttf");
+        let filename = filename.to_str().unwrap();
+        assert!(ttf.add_file(filename).is_err());
+
+        File::create(filename).unwrap().write_all(b"not a font").unwrap();
+        assert!(ttf.add_file(filename).is_err());
+        assert!(ttf.fonts.is_empty());
+
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_config_resolution() {
+        let data = b"header\nversion\n__lua__\ncls()\n__config__\nwidth 128\nheight 64\n".to_vec();
+        let mut cartridge = Cartridge::from_uni_raw("test.uni", data).unwrap();
+        assert_eq!(cartridge.config.width, 128);
+        assert_eq!(cartridge.config.height, 64);
+        assert_eq!(cartridge.config.nb_sprites(), 128);
+
+        let mut sprite_data = [0; 64];
+        sprite_data[3] = 0xab;
+        let mut sprites = vec![Sprite::new([0; 64]); 128];
+        sprites[17] = Sprite::new(sprite_data);
+        cartridge.gfx.set_sprites(sprites);
+
+        let data = cartridge.gfx.get_data(&cartridge.config);
+        let lines: Vec<String> = data.lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines.len(), 64);
+        assert_eq!(lines[0].len(), 128 * 3);
+
+        let gfx = CartridgeGFX::new(&lines, &cartridge.config).unwrap();
+        assert_eq!(gfx.sprites.len(), 128);
+        assert_eq!(gfx.sprites[17].data[3], 0xab);
+
+        // Outside of the palette
+        let mut lines = lines.clone();
+        lines[1] = format!("100{}", &lines[1][3..]);
+        match CartridgeGFX::new(&lines, &cartridge.config) {
+            Err(Error::BadHex { line, column, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(column, 1);
+            }
+            _ => panic!("the color is outside of the palette"),
+        }
+
+        let data = b"header\nversion\n__lua__\ncls()\n__config__\nwidth 100\n".to_vec();
+        match Cartridge::from_uni_raw("test.uni", data) {
+            Err(Error::BadValue { ref section, line }) => {
+                assert_eq!(section, "__config__");
+                assert_eq!(line, 1);
+            }
+            _ => panic!("the width is not a multiple of 8"),
+        }
+    }
+
+    #[test]
+    fn test_config_metadata() {
+        let lines: Vec<String> = vec!["title  Space Cat ".to_string(),
+                                      "author someone".to_string(),
+                                      "description first line".to_string(),
+                                      "description second line".to_string(),
+                                      "fps 30".to_string(),
+                                      "".to_string(),
+                                      "palette c64".to_string()];
+        let config = CartridgeConfig::new(&lines).unwrap();
+        assert_eq!(config.title, "Space Cat");
+        assert_eq!(config.description, "first line\nsecond line");
+        assert_eq!(config.fps, 30);
+        assert_eq!(config.font, "pico-8");
+        assert_eq!(config.get("palette"), Some("c64".to_string()));
+        assert_eq!(config.get("width"), Some("400".to_string()));
+        assert_eq!(config.get("unknown"), None);
+        assert!(!config.is_default());
+
+        let lines: Vec<String> = config.get_data().lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(CartridgeConfig::new(&lines).unwrap(), config);
+
+        assert!(CartridgeConfig::new(&vec!["fps 0".to_string()]).is_err());
+        assert_eq!(CartridgeConfig::empty().get_data(), "");
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let data = b"header\nversion\n__gfx__\n".to_vec();
+        match Cartridge::from_uni_raw("test.uni", data) {
+            Err(Error::MissingSection(_)) => (),
+            _ => panic!("the code section is missing"),
+        }
+
+        let data = b"header\nversion\n__lua__\ncls()\n__gff__\n00ff\n0g\n".to_vec();
+        match Cartridge::from_uni_raw("test.uni", data) {
+            Err(Error::BadHex { ref section, line, column }) => {
+                assert_eq!(section, "__gff__");
+                assert_eq!(line, 2);
+                assert_eq!(column, 1);
+            }
+            _ => panic!("the flags are invalid"),
+        }
+    }
+}
@@ -0,0 +1,540 @@
+use std::io::{BufRead, Cursor};
+
+use regex::Regex;
+
+use unicorn;
+
+use gfx::{map_index, Color, Sprite};
+
+use cartridge::{parse_hex, read_sections, Cartridge, CartridgeCode, CartridgeConfig, CartridgeFont,
+                CartridgeFormat, CartridgeGFF, CartridgeGFX, CartridgeMap, CartridgeMusic,
+                CartridgePalette, CartridgeTTF, Error};
+
+/* PICO-8 CART FORMAT (import only)
+
+pico-8 cartridge // http://www.pico-8.com
+version XX
+__lua__
+
+__gfx__ 128x128 -> 256 sprites, one hex digit per pixel
+
+__gff__ 256 flags, two hex digits per flag
+
+__map__ 128x32, two hex digits per cell
+
+__sfx__
+
+__music__
+
+The lower half of the sprite sheet (lines 64..127 of __gfx__) is shared with
+the rows 32..63 of the map: each map row is stored in two gfx lines, with the
+low digit of a cell first.
+
+The 128 pixels wide PICO-8 sheet is copied at the top left of the 400 pixels
+wide Unicorn sheet, so the pixel positions (sget, sset, sspr) don't change but
+the sprite numbers do, see sprite_index.
+
+*/
+
+pub static MAGIC: &'static [u8; 16] = b"pico-8 cartridge";
+
+const SHEET_WIDTH: usize = 128;
+const SHEET_HEIGHT: usize = 128;
+const SPRITES_PER_ROW: u32 = 16;
+const NB_SPRITES: u32 = 256;
+
+const MAP_WIDTH: usize = 128;
+const MAP_HEIGHT: usize = 64;
+
+/// Keywords ending the expression of a statement
+const STATEMENT_KEYWORDS: [&'static str; 6] = ["end", "else", "elseif", "until", "then", "do"];
+
+pub fn is_p8(filename: &str, data: &[u8]) -> bool {
+    filename.ends_with(".p8") || data.starts_with(&MAGIC[..])
+}
+
+/// Unicorn sprite number of the PICO-8 sprite `n`.
+pub fn sprite_index(n: u32) -> u32 {
+    (n / SPRITES_PER_ROW) * 50 + n % SPRITES_PER_ROW
+}
+
+fn read_gfx(lines: &[String]) -> Result<CartridgeGFX, Error> {
+    let mut sprites = vec![[0; 64]; 1500];
+
+    for (y, line) in lines.iter().take(SHEET_HEIGHT).enumerate() {
+        for x in 0..SHEET_WIDTH.min(line.len()) {
+            let color = try!(parse_hex("__gfx__", line, y, x, 1));
+
+            // One hexadecimal digit, always in the palette
+            sprites[(y / 8) * 50 + x / 8][(y % 8) * 8 + x % 8] = color as Color;
+        }
+    }
+
+    Ok(CartridgeGFX { sprites: sprites.into_iter().map(Sprite::new).collect() })
+}
+
+fn read_gff(lines: &[String]) -> Result<CartridgeGFF, Error> {
+    let mut flags = vec![0; 1500];
+
+    let mut n = 0;
+    for (y, line) in lines.iter().enumerate() {
+        let mut i = 0;
+        while i + 1 < line.len() && n < NB_SPRITES {
+            flags[sprite_index(n) as usize] = try!(parse_hex("__gff__", line, y, i, 2)) as u8;
+
+            n += 1;
+            i += 2;
+        }
+    }
+
+    Ok(CartridgeGFF { flags: flags })
+}
+
+fn set_cell(map: &mut Vec<u32>, x: usize, y: usize, value: u32) {
+    if value == 0 {
+        return;
+    }
+
+    if x >= unicorn::MAP_WIDTH || y >= unicorn::MAP_HEIGHT {
+        warn!("[CARTRIDGE][P8] Map cell {:?} {:?} out of the Unicorn map", x, y);
+        return;
+    }
+
+    map[map_index(x, y)] = sprite_index(value);
+}
+
+fn read_map(map_lines: &[String], gfx_lines: &[String]) -> Result<CartridgeMap, Error> {
+    let mut map = vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT];
+
+    for (y, line) in map_lines.iter().take(MAP_HEIGHT / 2).enumerate() {
+        let mut x = 0;
+        while x < MAP_WIDTH && 2 * x + 1 < line.len() {
+            let value = try!(parse_hex("__map__", line, y, 2 * x, 2));
+            set_cell(&mut map, x, y, value);
+
+            x += 1;
+        }
+    }
+
+    // Shared part of the map, in the lower half of the sprite sheet
+    for (l, line) in gfx_lines.iter().enumerate().take(SHEET_HEIGHT).skip(SHEET_HEIGHT / 2) {
+        let y = MAP_HEIGHT / 2 + (l - SHEET_HEIGHT / 2) / 2;
+        let offset = ((l - SHEET_HEIGHT / 2) % 2) * (MAP_WIDTH / 2);
+
+        let mut i = 0;
+        while i < MAP_WIDTH / 2 && 2 * i + 1 < line.len() {
+            let low = try!(parse_hex("__gfx__", line, l, 2 * i, 1));
+            let high = try!(parse_hex("__gfx__", line, l, 2 * i + 1, 1));
+            set_cell(&mut map, offset + i, y, high << 4 | low);
+
+            i += 1;
+        }
+    }
+
+    Ok(CartridgeMap { map: vec![map] })
+}
+
+/// Line of Lua code with its strings and comments replaced by placeholders,
+/// so that the rewriting only touches the code.
+struct MaskedLine {
+    code: String,
+    literals: Vec<String>,
+    /// Comment up to the end of the line
+    comment: String,
+}
+
+/// Level of the long bracket (`[[`, `[=[`...) starting at `i`
+fn long_bracket(chars: &[char], i: usize, open: char) -> Option<usize> {
+    if chars.get(i) != Some(&open) {
+        return None;
+    }
+
+    let mut level = 0;
+    while chars.get(i + 1 + level) == Some(&'=') {
+        level += 1;
+    }
+
+    if chars.get(i + 1 + level) == Some(&open) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// End (exclusive) of the long bracket of `level` closed from `i`, if it is
+/// closed on this line
+fn long_end(chars: &[char], i: usize, level: usize) -> Option<usize> {
+    (i..chars.len()).find(|&j| long_bracket(chars, j, ']') == Some(level)).map(|j| j + level + 2)
+}
+
+/// `long` is the level of the long string or comment still open at the end
+/// of the previous line
+fn mask_line(line: &str, long: &mut Option<usize>) -> MaskedLine {
+    let chars: Vec<char> = line.chars().collect();
+    let mut masked = MaskedLine {
+        code: String::new(),
+        literals: Vec::new(),
+        comment: String::new(),
+    };
+
+    let mut i = 0;
+    if let Some(level) = *long {
+        match long_end(&chars, 0, level) {
+            Some(end) => {
+                *long = None;
+                masked.code.push_str(&format!("\u{1}{}\u{2}", masked.literals.len()));
+                masked.literals.push(chars[..end].iter().collect());
+                i = end;
+            }
+            None => {
+                masked.comment = line.to_string();
+                return masked;
+            }
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        let end = if c == '-' && chars.get(i + 1) == Some(&'-') {
+            match long_bracket(&chars, i + 2, '[') {
+                Some(level) => {
+                    match long_end(&chars, i + 2, level) {
+                        Some(end) => end,
+                        None => {
+                            *long = Some(level);
+                            chars.len()
+                        }
+                    }
+                }
+                None => chars.len(),
+            }
+        } else if c == '"' || c == '\'' {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c {
+                if chars[j] == '\\' {
+                    j += 1;
+                }
+                j += 1;
+            }
+            (j + 1).min(chars.len())
+        } else if let Some(level) = long_bracket(&chars, i, '[') {
+            match long_end(&chars, i, level) {
+                Some(end) => end,
+                None => {
+                    *long = Some(level);
+                    chars.len()
+                }
+            }
+        } else {
+            masked.code.push(c);
+            i += 1;
+            continue;
+        };
+
+        let literal: String = chars[i..end].iter().collect();
+        if end == chars.len() && (c == '-' || long.is_some()) {
+            // Up to the end of the line, the code can be completed before it
+            masked.comment = literal;
+        } else {
+            masked.code.push_str(&format!("\u{1}{}\u{2}", masked.literals.len()));
+            masked.literals.push(literal);
+        }
+        i = end;
+    }
+
+    masked
+}
+
+fn unmask_line(masked: MaskedLine) -> String {
+    let mut line = masked.code;
+    for (idx, literal) in masked.literals.iter().enumerate() {
+        line = line.replace(&format!("\u{1}{}\u{2}", idx), literal);
+    }
+    line + &masked.comment
+}
+
+/// End of the expression starting at `start`: a name following a complete
+/// operand (other than `and` and `or`) starts the next statement, as does a
+/// `;`. The brackets and the string literals are skipped.
+fn expression_end(code: &str, start: usize) -> usize {
+    let chars: Vec<(usize, char)> = code[start..]
+        .char_indices()
+        .map(|(i, c)| (start + i, c))
+        .collect();
+
+    let mut depth = 0;
+    let mut operand = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        i += 1;
+
+        if depth > 0 {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => {
+                    depth -= 1;
+                    operand = depth == 0;
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        match c {
+            ';' | ')' | ']' | '}' => return pos,
+            '(' | '[' | '{' => depth += 1,
+            '\u{1}' => {
+                while i < chars.len() && chars[i].1 != '\u{2}' {
+                    i += 1;
+                }
+                i += 1;
+                operand = true;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                    word.push(chars[i].1);
+                    i += 1;
+                }
+
+                if word == "and" || word == "or" || word == "not" {
+                    operand = false;
+                } else if c.is_digit(10) {
+                    operand = true;
+                } else if operand || STATEMENT_KEYWORDS.contains(&word.as_str()) {
+                    return pos;
+                } else {
+                    operand = true;
+                }
+            }
+            c if c.is_whitespace() => (),
+            _ => operand = false,
+        }
+    }
+
+    code.len()
+}
+
+/// `if (cond) stmt` becomes `if (cond) then stmt end` when a statement
+/// follows the condition on the same line
+fn expand_short_if(code: &str, re_if: &Regex) -> Option<String> {
+    for m in re_if.find_iter(code) {
+        let close = expression_end(code, m.end() - 1);
+        let word: String = code[close..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        if word.is_empty() || word == "then" {
+            continue;
+        }
+
+        let rest = &code[close..];
+        let stmt = rest.trim_right();
+        return Some(format!("{}then {} end{}",
+                            &code[..close],
+                            stmt,
+                            &rest[stmt.len()..]));
+    }
+
+    None
+}
+
+/// `x op= expr` at the start of a statement becomes `x = x op (expr)`, the
+/// expression ending with the statement
+fn expand_compounds(code: &str, re_compound: &Regex) -> String {
+    let mut code = code.to_string();
+
+    // A statement can start right after the expression of the previous one
+    let mut from = 0;
+    loop {
+        let (target_start, target, op, start) = match re_compound.captures(&code[from..]) {
+            Some(caps) => {
+                let target = caps.get(1).unwrap();
+                (from + target.start(),
+                 target.as_str().to_string(),
+                 caps.get(2).unwrap().as_str().to_string(),
+                 from + caps.get(0).unwrap().end())
+            }
+            None => break,
+        };
+
+        let stop = expression_end(&code, start);
+        let segment = code[start..stop].to_string();
+        let expr = segment.trim();
+        if expr.is_empty() {
+            from = start;
+            continue;
+        }
+        let trailing = &segment[segment.trim_right().len()..];
+
+        let statement = format!("{} = {} {} ({})", target, target, op, expr);
+        from = target_start + statement.len();
+        code = format!("{}{}{}{}",
+                       &code[..target_start],
+                       statement,
+                       trailing,
+                       &code[stop..]);
+    }
+
+    code
+}
+
+/// Rewrite the PICO-8 only syntax of the Lua code: `!=`, the `if (cond) stmt`
+/// shorthand and the compound assignments (`x += 1`) of each statement. The
+/// strings and the comments are left as they are.
+pub fn convert_lua(lines: &[String]) -> Vec<String> {
+    let re_if = Regex::new(r"\bif\s*\(").unwrap();
+    let re_compound = Regex::new(r"(?:^|\b(?:then|do|else|repeat|end)\b|;)\s*([A-Za-z_][\w\.]*(?:\[[^\]]*\][\w\.]*)*)\s*(\.\.|[-+*/%^])=")
+        .unwrap();
+
+    let mut long = None;
+
+    lines.iter()
+        .map(|line| {
+            let mut masked = mask_line(line, &mut long);
+
+            masked.code = masked.code.replace("!=", "~=");
+
+            while let Some(code) = expand_short_if(&masked.code, &re_if) {
+                masked.code = code;
+            }
+
+            masked.code = expand_compounds(&masked.code, &re_compound);
+
+            unmask_line(masked)
+        })
+        .collect()
+}
+
+pub fn read(filename: &str, data: &[u8]) -> Result<Cartridge, Error> {
+    let mut buf = Cursor::new(data);
+
+    let mut header = String::new();
+    try!(buf.read_line(&mut header));
+
+    let mut version = String::new();
+    try!(buf.read_line(&mut version));
+
+    info!("[CARTRIDGE][P8] Import {:?} {:?}", header.trim(), version.trim());
+
+    let sections = try!(read_sections(&mut buf));
+
+    let mut cartridge_code = if let Some(lines) = sections.get("__lua__") {
+        CartridgeCode::new("lua".to_string(), &convert_lua(lines))
+    } else if let Some(lines) = sections.get("__python__") {
+        CartridgeCode::new("python".to_string(), lines)
+    } else if let Some(lines) = sections.get("__javascript__") {
+        CartridgeCode::new("javascript".to_string(), lines)
+    } else {
+        return Err(Error::MissingSection("__lua__, __python__ or __javascript__".to_string()));
+    };
+    cartridge_code.set_filename(filename);
+
+    let empty = Vec::new();
+    let gfx_lines = sections.get("__gfx__").unwrap_or(&empty);
+
+    let cartridge_gfx = try!(read_gfx(gfx_lines));
+
+    let cartridge_gff = match sections.get("__gff__") {
+        Some(lines) => try!(read_gff(lines)),
+        None => CartridgeGFF::empty(),
+    };
+
+    let cartridge_map = try!(read_map(sections.get("__map__").unwrap_or(&empty), gfx_lines));
+
+    // The PICO-8 sound chip is not the Unicorn chiptune one
+    for section in &["__sfx__", "__music__"] {
+        if sections.get(*section).map_or(false, |lines| !lines.is_empty()) {
+            warn!("[CARTRIDGE][P8] {} section is not imported", section);
+        }
+    }
+
+    Ok(Cartridge {
+           filename: filename.to_string(),
+           data_filename: "".to_string(),
+           header: header,
+           version: version,
+           config: CartridgeConfig::empty(),
+           gfx: cartridge_gfx,
+           code: cartridge_code,
+           palette: CartridgePalette::empty(),
+           map: cartridge_map,
+           gff: cartridge_gff,
+           music: CartridgeMusic::empty(),
+           font: CartridgeFont::empty(),
+           ttf: CartridgeTTF::empty(),
+           format: CartridgeFormat::Pico8Format,
+       })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p8_import() {
+        let mut gfx = vec!["0".repeat(128); 128];
+        gfx[0] = format!("{}{}", "0".repeat(8), "7".repeat(120));
+        // Map cell (1, 32) = 0x11
+        gfx[64] = format!("0011{}", "0".repeat(124));
+
+        let data = format!("pico-8 cartridge // http://www.pico-8.com\nversion 4\n__lua__\n\
+                            a += 1 -- inc\nif a != 2 then b -= a*2 end\n__gfx__\n{}\n\
+                            __gff__\n0003\n__map__\n0001\n",
+                           gfx.join("\n"));
+
+        assert!(is_p8("test.txt", data.as_bytes()));
+
+        let mut cartridge = read("test.p8", data.as_bytes()).unwrap();
+        assert_eq!(cartridge.code.get_data(),
+                   "a = a + (1) -- inc\nif a ~= 2 then b = b - (a*2) end\n");
+
+        assert_eq!(cartridge.gfx.sprites.len(), 1500);
+        assert_eq!(cartridge.gfx.sprites[0].data[0], 0);
+        assert_eq!(cartridge.gfx.sprites[1].data[0], 7);
+
+        assert_eq!(sprite_index(17), 51);
+        assert_eq!(cartridge.gff.flags[1], 3);
+
+        assert_eq!(cartridge.map.map[0][map_index(1, 0)], 1);
+        assert_eq!(cartridge.map.map[0][map_index(1, 32)], 51);
+    }
+
+    #[test]
+    fn test_convert_lua() {
+        let lines: Vec<String> = vec![// examples/rayzzz-0.2.p8
+                                      "  if (world[movey][flr(player.pos.x)]<=0) then player.pos.y+=player.dr.y*sp end",
+                                      "if (a != 1) x -= 1 -- \"!=\" kept",
+                                      "if (a) if (b) c *= 2",
+                                      "s ..= \"x += 1 != 2\"; n %= 3",
+                                      "for i=1,3 do t[i] += i end",
+                                      "--[[ x += 1",
+                                      "y != 2 ]]",
+                                      "if (a) then",
+                                      "elseif (b) then z /= 2 else z ^= 2 end",
+                                      "x+=dx y+=dy",
+                                      "if (btn(0)) x-=1 y+=f(a) z*=2",
+                                      "if (a) and",
+                                      "(b) then"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+
+        assert_eq!(convert_lua(&lines),
+                   vec!["  if (world[movey][flr(player.pos.x)]<=0) then player.pos.y = player.pos.y + (player.dr.y*sp) end",
+                        "if (a ~= 1) then x = x - (1) end -- \"!=\" kept",
+                        "if (a) then if (b) then c = c * (2) end end",
+                        "s = s .. (\"x += 1 != 2\"); n = n % (3)",
+                        "for i=1,3 do t[i] = t[i] + (i) end",
+                        "--[[ x += 1",
+                        "y != 2 ]]",
+                        "if (a) then",
+                        "elseif (b) then z = z / (2) else z = z ^ (2) end",
+                        "x = x + (dx) y = y + (dy)",
+                        "if (btn(0)) then x = x - (1) y = y + (f(a)) z = z * (2) end",
+                        "if (a) and",
+                        "(b) then"]);
+    }
+}
@@ -0,0 +1,200 @@
+#[cfg(feature = "image")]
+use std::io::Write;
+
+#[cfg(feature = "image")]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(feature = "image")]
+use image;
+
+use cartridge::{Cartridge, Error};
+#[cfg(feature = "image")]
+use cartridge::{binary, CartridgeFormat};
+
+/* PNG CART FORMAT
+
+A regular RGBA PNG image showing the label of the cartridge. Each pixel hides
+one byte of payload in the two lowest bits of its channels, row by row:
+
+    byte = (A & 3) << 6 | (R & 3) << 4 | (G & 3) << 2 | (B & 3)
+
+The payload is the length of the binary cartridge (u32, little endian)
+followed by the binary cartridge itself (see binary.rs). The image is made
+taller than the label when the payload doesn't fit in it.
+
+*/
+
+pub static MAGIC: &'static [u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// RGB picture shown on the cartridge, 3 bytes per pixel, row by row.
+pub struct Label {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Label {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Label {
+        Label {
+            width: width,
+            height: height,
+            data: data,
+        }
+    }
+
+    pub fn empty(width: u32, height: u32) -> Label {
+        Label::new(width, height, vec![0; (width * height * 3) as usize])
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0];
+        }
+
+        let idx = ((y * self.width + x) * 3) as usize;
+        match self.data.get(idx..idx + 3) {
+            Some(rgb) => [rgb[0], rgb[1], rgb[2]],
+            None => [0, 0, 0],
+        }
+    }
+}
+
+pub fn is_png(data: &[u8]) -> bool {
+    data.len() >= MAGIC.len() && &data[..MAGIC.len()] == &MAGIC[..]
+}
+
+#[cfg(feature = "image")]
+fn decode_rgba(data: &[u8]) -> Result<image::RgbaImage, Error> {
+    match image::load_from_memory(data) {
+        Ok(image) => Ok(image.to_rgba()),
+        Err(e) => Err(Error::Err(format!("Invalid PNG cartridge {:?}", e))),
+    }
+}
+
+/// Picture of a PNG cartridge, without the payload.
+#[cfg(feature = "image")]
+pub fn read_label(data: &[u8]) -> Result<Label, Error> {
+    let image = try!(decode_rgba(data));
+
+    let mut rgb = Vec::with_capacity((image.width() * image.height() * 3) as usize);
+    for pixel in image.pixels() {
+        rgb.push(pixel.data[0]);
+        rgb.push(pixel.data[1]);
+        rgb.push(pixel.data[2]);
+    }
+
+    Ok(Label::new(image.width(), image.height(), rgb))
+}
+
+#[cfg(feature = "image")]
+pub fn read(filename: &str, data: &[u8]) -> Result<Cartridge, Error> {
+    let image = try!(decode_rgba(data));
+
+    let payload: Vec<u8> = image.pixels()
+        .map(|p| {
+                 let c = p.data;
+                 (c[3] & 3) << 6 | (c[0] & 3) << 4 | (c[1] & 3) << 2 | (c[2] & 3)
+             })
+        .collect();
+
+    if payload.len() < 4 {
+        return Err(Error::Err("PNG cartridge too small".to_string()));
+    }
+
+    let length = (payload[0] as usize) | (payload[1] as usize) << 8 |
+                 (payload[2] as usize) << 16 | (payload[3] as usize) << 24;
+    if length > payload.len() - 4 {
+        return Err(Error::Err("No cartridge hidden in the PNG image".to_string()));
+    }
+
+    let mut cartridge = try!(binary::read(filename, &payload[4..4 + length]));
+    cartridge.format = CartridgeFormat::UnicornPngFormat;
+
+    Ok(cartridge)
+}
+
+#[cfg(feature = "image")]
+pub fn write<W: Write>(cartridge: &mut Cartridge, label: &Label, output: &mut W) -> Result<(), Error> {
+    let mut data = Vec::new();
+    try!(binary::write(cartridge, &mut data));
+
+    let mut payload = Vec::with_capacity(data.len() + 4);
+    try!(payload.write_u32::<LittleEndian>(data.len() as u32));
+    payload.extend(data);
+
+    let width = if label.width > 0 { label.width } else { 128 };
+    let rows = (payload.len() as u32 + width - 1) / width;
+    let height = if label.height > rows { label.height } else { rows };
+
+    info!("[CARTRIDGE][PNG] {:?} bytes in a {:?}x{:?} image",
+          payload.len(),
+          width,
+          height);
+
+    let mut buffer = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let rgb = label.pixel(x, y);
+            let byte = *payload.get((y * width + x) as usize).unwrap_or(&0);
+
+            buffer.push((rgb[0] & !3) | (byte >> 4) & 3);
+            buffer.push((rgb[1] & !3) | (byte >> 2) & 3);
+            buffer.push((rgb[2] & !3) | byte & 3);
+            buffer.push(0xFC | (byte >> 6) & 3);
+        }
+    }
+
+    let image: image::RgbaImage = match image::ImageBuffer::from_raw(width, height, buffer) {
+        Some(image) => image,
+        None => return Err(Error::Err("Invalid PNG cartridge size".to_string())),
+    };
+
+    match image::DynamicImage::ImageRgba8(image).save(output, image::ImageFormat::PNG) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Err(format!("Impossible to encode the PNG cartridge {:?}", e))),
+    }
+}
+
+#[cfg(not(feature = "image"))]
+pub fn read_label(_data: &[u8]) -> Result<Label, Error> {
+    Err(Error::Err("PNG cartridges need the image feature".to_string()))
+}
+
+#[cfg(not(feature = "image"))]
+pub fn read(_filename: &str, _data: &[u8]) -> Result<Cartridge, Error> {
+    Err(Error::Err("PNG cartridges need the image feature".to_string()))
+}
+
+#[cfg(not(feature = "image"))]
+pub fn write<W: ::std::io::Write>(_cartridge: &mut Cartridge,
+                                  _label: &Label,
+                                  _output: &mut W)
+                                  -> Result<(), Error> {
+    Err(Error::Err("PNG cartridges need the image feature".to_string()))
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+    use cartridge::CartridgeCode;
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut cartridge = Cartridge::empty();
+        cartridge.code = CartridgeCode::new("lua".to_string(), &vec!["cls()".to_string()]);
+
+        let label = Label::new(2, 1, vec![255, 255, 255, 10, 20, 30]);
+
+        let mut data = Vec::new();
+        write(&mut cartridge, &label, &mut data).unwrap();
+        assert!(is_png(&data));
+
+        let mut loaded = read("test.png", &data).unwrap();
+        assert_eq!(loaded.code.get_data(), "cls()\n");
+
+        let label = read_label(&data).unwrap();
+        assert_eq!(label.width, 2);
+        assert_eq!(label.data[0] & !3, 252);
+        assert_eq!(label.data[3] & !3, 8);
+    }
+}
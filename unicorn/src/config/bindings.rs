@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashMap};
+
+use config::files;
+use config::keys::PX8Key;
+use config::scancode::Scancode;
+use config::MAX_PLAYERS;
+
+/* BINDINGS FILE (JSON)
+
+{
+  "players": [
+    {
+      "keys": { "left": ["Left"], "a": ["Z", "C", "N"], ... },
+      "pad": { "left": ["dpleft", "-leftx", "-axis0"], "a": ["a", "button0"], ... },
+      "deadzone": 0.15
+    },
+    ...
+  ]
+}
+
+keys: scancode names, as printed by {:?} (see scancode.rs)
+pad: game controller buttons and axes with their SDL names ("a", "dpup",
+"leftx"...), joystick buttons "buttonN" and axes "axisN". An axis is
+prefixed by the direction: "-leftx" is the left of the stick.
+deadzone: analog values (between 0 and 1) ignored around the center of the
+sticks and the rest position of the triggers.
+
+*/
+
+/// Physical input bound to a key of a player
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Key(Scancode),
+    Pad(String),
+}
+
+/// Default deadzone of the analog sticks and triggers
+pub const DEFAULT_DEADZONE: f64 = 0.15;
+
+fn default_deadzone() -> f64 {
+    DEFAULT_DEADZONE
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerBindings {
+    #[serde(default)]
+    pub keys: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub pad: BTreeMap<String, Vec<String>>,
+    /// Analog values under this one are read as 0 by the axis API
+    #[serde(default = "default_deadzone")]
+    pub deadzone: f64,
+}
+
+impl PlayerBindings {
+    pub fn empty() -> PlayerBindings {
+        PlayerBindings {
+            keys: BTreeMap::new(),
+            pad: BTreeMap::new(),
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    fn add(map: &mut BTreeMap<String, Vec<String>>, key: PX8Key, names: &[&str]) {
+        map.insert(key.name().to_string(),
+                   names.iter().map(|n| n.to_string()).collect());
+    }
+
+    fn default_pad() -> BTreeMap<String, Vec<String>> {
+        let mut pad = BTreeMap::new();
+
+        PlayerBindings::add(&mut pad, PX8Key::Left, &["dpleft", "-leftx", "-axis0"]);
+        PlayerBindings::add(&mut pad, PX8Key::Right, &["dpright", "+leftx", "+axis0"]);
+        PlayerBindings::add(&mut pad, PX8Key::Up, &["dpup", "-lefty", "-axis1"]);
+        PlayerBindings::add(&mut pad, PX8Key::Down, &["dpdown", "+lefty", "+axis1"]);
+        PlayerBindings::add(&mut pad, PX8Key::A, &["a", "button0"]);
+        PlayerBindings::add(&mut pad, PX8Key::B, &["b", "button1"]);
+        PlayerBindings::add(&mut pad, PX8Key::X, &["x", "button2"]);
+        PlayerBindings::add(&mut pad, PX8Key::Y, &["y", "button3"]);
+        PlayerBindings::add(&mut pad, PX8Key::L, &["leftshoulder", "button4"]);
+        PlayerBindings::add(&mut pad, PX8Key::R, &["rightshoulder", "button5"]);
+        PlayerBindings::add(&mut pad, PX8Key::Select, &["back", "button6"]);
+        PlayerBindings::add(&mut pad, PX8Key::Pause, &["start", "button7"]);
+
+        pad
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BindingsFile {
+    players: Vec<PlayerBindings>,
+}
+
+pub fn default_filename() -> String {
+    files::config_path("bindings.json")
+}
+
+/// Keyboard and controller mapping of the players, loaded from and saved in
+/// a JSON file.
+#[derive(Clone)]
+pub struct Bindings {
+    pub filename: String,
+    pub players: Vec<PlayerBindings>,
+    keys: HashMap<Scancode, (u8, PX8Key)>,
+    pad: HashMap<(u8, String), PX8Key>,
+}
+
+impl Bindings {
+    /// Default mapping, two players sharing the keyboard and a controller
+    /// for every player
+    pub fn new() -> Bindings {
+        let mut player1 = PlayerBindings::empty();
+        PlayerBindings::add(&mut player1.keys, PX8Key::Left, &["Left"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::Right, &["Right"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::Up, &["Up"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::Down, &["Down"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::A, &["Z", "C", "N"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::B, &["X", "V", "M"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::Enter, &["Return", "KpEnter"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::Pause, &["Escape"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::X, &["Space"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::L, &["PageUp"]);
+        PlayerBindings::add(&mut player1.keys, PX8Key::R, &["PageDown"]);
+        player1.pad = PlayerBindings::default_pad();
+
+        let mut player2 = PlayerBindings::empty();
+        PlayerBindings::add(&mut player2.keys, PX8Key::Left, &["S"]);
+        PlayerBindings::add(&mut player2.keys, PX8Key::Right, &["F"]);
+        PlayerBindings::add(&mut player2.keys, PX8Key::Up, &["E"]);
+        PlayerBindings::add(&mut player2.keys, PX8Key::Down, &["D"]);
+        PlayerBindings::add(&mut player2.keys, PX8Key::A, &["LShift", "Tab", "A", "Q"]);
+        PlayerBindings::add(&mut player2.keys, PX8Key::B, &["B"]);
+        player2.pad = PlayerBindings::default_pad();
+
+        let mut players = vec![player1, player2];
+        while players.len() < MAX_PLAYERS as usize {
+            let mut player = PlayerBindings::empty();
+            player.pad = PlayerBindings::default_pad();
+            players.push(player);
+        }
+
+        let mut bindings = Bindings {
+            filename: default_filename(),
+            players: players,
+            keys: HashMap::new(),
+            pad: HashMap::new(),
+        };
+        bindings.resolve();
+
+        bindings
+    }
+
+    /// Load the bindings saved in `filename`, the default ones are used if
+    /// the file doesn't exist or is invalid.
+    pub fn load(filename: &str) -> Bindings {
+        let mut bindings = Bindings::new();
+        bindings.filename = filename.to_string();
+
+        if let Some(file) = files::load_json::<BindingsFile>("Bindings", filename) {
+            // Players missing from the file keep the default bindings
+            let mut players = file.players;
+            let nb_players = players.len();
+            players.extend(bindings.players.drain(..).skip(nb_players));
+
+            bindings.players = players;
+            bindings.resolve();
+        }
+
+        bindings
+    }
+
+    pub fn save(&self) -> bool {
+        let file = BindingsFile { players: self.players.clone() };
+        files::save_json("Bindings", &self.filename, &file)
+    }
+
+    /// Rebuild the lookup tables after a change of `players`
+    pub fn resolve(&mut self) {
+        self.keys.clear();
+        self.pad.clear();
+
+        for (player, bindings) in self.players.iter().enumerate() {
+            let player = player as u8;
+
+            for (name, scancodes) in &bindings.keys {
+                let key = match PX8Key::from_name(name) {
+                    Some(key) => key,
+                    None => {
+                        warn!("[Unicorn][Bindings] Unknown key {:?}", name);
+                        continue;
+                    }
+                };
+
+                for scancode_name in scancodes {
+                    match Scancode::from_name(scancode_name) {
+                        Some(scancode) => {
+                            self.keys.insert(scancode, (player, key));
+                        }
+                        None => warn!("[Unicorn][Bindings] Unknown scancode {:?}", scancode_name),
+                    }
+                }
+            }
+
+            for (name, inputs) in &bindings.pad {
+                match PX8Key::from_name(name) {
+                    Some(key) => {
+                        for input in inputs {
+                            self.pad.insert((player, input.clone()), key);
+                        }
+                    }
+                    None => warn!("[Unicorn][Bindings] Unknown key {:?}", name),
+                }
+            }
+        }
+    }
+
+    pub fn map_key(&self, scancode: Scancode) -> Option<(u8, PX8Key)> {
+        self.keys.get(&scancode).cloned()
+    }
+
+    pub fn map_pad(&self, player: u8, name: &str) -> Option<PX8Key> {
+        self.pad.get(&(player, name.to_string())).cloned()
+    }
+
+    pub fn deadzone(&self, player: u8) -> f64 {
+        match self.players.get(player as usize) {
+            Some(bindings) => bindings.deadzone.max(0.0).min(0.95),
+            None => DEFAULT_DEADZONE,
+        }
+    }
+
+    /// Inputs bound to a key of a player, for display
+    pub fn inputs(&self, player: u8, key: PX8Key) -> Vec<String> {
+        let mut inputs = Vec::new();
+
+        if let Some(bindings) = self.players.get(player as usize) {
+            if let Some(keys) = bindings.keys.get(key.name()) {
+                inputs.extend(keys.iter().cloned());
+            }
+            if let Some(pad) = bindings.pad.get(key.name()) {
+                inputs.extend(pad.iter().cloned());
+            }
+        }
+
+        inputs
+    }
+
+    /// Bind `input` to the key of a player, in place of the other inputs of
+    /// the same kind. A keyboard key is only bound to one player. Return
+    /// false without binding it if it is the last keyboard key of B for the
+    /// first player, which leaves the menus.
+    pub fn bind(&mut self, player: u8, key: PX8Key, input: Input) -> bool {
+        while self.players.len() <= player as usize {
+            self.players.push(PlayerBindings::empty());
+        }
+
+        match input {
+            Input::Key(scancode) => {
+                let name = format!("{:?}", scancode);
+
+                let last_back_key = match self.players[0].keys.get(PX8Key::B.name()) {
+                    Some(scancodes) => {
+                        !scancodes.is_empty() && scancodes.iter().all(|s| *s == name)
+                    }
+                    None => false,
+                };
+                if last_back_key && (player != 0 || key != PX8Key::B) {
+                    warn!("[Unicorn][Bindings] {:?} is the last key to go back", name);
+                    return false;
+                }
+
+                for bindings in self.players.iter_mut() {
+                    for scancodes in bindings.keys.values_mut() {
+                        scancodes.retain(|s| *s != name);
+                    }
+                }
+
+                self.players[player as usize]
+                    .keys
+                    .insert(key.name().to_string(), vec![name]);
+            }
+            Input::Pad(name) => {
+                let bindings = &mut self.players[player as usize];
+                for inputs in bindings.pad.values_mut() {
+                    inputs.retain(|s| *s != name);
+                }
+
+                bindings.pad.insert(key.name().to_string(), vec![name]);
+            }
+        }
+
+        self.resolve();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn test_bindings() {
+        let mut bindings = Bindings::new();
+        assert_eq!(bindings.map_key(Scancode::Z), Some((0, PX8Key::A)));
+        assert_eq!(bindings.map_key(Scancode::E), Some((1, PX8Key::Up)));
+        assert_eq!(bindings.map_pad(1, "-leftx"), Some(PX8Key::Left));
+        assert_eq!(bindings.map_key(Scancode::Space), Some((0, PX8Key::X)));
+        assert_eq!(bindings.map_key(Scancode::PageDown), Some((0, PX8Key::R)));
+
+        assert!(bindings.bind(1, PX8Key::A, Input::Key(Scancode::Z)));
+        assert_eq!(bindings.map_key(Scancode::Z), Some((1, PX8Key::A)));
+        assert_eq!(bindings.map_key(Scancode::LShift), None);
+        assert_eq!(bindings.map_key(Scancode::C), Some((0, PX8Key::A)));
+
+        // The last keyboard key of B for the first player stays bound
+        assert!(bindings.bind(1, PX8Key::Y, Input::Key(Scancode::X)));
+        assert!(bindings.bind(1, PX8Key::Y, Input::Key(Scancode::V)));
+        assert!(!bindings.bind(1, PX8Key::Y, Input::Key(Scancode::M)));
+        assert_eq!(bindings.map_key(Scancode::M), Some((0, PX8Key::B)));
+        assert!(bindings.bind(0, PX8Key::B, Input::Key(Scancode::M)));
+
+        assert!(bindings.bind(0, PX8Key::B, Input::Pad("a".to_string())));
+        assert_eq!(bindings.map_pad(0, "a"), Some(PX8Key::B));
+        assert_eq!(bindings.map_pad(0, "b"), None);
+
+        let data = serde_json::to_string(&BindingsFile { players: bindings.players.clone() })
+            .unwrap();
+        let file: BindingsFile = serde_json::from_str(&data).unwrap();
+        assert_eq!(file.players[1].keys["a"], vec!["Z".to_string()]);
+    }
+}
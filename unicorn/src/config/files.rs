@@ -0,0 +1,122 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+/* USER FILES
+
+~/.unicorn/settings.json    options of the pause menu
+~/.unicorn/bindings.json    keys of the players
+~/.unicorn/favorites.json   favorites of the launcher
+~/.unicorn/cartdata/        values saved by the cartridges
+
+The current directory is used when there is no home directory.
+
+*/
+
+fn home_dir() -> Option<PathBuf> {
+    let var = |name: &str| match env::var_os(name) {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => None,
+    };
+
+    var("HOME").or_else(|| var("USERPROFILE"))
+}
+
+/// Path of `name` in the directory of the user files
+pub fn config_path(name: &str) -> String {
+    match home_dir() {
+        Some(mut dir) => {
+            dir.push(".unicorn");
+            dir.push(name);
+            dir.to_string_lossy().to_string()
+        }
+        None => name.to_string(),
+    }
+}
+
+/// Content of the JSON file `filename`, None if it doesn't exist or is
+/// invalid. The messages are logged with the `tag` of the caller.
+pub fn load_json<T: DeserializeOwned>(tag: &str, filename: &str) -> Option<T> {
+    if !Path::new(filename).exists() {
+        info!("[Unicorn][{}] No file {:?}", tag, filename);
+        return None;
+    }
+
+    let mut data = String::new();
+    if let Err(e) = File::open(filename).and_then(|mut f| f.read_to_string(&mut data)) {
+        error!("[Unicorn][{}] Impossible to read {:?}: {:?}", tag, filename, e);
+        return None;
+    }
+
+    match serde_json::from_str::<T>(&data) {
+        Ok(value) => {
+            info!("[Unicorn][{}] Loaded from {:?}", tag, filename);
+            Some(value)
+        }
+        Err(e) => {
+            error!("[Unicorn][{}] Invalid JSON in {:?}: {:?}", tag, filename, e);
+            None
+        }
+    }
+}
+
+/// Write `value` in the JSON file `filename`, its directory is created if
+/// needed
+pub fn save_json<T: Serialize>(tag: &str, filename: &str, value: &T) -> bool {
+    info!("[Unicorn][{}] Save {:?}", tag, filename);
+
+    if let Some(directory) = Path::new(filename).parent() {
+        if let Err(e) = fs::create_dir_all(directory) {
+            error!("[Unicorn][{}] Impossible to create {:?}: {:?}", tag, directory, e);
+            return false;
+        }
+    }
+
+    let data = serde_json::to_string_pretty(value).unwrap();
+
+    match File::create(filename).and_then(|mut f| f.write_all(data.as_bytes())) {
+        Ok(_) => true,
+        Err(e) => {
+            error!("[Unicorn][{}] Impossible to write {:?}: {:?}", tag, filename, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut dir = env::temp_dir();
+        dir.push("unicorn-files-test");
+        let filename = dir.join("sub").join("values.json");
+        let filename = filename.to_string_lossy().to_string();
+
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(load_json::<BTreeMap<String, i32>>("Test", &filename), None);
+
+        let mut values = BTreeMap::new();
+        values.insert("a".to_string(), 1);
+        assert!(save_json("Test", &filename, &values));
+        assert_eq!(load_json::<BTreeMap<String, i32>>("Test", &filename), Some(values));
+
+        File::create(&filename).unwrap().write_all(b"{").unwrap();
+        assert_eq!(load_json::<BTreeMap<String, i32>>("Test", &filename), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_path() {
+        assert!(config_path("settings.json").ends_with("settings.json"));
+    }
+}
@@ -11,6 +11,11 @@ pub enum PX8Key {
     B,
     Pause,
     Enter,
+    X,
+    Y,
+    L,
+    R,
+    Select,
 }
 
 impl fmt::Debug for PX8Key {
@@ -28,36 +33,53 @@ impl fmt::Debug for PX8Key {
                    B => "B",
                    Pause => "Pause",
                    Enter => "Enter",
+                   X => "X",
+                   Y => "Y",
+                   L => "L",
+                   R => "R",
+                   Select => "Select",
                })
 
     }
 }
 
 
-pub fn map_keycode(key: Scancode) -> (Option<PX8Key>, u8) {
-    match key {
-        Scancode::Right => (Some(PX8Key::Right), 0),
-        Scancode::Left => (Some(PX8Key::Left), 0),
-        Scancode::Up => (Some(PX8Key::Up), 0),
-        Scancode::Down => (Some(PX8Key::Down), 0),
+/// Keys of a player, in the order of the btn indexes
+pub const PLAYER_KEYS: [PX8Key; 13] = [PX8Key::Left,
+                                       PX8Key::Right,
+                                       PX8Key::Up,
+                                       PX8Key::Down,
+                                       PX8Key::A,
+                                       PX8Key::B,
+                                       PX8Key::Enter,
+                                       PX8Key::Pause,
+                                       PX8Key::X,
+                                       PX8Key::Y,
+                                       PX8Key::L,
+                                       PX8Key::R,
+                                       PX8Key::Select];
 
-        Scancode::Z | Scancode::C | Scancode::N => (Some(PX8Key::A), 0),
-        Scancode::X | Scancode::V | Scancode::M => (Some(PX8Key::B), 0),
-
-        Scancode::F => (Some(PX8Key::Right), 1),
-        Scancode::S => (Some(PX8Key::Left), 1),
-        Scancode::E => (Some(PX8Key::Up), 1),
-        Scancode::D => (Some(PX8Key::Down), 1),
-
-        Scancode::LShift | Scancode::Tab => (Some(PX8Key::A), 1),
-
-        Scancode::A | Scancode::Q => (Some(PX8Key::A), 1),
-        Scancode::B => (Some(PX8Key::B), 1),
-
-        Scancode::Escape => (Some(PX8Key::Pause), 0),
-
-        Scancode::KpEnter | Scancode::Return => (Some(PX8Key::Enter), 0),
+impl PX8Key {
+    /// Name used in the bindings file
+    pub fn name(self) -> &'static str {
+        match self {
+            PX8Key::Right => "right",
+            PX8Key::Left => "left",
+            PX8Key::Up => "up",
+            PX8Key::Down => "down",
+            PX8Key::A => "a",
+            PX8Key::B => "b",
+            PX8Key::Pause => "pause",
+            PX8Key::Enter => "enter",
+            PX8Key::X => "x",
+            PX8Key::Y => "y",
+            PX8Key::L => "l",
+            PX8Key::R => "r",
+            PX8Key::Select => "select",
+        }
+    }
 
-        _ => (None, 0),
+    pub fn from_name(name: &str) -> Option<PX8Key> {
+        PLAYER_KEYS.iter().cloned().find(|key| key.name() == name)
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,19 @@
 pub mod keys;
 pub mod scancode;
+pub mod bindings;
+pub mod settings;
+pub mod files;
 
-use self::keys::PX8Key;
+use self::keys::{PX8Key, PLAYER_KEYS};
+use self::bindings::{Bindings, Input};
 use self::scancode::{Scancode, Mod};
 
 use std::collections::HashMap;
+use std::mem;
 
+use unicorn::golden::InputEvent;
+
+#[derive(Clone)]
 pub struct Mouse {
     pub x: i32,
     pub y: i32,
@@ -25,10 +33,18 @@ impl Mouse {
         }
     }
 }
+/// Number of local players
+pub const MAX_PLAYERS: u8 = 8;
+
+/// Analog axes of a player: left stick x/y, right stick x/y, left and right triggers
+pub const NB_AXES: usize = 6;
+
+#[derive(Clone)]
 pub struct PlayerKeys {
     frames: HashMap<PX8Key, f64>,
     keys: HashMap<PX8Key, bool>,
     keys_quick: HashMap<PX8Key, bool>,
+    axes: [f64; NB_AXES],
 }
 
 impl PlayerKeys {
@@ -36,28 +52,16 @@ impl PlayerKeys {
         let mut keys = HashMap::new();
         let mut keys_quick = HashMap::new();
 
-        keys.insert(PX8Key::Right, false);
-        keys.insert(PX8Key::Left, false);
-        keys.insert(PX8Key::Up, false);
-        keys.insert(PX8Key::Down, false);
-        keys.insert(PX8Key::A, false);
-        keys.insert(PX8Key::B, false);
-        keys.insert(PX8Key::Pause, false);
-        keys.insert(PX8Key::Enter, false);
-
-        keys_quick.insert(PX8Key::Right, false);
-        keys_quick.insert(PX8Key::Left, false);
-        keys_quick.insert(PX8Key::Up, false);
-        keys_quick.insert(PX8Key::Down, false);
-        keys_quick.insert(PX8Key::A, false);
-        keys_quick.insert(PX8Key::B, false);
-        keys_quick.insert(PX8Key::Pause, false);
-        keys_quick.insert(PX8Key::Enter, false);
+        for key in PLAYER_KEYS.iter() {
+            keys.insert(*key, false);
+            keys_quick.insert(*key, false);
+        }
 
         PlayerKeys {
             frames: HashMap::new(),
             keys: keys,
             keys_quick: keys_quick,
+            axes: [0.0; NB_AXES],
         }
     }
 
@@ -66,6 +70,7 @@ impl PlayerKeys {
     }
 }
 
+#[derive(Clone)]
 pub struct Players {
     pub mouse: Mouse,
     pub text: String,
@@ -77,29 +82,141 @@ pub struct Players {
     pub akeys: HashMap<Scancode, bool>,
     // keys pressed by scancode (only true for the first frame it's pressed)
     pub akeys_quick: HashMap<Scancode, bool>,
+    // keyboard and controller mapping of the players
+    pub bindings: Bindings,
+    // last key or controller input pressed, used to remap the keys
+    pub last_input: Option<Input>,
+    // controller axes pushed beyond the threshold, by player and name ("-leftx")
+    pad_axes: HashMap<(u8, String), bool>,
+    // inputs applied since the last take_journal, when a movie is recorded
+    journal: Option<Vec<InputEvent>>,
+    // a movie is replayed, the inputs of the frontend are ignored
+    replaying: bool,
 }
 
+/// Keys, mouse and axes of the players kept by a snapshot. The bindings and
+/// the movie recording or replay are not part of it.
+#[derive(Clone)]
+pub struct PlayersState {
+    mouse: Mouse,
+    text: String,
+    delta: f64,
+    pkeys: HashMap<u8, PlayerKeys>,
+    akeys: HashMap<Scancode, bool>,
+    akeys_quick: HashMap<Scancode, bool>,
+    pad_axes: HashMap<(u8, String), bool>,
+}
+
+/// An axis is considered as a pressed direction after this value
+pub const AXIS_THRESHOLD: i16 = 16384;
+
 impl Players {
     pub fn new() -> Players {
         let mut keys = HashMap::new();
-        keys.insert(0, PlayerKeys::new());
-        keys.insert(1, PlayerKeys::new());
+        for player in 0..MAX_PLAYERS {
+            keys.insert(player, PlayerKeys::new());
+        }
 
         Players {
             pkeys: keys,
             mouse: Mouse::new(),
             akeys: HashMap::new(),
             akeys_quick: HashMap::new(),
+            bindings: Bindings::new(),
+            last_input: None,
+            pad_axes: HashMap::new(),
+            journal: None,
+            replaying: false,
             text: "".to_string(),
             delta: 0.1,
         }
     }
 
+    /// Keep every input applied from now, see take_journal. The mouse
+    /// doesn't have to move to have its position in the journal.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(vec![InputEvent::MouseMove(self.mouse.x, self.mouse.y)]);
+    }
+
+    pub fn save_state(&self) -> PlayersState {
+        PlayersState {
+            mouse: self.mouse.clone(),
+            text: self.text.clone(),
+            delta: self.delta,
+            pkeys: self.pkeys.clone(),
+            akeys: self.akeys.clone(),
+            akeys_quick: self.akeys_quick.clone(),
+            pad_axes: self.pad_axes.clone(),
+        }
+    }
+
+    pub fn restore_state(&mut self, state: &PlayersState) {
+        self.mouse = state.mouse.clone();
+        self.text = state.text.clone();
+        self.delta = state.delta;
+        self.pkeys = state.pkeys.clone();
+        self.akeys = state.akeys.clone();
+        self.akeys_quick = state.akeys_quick.clone();
+        self.pad_axes = state.pad_axes.clone();
+    }
+
+    pub fn stop_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Inputs applied since the previous call
+    pub fn take_journal(&mut self) -> Vec<InputEvent> {
+        match self.journal {
+            Some(ref mut journal) => mem::replace(journal, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+
+    fn log(&mut self, event: InputEvent) {
+        if let Some(ref mut journal) = self.journal {
+            // Only the last position of the mouse matters
+            let moved = match (&event, journal.last()) {
+                (&InputEvent::MouseMove(..), Some(&InputEvent::MouseMove(..))) => true,
+                _ => false,
+            };
+            if moved {
+                journal.pop();
+            }
+
+            journal.push(event);
+        }
+    }
+
+    pub fn set_replaying(&mut self, replaying: bool) {
+        self.replaying = replaying;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Apply recorded inputs, the only ones accepted while replaying
+    pub fn replay(&mut self, events: &[InputEvent], elapsed: f64) {
+        let replaying = self.replaying;
+        self.replaying = false;
+
+        for event in events.iter() {
+            event.apply(self, elapsed);
+        }
+
+        self.replaying = replaying;
+    }
+
     pub fn clear_text(&mut self) {
         self.text = "".to_string();
     }
 
     pub fn set_text(&mut self, text: String) {
+        if self.replaying {
+            return;
+        }
+
+        self.log(InputEvent::Text(text.clone()));
         self.text = text;
     }
 
@@ -108,14 +225,31 @@ impl Players {
     }
 
     pub fn set_mouse_x(&mut self, x: i32) {
+        if self.replaying || self.mouse.x == x {
+            return;
+        }
+
+        let y = self.mouse.y;
+        self.log(InputEvent::MouseMove(x, y));
         self.mouse.x = x;
     }
 
     pub fn set_mouse_y(&mut self, y: i32) {
+        if self.replaying || self.mouse.y == y {
+            return;
+        }
+
+        let x = self.mouse.x;
+        self.log(InputEvent::MouseMove(x, y));
         self.mouse.y = y;
     }
 
     pub fn mouse_button_down(&mut self, left: bool, right: bool, middle: bool, elapsed: f64) {
+        if self.replaying {
+            return;
+        }
+
+        self.log(InputEvent::MouseDown(left, right, middle));
         self.mouse.state = 0;
 
         if left {
@@ -131,6 +265,11 @@ impl Players {
     }
 
     pub fn mouse_button_up(&mut self) {
+        if self.replaying {
+            return;
+        }
+
+        self.log(InputEvent::MouseUp);
         self.mouse.state = 0;
         self.mouse.state_quick = 0;
     }
@@ -160,6 +299,10 @@ impl Players {
     }
 
     pub fn key_down(&mut self, keymod: Mod, scancode: Scancode) {
+        if self.replaying {
+            return;
+        }
+
         debug!("SCANCODE {:?} {:?} -> DOWN",
                keymod,
                scancode);
@@ -177,19 +320,46 @@ impl Players {
             }
         }
 
+        self.scancode_down(scancode);
+        self.last_input = Some(Input::Key(scancode));
+
+        if let Some((player, key)) = self.bindings.map_key(scancode) {
+            self.key_down_direct(player, key);
+        }
+    }
+
+    /// Raw keyboard state only, the keys of the players are not changed
+    pub fn scancode_down(&mut self, scancode: Scancode) {
+        if self.replaying {
+            return;
+        }
+
+        self.log(InputEvent::ScancodeDown(scancode));
         self.akeys.insert(scancode, true);
         self.akeys_quick.insert(scancode, true);
+    }
 
-        if let (Some(key), player) = self::keys::map_keycode(scancode) {
-            self.key_down_direct(player, key);
+    pub fn scancode_up(&mut self, scancode: Scancode) {
+        if self.replaying {
+            return;
         }
+
+        self.log(InputEvent::ScancodeUp(scancode));
+        self.akeys.insert(scancode, false);
+        self.akeys_quick.insert(scancode, false);
     }
 
     pub fn key_down_direct(&mut self, player: u8, key: PX8Key) {
+        if self.replaying {
+            return;
+        }
+
         debug!("KEY {:?} Player {:?} -> DOWN",
                key,
                player);
 
+        self.log(InputEvent::KeyDown(player, key));
+
         match self.pkeys.get_mut(&player) {
             Some(keys) => {
                 keys.keys.insert(key, true);
@@ -199,6 +369,19 @@ impl Players {
         }
     }
 
+    /// Release all the keys of the players, after a change of the bindings
+    pub fn release_keys(&mut self) {
+        if self.replaying {
+            return;
+        }
+
+        self.log(InputEvent::ReleaseKeys);
+        for (_, keys) in self.pkeys.iter_mut() {
+            *keys = PlayerKeys::new();
+        }
+        self.pad_axes.clear();
+    }
+
     pub fn key_direc_hor_up(&mut self, player: u8) {
         match self.pkeys.get_mut(&player) {
             Some(keys) => {
@@ -220,6 +403,10 @@ impl Players {
     }
 
     pub fn key_up(&mut self, keymod: Mod, scancode: Scancode) {
+        if self.replaying {
+            return;
+        }
+
         debug!("SCANCODE {:?} UP", scancode);
 
         let mut scancode = scancode;
@@ -235,17 +422,62 @@ impl Players {
             }
         }
 
-        self.akeys.insert(scancode, false);
-        self.akeys_quick.insert(scancode, false);
+        self.scancode_up(scancode);
 
-        if let (Some(key), player) = self::keys::map_keycode(scancode) {
+        if let Some((player, key)) = self.bindings.map_key(scancode) {
             self.key_up_direct(player, key);
         }
     }
 
+    /// Controller or joystick button, `name` is the one used by the bindings
+    pub fn pad_button(&mut self, player: u8, name: &str, down: bool) {
+        if self.replaying {
+            return;
+        }
+
+        if down {
+            self.last_input = Some(Input::Pad(name.to_string()));
+        }
+
+        if let Some(key) = self.bindings.map_pad(player, name) {
+            if down {
+                self.key_down_direct(player, key);
+            } else {
+                self.key_up_direct(player, key);
+            }
+        }
+    }
+
+    /// Controller or joystick axis, each direction is bound like a button
+    /// ("-leftx" and "+leftx")
+    pub fn pad_axis(&mut self, player: u8, name: &str, value: i16) {
+        if self.replaying {
+            return;
+        }
+
+        let directions = [(format!("-{}", name), value <= -AXIS_THRESHOLD),
+                          (format!("+{}", name), value >= AXIS_THRESHOLD)];
+
+        for &(ref direction, down) in directions.iter() {
+            let previous = self.pad_axes
+                .insert((player, direction.clone()), down)
+                .unwrap_or(false);
+
+            if previous != down {
+                self.pad_button(player, direction, down);
+            }
+        }
+    }
+
     pub fn key_up_direct(&mut self, player: u8, key: PX8Key) {
+        if self.replaying {
+            return;
+        }
+
         debug!("KEY {:?} Player {:?} -> UP", key, player);
 
+        self.log(InputEvent::KeyUp(player, key));
+
         match self.pkeys.get_mut(&player) {
             Some(keys) => {
                 keys.keys.insert(key, false);
@@ -255,45 +487,60 @@ impl Players {
         }
     }
 
+    /// State of the button `index` (see keys::PLAYER_KEYS for the order)
     pub fn get_value(&self, player: u8, index: u8) -> bool {
-        match self.pkeys.get(&player) {
-            Some(keys) => {
-                match index {
-                    0 if keys.keys[&PX8Key::Left] => true,
-                    1 if keys.keys[&PX8Key::Right] => true,
-                    2 if keys.keys[&PX8Key::Up] => true,
-                    3 if keys.keys[&PX8Key::Down] => true,
-                    4 if keys.keys[&PX8Key::A] => true,
-                    5 if keys.keys[&PX8Key::B] => true,
-                    6 if keys.keys[&PX8Key::Enter] => true,
-                    7 if keys.keys[&PX8Key::Pause] => true,
-                    _ => false,
-                }
-            }
-            None => false,
+        match (self.pkeys.get(&player), PLAYER_KEYS.get(index as usize)) {
+            (Some(keys), Some(key)) => keys.keys.get(key).cloned().unwrap_or(false),
+            _ => false,
         }
     }
 
-
     pub fn get_value_quick(&self, player: u8, index: u8) -> bool {
-        match self.pkeys.get(&player) {
-            Some(keys) => {
-                match index {
-                    0 if keys.keys_quick[&PX8Key::Left] => true,
-                    1 if keys.keys_quick[&PX8Key::Right] => true,
-                    2 if keys.keys_quick[&PX8Key::Up] => true,
-                    3 if keys.keys_quick[&PX8Key::Down] => true,
-                    4 if keys.keys_quick[&PX8Key::A] => true,
-                    5 if keys.keys_quick[&PX8Key::B] => true,
-                    6 if keys.keys_quick[&PX8Key::Enter] => true,
-                    7 if keys.keys_quick[&PX8Key::Pause] => true,
-                    _ => false,
-                }
-            }
-            None => false,
+        match (self.pkeys.get(&player), PLAYER_KEYS.get(index as usize)) {
+            (Some(keys), Some(key)) => keys.keys_quick.get(key).cloned().unwrap_or(false),
+            _ => false,
         }
     }
 
+    /// Analog value of an axis, between -1 and 1
+    pub fn set_axis(&mut self, player: u8, index: usize, value: f64) {
+        if self.replaying || index >= NB_AXES || !self.pkeys.contains_key(&player) {
+            return;
+        }
+
+        let value = value.max(-1.0).min(1.0);
+        self.log(InputEvent::Axis(player, index, value));
+
+        if let Some(keys) = self.pkeys.get_mut(&player) {
+            keys.axes[index] = value;
+        }
+    }
+
+    /// Analog value of an axis, with the deadzone of the player removed
+    pub fn axis(&self, player: u8, index: usize) -> f64 {
+        let axes = match self.pkeys.get(&player) {
+            Some(keys) if index < NB_AXES => keys.axes,
+            _ => return 0.0,
+        };
+
+        let deadzone = self.bindings.deadzone(player);
+
+        // The deadzone of a stick is a circle, to keep the diagonals smooth
+        let magnitude = if index < 4 {
+            let stick = index - index % 2;
+            (axes[stick] * axes[stick] + axes[stick + 1] * axes[stick + 1]).sqrt()
+        } else {
+            axes[index].abs()
+        };
+
+        if magnitude <= deadzone {
+            return 0.0;
+        }
+
+        let scale = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0) / magnitude;
+        axes[index] * scale
+    }
+
     pub fn btn(&self, player: u8, index: u8) -> bool {
         self.get_value(player, index)
     }
@@ -331,4 +578,50 @@ impl Players {
     pub fn mouse_state_quick(&self) -> u32 {
         self.mouse.state_quick
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis_deadzone() {
+        let mut players = Players::new();
+        players.bindings.players[0].deadzone = 0.2;
+
+        players.set_axis(0, 0, 0.1);
+        players.set_axis(0, 1, 0.1);
+        assert_eq!(players.axis(0, 0), 0.0);
+
+        players.set_axis(0, 0, 1.0);
+        players.set_axis(0, 1, 0.0);
+        assert_eq!(players.axis(0, 0), 1.0);
+        assert_eq!(players.axis(0, 1), 0.0);
+
+        players.set_axis(0, 4, 0.6);
+        assert!((players.axis(0, 4) - 0.5).abs() < 1e-9);
+
+        players.set_axis(0, 5, -3.0);
+        assert_eq!(players.axis(0, 5), -1.0);
+        assert_eq!(players.axis(8, 0), 0.0);
+    }
+
+    #[test]
+    fn test_restore_state() {
+        let mut players = Players::new();
+        players.set_axis(0, 0, 1.0);
+        let state = players.save_state();
+
+        players.set_axis(0, 0, -1.0);
+        players.bindings.players[0].deadzone = 0.3;
+        players.start_journal();
+        players.set_replaying(true);
+
+        players.restore_state(&state);
+        assert_eq!(players.axis(0, 0), 1.0);
+
+        // Only the inputs are restored
+        assert_eq!(players.bindings.players[0].deadzone, 0.3);
+        assert!(players.is_replaying());
+        assert!(!players.take_journal().is_empty());
+    }
+}
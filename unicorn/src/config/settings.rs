@@ -0,0 +1,170 @@
+use config::files;
+
+/* SETTINGS FILE (JSON)
+
+{
+  "volume": 128,
+  "palette": "db32",
+  "scale": 4,
+  "fullscreen": false,
+  "info_overlay": false
+}
+
+volume: volume of the music, between 0 and MAX_VOLUME
+palette: used instead of the palette of the cartridges, "" to keep it
+scale, fullscreen: display of the frontend, missing to keep the command
+line options
+
+*/
+
+/// Volume of the chiptune player at full scale
+pub const MAX_VOLUME: i32 = 128;
+
+fn default_volume() -> i32 {
+    MAX_VOLUME
+}
+
+pub fn default_filename() -> String {
+    files::config_path("settings.json")
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SettingsFile {
+    #[serde(default = "default_volume")]
+    volume: i32,
+    #[serde(default)]
+    palette: String,
+    #[serde(default)]
+    scale: Option<usize>,
+    #[serde(default)]
+    fullscreen: Option<bool>,
+    #[serde(default)]
+    info_overlay: bool,
+}
+
+/// Options of the console chosen in the pause menu, loaded from and saved
+/// in a JSON file.
+#[derive(Clone)]
+pub struct Settings {
+    pub filename: String,
+    pub volume: i32,
+    pub palette: String,
+    pub scale: Option<usize>,
+    pub fullscreen: Option<bool>,
+    pub info_overlay: bool,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            filename: default_filename(),
+            volume: MAX_VOLUME,
+            palette: "".to_string(),
+            scale: None,
+            fullscreen: None,
+            info_overlay: false,
+        }
+    }
+
+    /// Load the settings saved in `filename`, the default ones are used if
+    /// the file doesn't exist or is invalid.
+    pub fn load(filename: &str) -> Settings {
+        let mut settings = Settings::new();
+        settings.filename = filename.to_string();
+
+        if let Some(file) = files::load_json::<SettingsFile>("Settings", filename) {
+            settings.volume = file.volume.max(0).min(MAX_VOLUME);
+            settings.palette = file.palette;
+            settings.scale = file.scale;
+            settings.fullscreen = file.fullscreen;
+            settings.info_overlay = file.info_overlay;
+        }
+
+        settings
+    }
+
+    pub fn save(&self) -> bool {
+        let file = SettingsFile {
+            volume: self.volume,
+            palette: self.palette.clone(),
+            scale: self.scale,
+            fullscreen: self.fullscreen,
+            info_overlay: self.info_overlay,
+        };
+        files::save_json("Settings", &self.filename, &file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use super::*;
+
+    fn test_filename(name: &str) -> String {
+        let mut dir = env::temp_dir();
+        dir.push("unicorn-settings-test");
+        dir.push(name);
+        dir.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let filename = test_filename("missing.json");
+        let _ = fs::remove_file(&filename);
+
+        let settings = Settings::load(&filename);
+        assert_eq!(settings.filename, filename);
+        assert_eq!(settings.volume, MAX_VOLUME);
+        assert_eq!(settings.palette, "");
+        assert_eq!(settings.scale, None);
+        assert_eq!(settings.fullscreen, None);
+        assert!(!settings.info_overlay);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let filename = test_filename("round_trip.json");
+
+        let mut settings = Settings::load(&filename);
+        settings.volume = 64;
+        settings.palette = "db32".to_string();
+        settings.scale = Some(2);
+        settings.fullscreen = Some(true);
+        settings.info_overlay = true;
+        assert!(settings.save());
+
+        let settings = Settings::load(&filename);
+        assert_eq!(settings.volume, 64);
+        assert_eq!(settings.palette, "db32");
+        assert_eq!(settings.scale, Some(2));
+        assert_eq!(settings.fullscreen, Some(true));
+        assert!(settings.info_overlay);
+
+        fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_invalid() {
+        let filename = test_filename("invalid.json");
+        fs::create_dir_all(Path::new(&filename).parent().unwrap()).unwrap();
+
+        File::create(&filename).unwrap().write_all(b"{\"volume\": ").unwrap();
+        let settings = Settings::load(&filename);
+        assert_eq!(settings.volume, MAX_VOLUME);
+        assert_eq!(settings.scale, None);
+
+        // Missing fields are the default ones, the volume is clamped
+        File::create(&filename).unwrap().write_all(b"{\"volume\": 1000, \"scale\": 3}").unwrap();
+        let settings = Settings::load(&filename);
+        assert_eq!(settings.volume, MAX_VOLUME);
+        assert_eq!(settings.palette, "");
+        assert_eq!(settings.scale, Some(3));
+
+        fs::remove_file(&filename).unwrap();
+    }
+}
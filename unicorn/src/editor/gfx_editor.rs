@@ -1,4 +1,4 @@
-use gfx::Screen;
+use gfx::{Screen, MAP_LAYERS, map_index};
 use config::Players;
 use std::sync::{Arc, Mutex};
 use std::cmp::{max, min};
@@ -199,6 +199,9 @@ impl Flags {
     }
 }
 
+/// Keys selecting the edited map layer
+const MAP_LAYER_KEYS: [Scancode; 4] = [Scancode::Num1, Scancode::Num2, Scancode::Num3, Scancode::Num4];
+
 #[derive(Debug)]
 pub enum EditorState {
     SpriteEditor,
@@ -213,7 +216,9 @@ pub struct MapEditor {
     available_zooms: [f32; 3],
     idx_zoom: u32,
     zoom: f32,
-    cache: [u32; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT],
+    cache: Vec<Vec<u32>>,
+    layer: usize,
+    visible_layers: [bool; MAP_LAYERS],
     select_field: [i32; 2],
     size_sprite: i32,
     current_sprite: [u32; 2],
@@ -231,7 +236,9 @@ impl MapEditor {
             available_zooms: [1., 0.5, 0.25],
             idx_zoom: 0,
             zoom: 1.,
-            cache: [0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT],
+            cache: vec![vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT]; MAP_LAYERS],
+            layer: 0,
+            visible_layers: [true; MAP_LAYERS],
             select_field: [0, 8],
             size_sprite: 8,
             current_sprite: [0, 0],
@@ -243,11 +250,7 @@ impl MapEditor {
     pub fn init(&mut self, screen: &mut Screen) {
         info!("[EDITOR][GFX][MAP] Init");
 
-        for y in 0..unicorn::MAP_HEIGHT {
-            for x in 0..unicorn::MAP_WIDTH {
-                self.cache[x + y * unicorn::MAP_WIDTH] = screen.mget(x as i32, y as i32);
-            }
-        }
+        self.cache = screen.map.clone();
     }
 
     pub fn update(&mut self, players: Arc<Mutex<Players>>, screen: &mut Screen) {
@@ -284,6 +287,17 @@ impl MapEditor {
             self.size_sprite = (8. * self.zoom).floor() as i32;
         }
 
+        for (layer, key) in MAP_LAYER_KEYS.iter().take(MAP_LAYERS).enumerate() {
+            if players.lock().unwrap().btnp_raw(*key) {
+                info!("[EDITOR][GFX][MAP] Edit layer {:?}", layer);
+                self.layer = layer;
+            }
+        }
+
+        if players.lock().unwrap().btnp_raw(Scancode::V) {
+            self.visible_layers[self.layer] = !self.visible_layers[self.layer];
+        }
+
         let mouse_x = self.state.lock().unwrap().mouse_x;
         let mouse_y = self.state.lock().unwrap().mouse_y;
 
@@ -317,10 +331,17 @@ impl MapEditor {
                             let current_sprite = self.state.lock().unwrap().current_sprite + x +
                                                  y * 16;
 
-                            let idx = ((new_x + x) as f64 + (new_y + y) as f64 * unicorn::MAP_WIDTH as f64).floor() as
-                                      usize;
-                            self.cache[idx] = current_sprite;
-                            screen.mset((new_x + x) as i32, (new_y + y) as i32, current_sprite);
+                            let map_x = (new_x + x) as usize;
+                            let map_y = (new_y + y) as usize;
+                            if map_x >= unicorn::MAP_WIDTH || map_y >= unicorn::MAP_HEIGHT {
+                                continue;
+                            }
+
+                            self.cache[self.layer][map_index(map_x, map_y)] = current_sprite;
+                            screen.mset(map_x as i32,
+                                        map_y as i32,
+                                        current_sprite,
+                                        self.layer as u32);
                         }
                     }
                 }
@@ -339,41 +360,47 @@ impl MapEditor {
                         0);
         screen.rectfill(self.coord[2], self.coord[1], 240, self.coord[3], 5);
 
-        // draw map
-        let mut idx_y = 0;
-        for y in self.offset_y as u32..
-                 min(unicorn::MAP_HEIGHT as u32,
-                     self.offset_y as u32 + (self.sprites_per_y / self.zoom).floor() as u32) {
-            let mut idx_x = 0;
-
-            for x in self.offset_x as u32..
-                     min(unicorn::MAP_WIDTH as u32,
-                         self.offset_x as u32 + (self.sprites_per_x / self.zoom).floor() as u32) {
-                let offset = x + y * unicorn::MAP_WIDTH as u32;
-
-                let sprite_number = self.cache[offset as usize];
-                if sprite_number != 0 {
-                    let sprite_x = (sprite_number % 16) * 8;
-                    let sprite_y = (sprite_number as f32 / 16.).floor() as i32 * 8;
-
-                    let dx = idx_x * ((8. * self.zoom).floor() as i32);
-                    let dy = idx_y * ((8. * self.zoom).floor() as i32) + 9;
-                    screen.sspr(sprite_x as u32,
-                                sprite_y as u32,
-                                8,
-                                8,
-                                dx,
-                                dy,
-                                (self.zoom * 8.).floor() as u32,
-                                (self.zoom * 8.).floor() as u32,
-                                false,
-                                false);
+        // draw the visible map layers, the first one at the bottom
+        for layer in 0..self.cache.len() {
+            if !self.visible_layers[layer] {
+                continue;
+            }
+
+            let mut idx_y = 0;
+            for y in self.offset_y as u32..
+                     min(unicorn::MAP_HEIGHT as u32,
+                         self.offset_y as u32 + (self.sprites_per_y / self.zoom).floor() as u32) {
+                let mut idx_x = 0;
+
+                for x in self.offset_x as u32..
+                         min(unicorn::MAP_WIDTH as u32,
+                             self.offset_x as u32 + (self.sprites_per_x / self.zoom).floor() as u32) {
+                    let offset = map_index(x as usize, y as usize);
+
+                    let sprite_number = self.cache[layer][offset];
+                    if sprite_number != 0 {
+                        let sprite_x = (sprite_number % 16) * 8;
+                        let sprite_y = (sprite_number as f32 / 16.).floor() as i32 * 8;
+
+                        let dx = idx_x * ((8. * self.zoom).floor() as i32);
+                        let dy = idx_y * ((8. * self.zoom).floor() as i32) + 9;
+                        screen.sspr(sprite_x as u32,
+                                    sprite_y as u32,
+                                    8,
+                                    8,
+                                    dx,
+                                    dy,
+                                    (self.zoom * 8.).floor() as u32,
+                                    (self.zoom * 8.).floor() as u32,
+                                    false,
+                                    false);
+                    }
+
+                    idx_x += 1
                 }
 
-                idx_x += 1
+                idx_y += 1
             }
-
-            idx_y += 1
         }
 
 
@@ -390,11 +417,18 @@ impl MapEditor {
         screen.print(format!("{:?} {:?}: {:?}",
                              self.current_sprite[0],
                              self.current_sprite[1],
-                             self.cache[(self.current_sprite[0] + self.current_sprite[1] * 128) as
-                             usize]),
+                             self.cache[self.layer][map_index(self.current_sprite[0] as usize,
+                                                              self.current_sprite[1] as usize)]),
                      210,
                      25,
                      7);
+
+        screen.print(format!("L{:?}{}",
+                             self.layer + 1,
+                             if self.visible_layers[self.layer] { "" } else { " HID" }),
+                     210,
+                     33,
+                     7);
     }
 }
 
@@ -887,17 +921,20 @@ impl SpritesMap {
             let idx_sprites_batch_x = self.state.lock().unwrap().idx_sprites_batch_x;
             let idx_sprites_batch_y = self.state.lock().unwrap().idx_sprites_batch_y;
 
+            let sprites_per_row = screen.sprites_per_row() as u32;
+            let width = screen.mode_width() as i32;
+
             if ((mouse_y >= idx_sprites_batch_y) && (mouse_y < (idx_sprites_batch_y + 60))) &&
-               ((mouse_x >= idx_sprites_batch_x) && (mouse_x <= (idx_sprites_batch_x + 400))) {
+               ((mouse_x >= idx_sprites_batch_x) && (mouse_x < (idx_sprites_batch_x + width))) {
 
                 let y = ((mouse_y - idx_sprites_batch_y) as f64 / 8.).floor() as u32;
                 let x = ((mouse_x - idx_sprites_batch_x)as f64 / 8.).floor() as u32;
 
-                self.state.lock().unwrap().current_sprite = x + y * 50;
+                self.state.lock().unwrap().current_sprite = x + y * sprites_per_row;
 
                 let current_sprite = self.state.lock().unwrap().current_sprite;
-                self.state.lock().unwrap().x_zoom_sprite = (current_sprite % 50) * 8;
-                self.state.lock().unwrap().y_zoom_sprite = (current_sprite as f64 / 50.).floor() as u32 * 8;
+                self.state.lock().unwrap().x_zoom_sprite = (current_sprite % sprites_per_row) * 8;
+                self.state.lock().unwrap().y_zoom_sprite = (current_sprite / sprites_per_row) * 8;
             }
         }
 
@@ -927,15 +964,18 @@ impl SpritesMap {
 
         let mut y = idx_sprites_batch_y;
 
+        let sprites_per_row = screen.sprites_per_row();
+        let width = screen.mode_width() as i32;
+
         // Draw black screen
         screen.rectfill(idx_sprites_batch_x, idx_sprites_batch_y,
-                        idx_sprites_batch_x + 400, idx_sprites_batch_y + 60,
+                        idx_sprites_batch_x + width, idx_sprites_batch_y + 60,
                         0);
         
         for _ in 0..2 {
             for _ in 0..4 {
                 let mut x = idx_sprites_batch_x;
-                for _ in 0..50 {
+                for _ in 0..sprites_per_row {
                     screen.spr(idx, x, y, 1, 1, false, false, 0., 1.0, false);
                     if idx == self.state.lock().unwrap().current_sprite {
                         current_sprite_x = x;
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+/* BDF FONT (Glyph Bitmap Distribution Format), only what is needed to draw
+
+STARTFONT 2.1
+FONT -misc-tiny-medium-r-normal--8-80-75-75-c-60-iso10646-1
+SIZE 8 75 75
+FONTBOUNDINGBOX 5 8 0 -1
+STARTPROPERTIES 3
+FAMILY_NAME "tiny"     <- name used by font(), FONT otherwise
+FONT_ASCENT 7
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 1
+STARTCHAR A
+ENCODING 65            <- unicode codepoint
+DWIDTH 6 0             <- advance width
+BBX 5 7 0 0            <- width height x_offset y_offset (from the baseline)
+BITMAP
+70                     <- one line per row, first pixel in the MSB
+...
+ENDCHAR
+ENDFONT
+
+*/
+
+pub struct Glyph {
+    pub width: i32,
+    pub height: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+    /// Horizontal distance to the next character
+    pub advance: i32,
+    /// width * height pixels, row by row
+    pub pixels: Vec<bool>,
+}
+
+/// Proportional font with glyphs indexed by unicode codepoint
+pub struct BitmapFont {
+    pub name: String,
+    /// Pixels above the baseline
+    pub ascent: i32,
+    /// Pixels below the baseline
+    pub descent: i32,
+    pub glyphs: HashMap<u32, Glyph>,
+    /// Codepoint drawn for the characters missing in the font
+    pub default_char: Option<u32>,
+}
+
+fn parse_values(keyword: &str, values: &[&str], count: usize) -> Result<Vec<i32>, String> {
+    if values.len() < count {
+        return Err(format!("Missing values for {}", keyword));
+    }
+
+    let mut result = Vec::new();
+    for value in values.iter().take(count) {
+        match value.parse::<i32>() {
+            Ok(v) => result.push(v),
+            Err(_) => return Err(format!("Bad value {:?} for {}", value, keyword)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// The rows of the bitmap are read on 32 bits
+const MAX_GLYPH_WIDTH: i32 = 32;
+const MAX_GLYPH_HEIGHT: i32 = 64;
+
+impl BitmapFont {
+    pub fn from_bdf(lines: &[String]) -> Result<BitmapFont, String> {
+        let mut font_name = "".to_string();
+        let mut family_name = "".to_string();
+        let mut bounding_box = (0, 0, 0, 0);
+        let mut ascent = None;
+        let mut descent = None;
+        let mut default_char = None;
+        let mut glyphs = HashMap::new();
+
+        let mut encoding: Option<u32> = None;
+        let mut advance = None;
+        let mut bbx = None;
+        let mut rows: Option<Vec<u32>> = None;
+
+        for (y, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let keyword = match fields.first() {
+                Some(keyword) => *keyword,
+                None => continue,
+            };
+            let values = &fields[1..];
+
+            if rows.is_some() && keyword != "ENDCHAR" {
+                let row = match u32::from_str_radix(keyword, 16) {
+                    Ok(row) if keyword.len() <= 8 => row,
+                    _ => return Err(format!("Bad bitmap row {:?} at line {}", keyword, y + 1)),
+                };
+                // Left aligned on 32 bits, whatever the number of bytes of the line
+                rows.as_mut().unwrap().push(row << (32 - 4 * keyword.len() as u32));
+                continue;
+            }
+
+            match keyword {
+                "FONT" => font_name = values.join(" "),
+                "FAMILY_NAME" => family_name = values.join(" ").trim_matches('"').to_string(),
+                "FONTBOUNDINGBOX" => {
+                    let v = try!(parse_values(keyword, values, 4));
+                    bounding_box = (v[0], v[1], v[2], v[3]);
+                }
+                "FONT_ASCENT" => ascent = Some(try!(parse_values(keyword, values, 1))[0]),
+                "FONT_DESCENT" => descent = Some(try!(parse_values(keyword, values, 1))[0]),
+                "DEFAULT_CHAR" => default_char = Some(try!(parse_values(keyword, values, 1))[0] as u32),
+                "STARTCHAR" => {
+                    encoding = None;
+                    advance = None;
+                    bbx = None;
+                }
+                "ENCODING" => {
+                    // -1: glyph without standard encoding, it can't be drawn
+                    let v = try!(parse_values(keyword, values, 1))[0];
+                    encoding = if v >= 0 { Some(v as u32) } else { None };
+                }
+                "DWIDTH" => advance = Some(try!(parse_values(keyword, values, 1))[0]),
+                "BBX" => {
+                    let v = try!(parse_values(keyword, values, 4));
+                    bbx = Some((v[0], v[1], v[2], v[3]));
+                }
+                "BITMAP" => rows = Some(Vec::new()),
+                "ENDCHAR" => {
+                    let (width, height, x_offset, y_offset) = bbx.unwrap_or(bounding_box);
+                    let data = rows.take().unwrap_or_default();
+
+                    // Checked before allocating the pixels, the font comes from the cartridge
+                    if width < 0 || width > MAX_GLYPH_WIDTH {
+                        return Err(format!("Bad glyph width ({}) at line {}", width, y + 1));
+                    }
+                    if height < 0 || height > MAX_GLYPH_HEIGHT {
+                        return Err(format!("Bad glyph height ({}) at line {}", height, y + 1));
+                    }
+
+                    let mut pixels = vec![false; (width * height) as usize];
+                    for (row_y, row) in data.iter().take(height as usize).enumerate() {
+                        for x in 0..width as usize {
+                            pixels[row_y * width as usize + x] = row & (0x8000_0000 >> x) != 0;
+                        }
+                    }
+
+                    if let Some(codepoint) = encoding {
+                        glyphs.insert(codepoint,
+                                      Glyph {
+                                          width: width,
+                                          height: height,
+                                          x_offset: x_offset,
+                                          y_offset: y_offset,
+                                          advance: advance.unwrap_or(width + 1),
+                                          pixels: pixels,
+                                      });
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if glyphs.is_empty() {
+            return Err("No glyph in the font".to_string());
+        }
+
+        let name = if !family_name.is_empty() {
+            family_name
+        } else {
+            font_name
+        };
+
+        if name.is_empty() {
+            return Err("Missing FONT name".to_string());
+        }
+
+        Ok(BitmapFont {
+               name: name,
+               ascent: ascent.unwrap_or(bounding_box.1 + bounding_box.3),
+               descent: descent.unwrap_or(-bounding_box.3),
+               glyphs: glyphs,
+               default_char: default_char,
+           })
+    }
+
+    /// Glyph of the character, or the default one (space if there is none)
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&(c as u32))
+            .or_else(|| self.default_char.and_then(|d| self.glyphs.get(&d)))
+            .or_else(|| self.glyphs.get(&32))
+    }
+
+    pub fn advance(&self, c: char) -> i32 {
+        match self.glyph(c) {
+            Some(glyph) => glyph.advance,
+            None => self.line_height() / 2,
+        }
+    }
+
+    /// Vertical distance between lines
+    pub fn line_height(&self) -> i32 {
+        self.ascent + self.descent + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bdf() {
+        let data = "STARTFONT 2.1
+FONT -misc-tiny-medium-r-normal--8-80-75-75-c-60-iso10646-1
+FONTBOUNDINGBOX 5 8 0 -1
+STARTPROPERTIES 3
+FAMILY_NAME \"tiny\"
+FONT_ASCENT 7
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR i
+ENCODING 105
+DWIDTH 2 0
+BBX 1 3 0 0
+BITMAP
+80
+00
+80
+ENDCHAR
+STARTCHAR eacute
+ENCODING 233
+DWIDTH 6 0
+BBX 5 2 0 5
+BITMAP
+1000
+F8
+ENDCHAR
+ENDFONT";
+        let lines: Vec<String> = data.lines().map(|l| l.to_string()).collect();
+        let font = BitmapFont::from_bdf(&lines).unwrap();
+
+        assert_eq!(font.name, "tiny");
+        assert_eq!(font.line_height(), 9);
+
+        let glyph = font.glyph('i').unwrap();
+        assert_eq!(glyph.advance, 2);
+        assert_eq!(glyph.pixels, vec![true, false, true]);
+
+        let glyph = font.glyph('é').unwrap();
+        assert_eq!(glyph.advance, 6);
+        assert_eq!(glyph.y_offset, 5);
+        assert_eq!(glyph.pixels[3], true);
+        assert_eq!(glyph.pixels[5..].to_vec(), vec![true; 5]);
+
+        // No default char and no space
+        assert!(font.glyph('z').is_none());
+        assert_eq!(font.advance('z'), 4);
+
+        assert!(BitmapFont::from_bdf(&vec!["STARTFONT 2.1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_from_bdf_bad_bbx() {
+        for bbx in &["BBX -1 3 0 0", "BBX 1 -3 0 0", "BBX 33 3 0 0", "BBX 1 65 0 0",
+                     "BBX 2147483647 2147483647 0 0"] {
+            let data = format!("STARTFONT 2.1
+FONT tiny
+FONTBOUNDINGBOX 5 8 0 -1
+STARTCHAR i
+ENCODING 105
+{}
+BITMAP
+80
+ENDCHAR
+ENDFONT",
+                               bbx);
+            let lines: Vec<String> = data.lines().map(|l| l.to_string()).collect();
+            assert!(BitmapFont::from_bdf(&lines).is_err(), "{}", bbx);
+        }
+
+        // The default bounding box is checked too
+        let lines: Vec<String> = "FONT tiny
+FONTBOUNDINGBOX 5 -8 0 -1
+STARTCHAR i
+ENCODING 105
+ENDCHAR"
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        assert!(BitmapFont::from_bdf(&lines).is_err());
+    }
+}
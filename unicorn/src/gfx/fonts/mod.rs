@@ -3,3 +3,5 @@ pub mod bbc;
 pub mod cbmii;
 pub mod appleii;
 pub mod trollmini;
+pub mod bdf;
+pub mod ttf;
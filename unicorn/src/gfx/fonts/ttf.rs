@@ -0,0 +1,245 @@
+use rusttype;
+use rusttype::{point, FontCollection, Scale};
+
+/// How the antialiased coverage of the glyphs becomes palette pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TtfMode {
+    /// Pixels covered at least by half
+    Threshold,
+    /// 4x4 ordered dithering of the coverage
+    Dither,
+}
+
+impl TtfMode {
+    /// "dither", the other names give the threshold mode
+    pub fn from_name(name: &str) -> TtfMode {
+        match name {
+            "dither" => TtfMode::Dither,
+            _ => TtfMode::Threshold,
+        }
+    }
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+static BAYER: [u8; 16] = [
+    0, 8, 2, 10,
+    12, 4, 14, 6,
+    3, 11, 1, 9,
+    15, 7, 13, 5,
+];
+
+/// Is the pixel (x, y) drawn for this coverage (0.0 to 1.0)
+pub fn is_set(mode: TtfMode, x: i32, y: i32, coverage: f32) -> bool {
+    match mode {
+        TtfMode::Threshold => coverage >= 0.5,
+        TtfMode::Dither => {
+            let level = BAYER[((y & 3) * 4 + (x & 3)) as usize];
+            coverage > (level as f32 + 0.5) / 16.0
+        }
+    }
+}
+
+/// TrueType/OpenType font of a cartridge
+pub struct TtfFont {
+    pub name: String,
+    font: rusttype::Font<'static>,
+}
+
+impl TtfFont {
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> Result<TtfFont, String> {
+        match FontCollection::from_bytes(data).into_font() {
+            Some(font) => {
+                Ok(TtfFont {
+                       name: name.to_string(),
+                       font: font,
+                   })
+            }
+            None => Err(format!("Invalid TTF font {:?}", name)),
+        }
+    }
+
+    /// Coverage of the pixels of the text drawn at `size` pixels, from the
+    /// top left corner of the line
+    pub fn rasterize(&self, text: &str, size: f32) -> Vec<(i32, i32, f32)> {
+        let scale = Scale::uniform(size);
+        let ascent = self.font.v_metrics(scale).ascent;
+
+        let mut pixels = Vec::new();
+        for glyph in self.font.layout(text, scale, point(0.0, ascent)) {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|x, y, v| if v > 0.0 {
+                               pixels.push((bb.min.x + x as i32, bb.min.y + y as i32, v));
+                           });
+            }
+        }
+
+        pixels
+    }
+
+    /// Width of the text drawn at `size` pixels
+    pub fn width(&self, text: &str, size: f32) -> i32 {
+        let scale = Scale::uniform(size);
+
+        match self.font.layout(text, scale, point(0.0, 0.0)).last() {
+            Some(glyph) => {
+                let advance = glyph.unpositioned().h_metrics().advance_width;
+                (glyph.position().x + advance).ceil() as i32
+            }
+            None => 0,
+        }
+    }
+
+    /// Vertical distance between lines at `size` pixels
+    pub fn line_height(&self, size: f32) -> i32 {
+        let v_metrics = self.font.v_metrics(Scale::uniform(size));
+        (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn push_u16(data: &mut Vec<u8>, value: u16) {
+        data.push((value >> 8) as u8);
+        data.push(value as u8);
+    }
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        push_u16(data, (value >> 16) as u16);
+        push_u16(data, value as u16);
+    }
+
+    /// TrueType font with only 'A', a rectangle of 1100x1000 units drawn
+    /// on the baseline. The line is 1000 units high, so a size of 8 gives a
+    /// 8.8x8 pixels rectangle.
+    fn test_font() -> Vec<u8> {
+        let mut cmap = Vec::new();
+        push_u16(&mut cmap, 0); // version
+        push_u16(&mut cmap, 1); // encoding records
+        push_u16(&mut cmap, 3); // Microsoft
+        push_u16(&mut cmap, 1); // unicode BMP
+        push_u32(&mut cmap, 12);
+        // format 4, 'A' and the final 0xFFFF segment
+        for value in &[4, 32, 0, 4, 4, 1, 0, 65, 0xFFFF, 0, 65, 0xFFFF, (1 - 65i32) as u16, 1, 0, 0] {
+            push_u16(&mut cmap, *value);
+        }
+
+        let mut glyf = Vec::new();
+        for value in &[1, 0, 0, 1100, 1000, 3, 0] {
+            push_u16(&mut glyf, *value);
+        }
+        glyf.extend_from_slice(&[1, 1, 1, 1]); // on curve points
+        for value in &[0, 0, 1100, 0, 0, 1000, 0, -1000i16 as u16] {
+            push_u16(&mut glyf, *value);
+        }
+
+        let mut head = vec![0; 54];
+        head[0..4].copy_from_slice(&[0, 1, 0, 0]);
+        head[18..20].copy_from_slice(&[0x03, 0xE8]); // 1000 units per em
+
+        let mut hhea = vec![0; 36];
+        hhea[0..4].copy_from_slice(&[0, 1, 0, 0]);
+        hhea[4..6].copy_from_slice(&[0x03, 0xE8]); // ascent
+        hhea[34..36].copy_from_slice(&[0, 2]); // horizontal metrics
+
+        let mut hmtx = Vec::new();
+        for value in &[500, 0, 1200, 0] {
+            push_u16(&mut hmtx, *value);
+        }
+
+        let mut loca = Vec::new();
+        for value in &[0, 0, glyf.len() as u16 / 2] {
+            push_u16(&mut loca, *value);
+        }
+
+        let mut maxp = Vec::new();
+        push_u32(&mut maxp, 0x5000);
+        push_u16(&mut maxp, 2);
+
+        let tables: Vec<(&[u8], Vec<u8>)> = vec![(&b"cmap"[..], cmap),
+                                                 (&b"glyf"[..], glyf),
+                                                 (&b"head"[..], head),
+                                                 (&b"hhea"[..], hhea),
+                                                 (&b"hmtx"[..], hmtx),
+                                                 (&b"loca"[..], loca),
+                                                 (&b"maxp"[..], maxp)];
+
+        let mut font = Vec::new();
+        push_u32(&mut font, 0x10000);
+        push_u16(&mut font, tables.len() as u16);
+        for _ in 0..3 {
+            push_u16(&mut font, 0);
+        }
+
+        let mut offset = 12 + 16 * tables.len();
+        for &(tag, ref table) in &tables {
+            font.extend_from_slice(tag);
+            push_u32(&mut font, 0);
+            push_u32(&mut font, offset as u32);
+            push_u32(&mut font, table.len() as u32);
+            offset += (table.len() + 3) & !3;
+        }
+        for &(_, ref table) in &tables {
+            font.extend_from_slice(table);
+            while font.len() % 4 != 0 {
+                font.push(0);
+            }
+        }
+
+        font
+    }
+
+    fn set_pixels(font: &TtfFont, mode: TtfMode) -> HashSet<(i32, i32)> {
+        font.rasterize("A", 8.0)
+            .into_iter()
+            .filter(|&(x, y, coverage)| is_set(mode, x, y, coverage))
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let font = TtfFont::from_bytes("rect", test_font()).unwrap();
+        assert_eq!(font.line_height(8.0), 8);
+        assert_eq!(font.width("A", 8.0), 10);
+
+        // The last column is covered at 80%
+        let pixels = set_pixels(&font, TtfMode::Threshold);
+        assert_eq!(pixels.len(), 9 * 8);
+        assert!(pixels.contains(&(0, 0)));
+        assert!(pixels.contains(&(8, 7)));
+        assert!(!pixels.contains(&(9, 0)));
+        assert!(!pixels.contains(&(0, 8)));
+
+        // Dithered, one pixel out of 4 is missing in the last column
+        let pixels = set_pixels(&font, TtfMode::Dither);
+        assert_eq!(pixels.len(), 8 * 8 + 6);
+        assert!(pixels.contains(&(0, 0)));
+        assert!(pixels.contains(&(7, 7)));
+        assert!(pixels.contains(&(8, 0)));
+        assert!(pixels.contains(&(8, 1)));
+        assert!(pixels.contains(&(8, 2)));
+        assert!(!pixels.contains(&(8, 3)));
+        assert!(!pixels.contains(&(8, 7)));
+
+        assert!(TtfFont::from_bytes("invalid", vec![0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_is_set() {
+        assert!(is_set(TtfMode::Threshold, 0, 0, 0.5));
+        assert!(!is_set(TtfMode::Threshold, 0, 0, 0.4));
+
+        // Half of the pixels of a 4x4 block for a half coverage
+        let count = (0..16).filter(|i| is_set(TtfMode::Dither, i % 4, i / 4, 0.5)).count();
+        assert_eq!(count, 8);
+        assert!(!is_set(TtfMode::Dither, 0, 0, 0.0));
+        assert!(is_set(TtfMode::Dither, 3, 3, 1.0));
+
+        assert_eq!(TtfMode::from_name("dither"), TtfMode::Dither);
+        assert_eq!(TtfMode::from_name("other"), TtfMode::Threshold);
+    }
+}
@@ -1,6 +1,8 @@
 mod fonts;
 
 use std::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use unicorn;
 use std::cmp;
@@ -9,6 +11,23 @@ use num_traits::pow;
 use std::f64;
 use std::f64::consts::PI;
 
+/// Palette index of a pixel
+pub type Color = u8;
+
+/// Number of colors of the palette
+pub const PALETTE_SIZE: usize = 256;
+
+/// Palette index of a color given by a cartridge, None if it is outside of
+/// the palette
+#[inline]
+pub fn to_color(value: u32) -> Option<Color> {
+    if value < PALETTE_SIZE as u32 {
+        Some(value as Color)
+    } else {
+        None
+    }
+}
+
 // Fixed pitch font definition
 #[allow(dead_code)]
 pub struct Font {
@@ -29,16 +48,71 @@ pub struct Font {
     name: &'static str,
 }
 
+pub use self::fonts::bdf::{BitmapFont, Glyph};
+pub use self::fonts::ttf::{TtfFont, TtfMode};
+
+/// Horizontal alignment of the lines of print_box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    /// "center" or "right", the other names align on the left
+    pub fn from_name(name: &str) -> TextAlign {
+        match name {
+            "center" => TextAlign::Center,
+            "right" => TextAlign::Right,
+            _ => TextAlign::Left,
+        }
+    }
+}
+
+/// Font used by print: a builtin fixed pitch one, or a bitmap font loaded
+/// from a cartridge
+#[derive(Clone)]
+pub enum ScreenFont {
+    Fixed(&'static Font),
+    Bitmap(Arc<BitmapFont>),
+}
+
+impl ScreenFont {
+    pub fn name(&self) -> String {
+        match *self {
+            ScreenFont::Fixed(font) => font.name.to_string(),
+            ScreenFont::Bitmap(ref font) => font.name.clone(),
+        }
+    }
+
+    /// Horizontal distance from the character to the next one
+    pub fn advance(&self, c: char) -> i32 {
+        match *self {
+            ScreenFont::Fixed(font) => font.advance_width,
+            ScreenFont::Bitmap(ref font) => font.advance(c),
+        }
+    }
+
+    /// Vertical distance between lines
+    pub fn line_height(&self) -> i32 {
+        match *self {
+            ScreenFont::Fixed(font) => font.line_height,
+            ScreenFont::Bitmap(ref font) => font.line_height(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct DynamicSprite {
-    pub data: Vec<u32>,
+    pub data: Vec<Color>,
     pub width: u32,
     pub height: u32,
     pub flags: u8,
 }
 
 impl DynamicSprite {
-    pub fn new(d: Vec<u32>, width: u32, height: u32) -> DynamicSprite {
+    pub fn new(d: Vec<Color>, width: u32, height: u32) -> DynamicSprite {
         DynamicSprite { data: d, width: width, height: height, flags: 0 }
     }
 
@@ -46,7 +120,7 @@ impl DynamicSprite {
 
 #[derive(Copy)]
 pub struct Sprite {
-    pub data: [u32; 64],
+    pub data: [Color; 64],
     pub flags: u8,
 }
 
@@ -57,7 +131,7 @@ impl Clone for Sprite {
 }
 
 impl Sprite {
-    pub fn new(d: [u32; 64]) -> Sprite {
+    pub fn new(d: [Color; 64]) -> Sprite {
         Sprite { data: d, flags: 0 }
     }
 
@@ -85,7 +159,7 @@ impl Sprite {
         self.flags = flags;
     }
 
-    pub fn set_data(&mut self, idx: usize, col: u32) {
+    pub fn set_data(&mut self, idx: usize, col: Color) {
         self.data[idx] = col;
     }
 
@@ -118,8 +192,8 @@ impl Sprite {
         data
     }
 
-    pub fn horizontal_reflection(&self) -> [u32; 64] {
-        let mut ret: [u32; 64] = self.to_u32_64_array();
+    pub fn horizontal_reflection(&self) -> [Color; 64] {
+        let mut ret = self.data;
 
         for i in 0..4 {
             for j in 0..8 {
@@ -130,8 +204,8 @@ impl Sprite {
         ret
     }
 
-    pub fn vertical_reflection(&self) -> [u32; 64] {
-        let mut ret: [u32; 64] = self.to_u32_64_array();
+    pub fn vertical_reflection(&self) -> [Color; 64] {
+        let mut ret = self.data;
 
         for i in 0..4 {
             for j in 0..8 {
@@ -149,14 +223,6 @@ impl Sprite {
     pub fn flip_y(&self) -> Sprite {
         Sprite::new(self.vertical_reflection())
     }
-
-    pub fn to_u32_64_array(&self) -> [u32; 64] {
-        let mut arr = [0u32; 64];
-        for (place, element) in arr.iter_mut().zip(self.data.iter()) {
-            *place = *element;
-        }
-        arr
-    }
 }
 
 impl fmt::Debug for Sprite {
@@ -175,7 +241,157 @@ impl fmt::Debug for Sprite {
 
 #[cfg(test)]
 mod tests {
-    use super::Sprite;
+    use super::{Screen, Sprite, TextAlign, MAP_LAYERS, MAP_MAX_VALUE};
+    use unicorn;
+
+    #[test]
+    fn test_text_layout() {
+        // pico-8 font: 4 pixels per character, 8 per line
+        let mut screen = Screen::new(128, 128);
+
+        assert_eq!(screen.text_width("hello"), 20);
+        assert_eq!(screen.text_width("ab\nlonger"), 24);
+        assert_eq!(screen.text_height("ab\nlonger"), 16);
+
+        assert_eq!(screen.wrap_text("the quick brown fox", 40),
+                   vec!["the quick", "brown fox"]);
+        assert_eq!(screen.wrap_text("abcdefgh ij\n\nk", 16),
+                   vec!["abcd", "efgh", "ij", "", "k"]);
+
+        assert_eq!(screen.print_box("the quick brown fox".to_string(),
+                                    0,
+                                    0,
+                                    40,
+                                    0,
+                                    TextAlign::Center,
+                                    7),
+                   2);
+        assert_eq!(screen.print_box("the quick brown fox".to_string(),
+                                    0,
+                                    0,
+                                    40,
+                                    12,
+                                    TextAlign::Right,
+                                    7),
+                   1);
+    }
+
+    #[test]
+    fn test_pal_palt_pget() {
+        let mut screen = Screen::new(128, 128);
+        screen.init();
+
+        screen.pset(1, 1, 200);
+        assert_eq!(screen.pget(1, 1), 200);
+
+        screen.pal(200, 255);
+        screen.pset(2, 1, 200);
+        assert_eq!(screen.pget(2, 1), 255);
+
+        // Outside of the palette: reset
+        screen.pal(200, 256);
+        screen.pset(3, 1, 200);
+        assert_eq!(screen.pget(3, 1), 200);
+
+        screen.palt(255, true);
+        assert!(screen.is_transparent(255));
+        screen.palt(256, true);
+        screen.palt(-1, false);
+        assert!(!screen.is_transparent(255));
+        assert!(screen.is_transparent(0));
+
+        screen.rectfill(0, 0, 1, 1, -1);
+        assert_eq!(screen.pget(0, 0), screen.color as u32);
+    }
+
+    #[test]
+    fn test_fillp() {
+        let mut screen = Screen::new(128, 128);
+        screen.init();
+
+        // Checkerboard with a transparent secondary color
+        screen.fillp(0b1010_0101_1010_0101, 4, -1);
+        screen.rectfill(0, 0, 7, 7, 8);
+        assert_eq!(screen.pget(0, 0), 0);
+        assert_eq!(screen.pget(1, 0), 8);
+        assert_eq!(screen.pget(0, 1), 8);
+        assert_eq!(screen.pget(5, 5), 0);
+
+        // Upper half of a 8x8 pattern with a secondary color
+        screen.fillp(0xFFFF_FFFF_0000_0000, 8, 2);
+        screen.rectfill(0, 0, 7, 7, 8);
+        assert_eq!(screen.pget(7, 3), 2);
+        assert_eq!(screen.pget(0, 4), 8);
+
+        // Kept by the pause menu
+        screen.save();
+        assert_eq!(screen.fill_pattern.bits, 0);
+        screen.restore();
+        assert_eq!(screen.fill_pattern.bits, 0xFFFF_FFFF_0000_0000);
+
+        screen.fillp(0, 4, -1);
+        screen.rectfill(0, 0, 7, 7, 9);
+        assert_eq!(screen.pget(7, 3), 9);
+
+        // The outlines are not patterned
+        screen.cls(-1);
+        screen.fillp(0xFFFF, 4, -1);
+        screen.trigon(0, 0, 7, 0, 0, 7, 8);
+        assert_eq!(screen.pget(3, 0), 8);
+        assert_eq!(screen.pget(0, 5), 8);
+        assert_eq!(screen.pget(3, 4), 8);
+        assert_eq!(screen.pget(2, 2), 0);
+
+        screen.polygon(vec![10, 20, 20, 10], vec![10, 10, 20, 20], 7);
+        assert_eq!(screen.pget(15, 10), 7);
+        assert_eq!(screen.pget(20, 15), 7);
+        assert_eq!(screen.pget(15, 20), 7);
+        assert_eq!(screen.pget(10, 15), 7);
+        assert_eq!(screen.pget(15, 15), 0);
+    }
+
+    #[test]
+    fn test_out_of_palette_colors() {
+        let mut screen = Screen::new(128, 128);
+        screen.init();
+
+        // Ignored instead of wrapping to 0 and 254
+        screen.pset(1, 1, 256);
+        screen.pset(2, 1, -2);
+        screen.line(0, 2, 4, 2, 300);
+        screen.rectfill(0, 3, 4, 4, -5);
+        screen.circfill(8, 8, 2, 512);
+        screen.print("a".to_string(), 0, 16, 257);
+
+        for y in 0..24 {
+            for x in 0..16 {
+                assert_eq!(screen.pget(x, y), 0);
+            }
+        }
+
+        screen.pset(1, 1, 255);
+        assert_eq!(screen.pget(1, 1), 255);
+    }
+
+    #[test]
+    fn test_sset_spr_reg() {
+        let mut screen = Screen::new(128, 128);
+        screen.init();
+        screen.set_sprites(vec![Sprite::new([0; 64]); 256]);
+
+        screen.sset(9, 1, 255);
+        assert_eq!(screen.sget(9, 1), 255);
+        assert_eq!(screen.sprites[1].data[9], 255);
+
+        // Outside of the palette: ignored
+        screen.sset(9, 1, 256);
+        screen.sset(9, 1, -2);
+        assert_eq!(screen.sget(9, 1), 255);
+
+        assert_eq!(screen.spr_reg(-1, vec![1, 2, 3, 256], 2, 2), -1);
+        assert_eq!(screen.spr_reg(-1, vec![1, 2, 3, 255], 2, 2), 0);
+        assert_eq!(screen.dyn_sprites[0].data, vec![1, 2, 3, 255]);
+    }
 
     #[test]
     fn test_sprite_flags() {
@@ -203,6 +419,49 @@ mod tests {
         assert_eq!(s.is_flags_set(6), false);
         assert_eq!(s.is_flags_set(7), true);
     }
+
+    #[test]
+    fn test_map_mset_mget() {
+        let mut screen = Screen::new(128, 128);
+        let last_x = unicorn::MAP_WIDTH as i32 - 1;
+        let last_y = unicorn::MAP_HEIGHT as i32 - 1;
+
+        screen.mset(3, 1, 42, 0);
+        screen.mset(last_x, last_y, 7, 0);
+        screen.mset(unicorn::MAP_WIDTH as i32, 0, 9, 0);
+        screen.mset(3, 1, 5, 1);
+        screen.mset(3, 1, 6, MAP_LAYERS as u32);
+        screen.mset(4, 1, MAP_MAX_VALUE, 0);
+        screen.mset(5, 1, MAP_MAX_VALUE + 1, 0);
+
+        assert_eq!(screen.mget(3, 1, 0), 42);
+        assert_eq!(screen.mget(1, 3, 0), 0);
+        assert_eq!(screen.mget(last_x, last_y, 0), 7);
+        assert_eq!(screen.mget(unicorn::MAP_WIDTH as i32, 0, 0), 0);
+        assert_eq!(screen.mget(-1, 0, 0), 0);
+        assert_eq!(screen.mget(3, 1, 1), 5);
+        assert_eq!(screen.mget(3, 1, MAP_LAYERS as u32), 0);
+        assert_eq!(screen.mget(4, 1, 0), MAP_MAX_VALUE);
+        assert_eq!(screen.mget(5, 1, 0), 0);
+    }
+
+    #[test]
+    fn test_mapdraw_wrap() {
+        let mut screen = Screen::new(128, 128);
+        screen.init();
+        screen.set_sprites(vec![Sprite::new([0; 64]), Sprite::new([7; 64]), Sprite::new([9; 64])]);
+
+        let last_x = unicorn::MAP_WIDTH as i32 - 1;
+        let last_y = unicorn::MAP_HEIGHT as i32 - 1;
+        screen.mset(last_x, last_y, 1, 0);
+        screen.mset(0, 0, 2, 0);
+
+        // The cel (-1, -1) is the last one of the map
+        screen.mapdraw(-1i32 as u32, -1i32 as u32, 0, 0, 2, 2, 0, 0);
+        assert_eq!(screen.pget(0, 0), 7);
+        assert_eq!(screen.pget(8, 8), 9);
+        assert_eq!(screen.pget(8, 0), 0);
+    }
 }
 
 // Screen scaling
@@ -232,8 +491,23 @@ impl Scale {
             Scale::Scale10x => 10,
         }
     }
+
+    pub fn from_factor(factor: usize) -> Option<Scale> {
+        match factor {
+            1 => Some(Scale::Scale1x),
+            2 => Some(Scale::Scale2x),
+            3 => Some(Scale::Scale3x),
+            4 => Some(Scale::Scale4x),
+            5 => Some(Scale::Scale5x),
+            6 => Some(Scale::Scale6x),
+            8 => Some(Scale::Scale8x),
+            10 => Some(Scale::Scale10x),
+            _ => None,
+        }
+    }
 }
 
+#[derive(Copy, Clone)]
 pub struct Camera {
     pub x: i32,
     pub y: i32,
@@ -245,7 +519,42 @@ impl Camera {
     }
 }
 
+/// Pattern of the filled shapes (fillp). The pixels of the set bits are
+/// drawn with the secondary color, or skipped if it is transparent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FillPattern {
+    /// Rows from the top, the left pixel of a row in the most significant bit
+    pub bits: u64,
+    /// 4 for a 4x4 pattern (16 bits), 8 for a 8x8 one (64 bits)
+    pub size: u32,
+    /// None for a transparent secondary color
+    pub color: Option<Color>,
+}
+
+impl FillPattern {
+    pub fn new() -> FillPattern {
+        FillPattern {
+            bits: 0,
+            size: 4,
+            color: None,
+        }
+    }
+
+    /// Is the bit of the screen pixel (x, y) set, the pattern repeats over the screen
+    #[inline]
+    pub fn is_set(&self, x: i32, y: i32) -> bool {
+        if self.bits == 0 {
+            return false;
+        }
+
+        let size = self.size as i32;
+        let bit = (y & (size - 1)) * size + (x & (size - 1));
+        self.bits & (1u64 << (size * size - 1 - bit)) != 0
+    }
+}
+
 // ClipRect rectangle is exclusive of right and bottom edges
+#[derive(Copy, Clone)]
 pub struct ClipRect {
     left: i32,
     top: i32,
@@ -275,32 +584,79 @@ impl ClipRect {
     }
 }
 
+/// Number of map layers (background, collision, foreground...) of a screen
+pub const MAP_LAYERS: usize = 4;
+
+/// Biggest value of a map cell, saved with 3 hexadecimal digits
+pub const MAP_MAX_VALUE: u32 = 0xFFF;
+
+/// Offset of the cell (x, y) in a map layer, which is stored row by row.
+#[inline]
+pub fn map_index(x: usize, y: usize) -> usize {
+    y * unicorn::MAP_WIDTH + x
+}
+
 pub struct Screen {
     pub width: usize,
     pub height: usize,
     pub aspect_ratio: f32,
 
-    pub frame_buffer: Vec<u32>,
-    pub saved_frame_buffer: Vec<u32>,
+    /// One palette index per pixel, row by row
+    pub frame_buffer: Vec<Color>,
+    pub saved_frame_buffer: Vec<Color>,
     pub sprites: Vec<Sprite>,
     pub dyn_sprites: Vec<DynamicSprite>,
 
-    pub map: Vec<u32>,
+    /// One map per layer, always MAP_LAYERS of them
+    pub map: Vec<Vec<u32>>,
 
-    pub transparency_map: [bool; 256],
+    /// Colors skipped when drawing the sprites and the map (palt)
+    pub transparency_map: [bool; PALETTE_SIZE],
 
-    pub color: u32,
-    pub color_map: [u32; 0xFFF],
+    pub color: Color,
+    /// Color drawn for each color (pal)
+    pub color_map: [Color; PALETTE_SIZE],
 
     pub camera: Camera,
     pub cliprect: ClipRect,
-    
-    pub font: &'static Font,
+
+    pub fill_pattern: FillPattern,
+    /// Pattern of the cartridge while the pause menu is drawn
+    pub saved_fill_pattern: FillPattern,
+
+    pub font: ScreenFont,
+    /// Bitmap fonts of the cartridge, by name
+    pub fonts: HashMap<String, Arc<BitmapFont>>,
+
+    /// Font used by print_ttf, with its size in pixels
+    pub ttf_font: Option<Arc<TtfFont>>,
+    pub ttf_size: f32,
+    pub ttf_mode: TtfMode,
+    /// TTF fonts of the cartridge, by name
+    pub ttf_fonts: HashMap<String, Arc<TtfFont>>,
 }
 
 unsafe impl Send for Screen {}
 unsafe impl Sync for Screen {}
 
+// Copy of everything a cartridge can change on the screen, used by the console snapshots
+pub struct ScreenState {
+    pub frame_buffer: Vec<Color>,
+    pub sprites: Vec<Sprite>,
+    pub dyn_sprites: Vec<DynamicSprite>,
+    pub map: Vec<Vec<u32>>,
+    pub transparency_map: [bool; PALETTE_SIZE],
+    pub color: Color,
+    pub color_map: [Color; PALETTE_SIZE],
+    pub camera: Camera,
+    pub cliprect: ClipRect,
+    pub fill_pattern: FillPattern,
+    pub font: ScreenFont,
+    pub ttf_font: Option<Arc<TtfFont>>,
+    pub ttf_size: f32,
+    pub ttf_mode: TtfMode,
+}
+
 impl Screen {
     pub fn new(width: usize, height: usize) -> Screen {
         info!("Creating Screen. width:{} height:{}", width, height);
@@ -312,13 +668,20 @@ impl Screen {
             aspect_ratio: width as f32 / height as f32,
             sprites: Vec::new(),
             dyn_sprites: Vec::new(),
-            map: Vec::new(),
-            transparency_map: [false; 256],
-            color_map: [0; 0xFFF],
+            map: vec![vec![0; unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT]; MAP_LAYERS],
+            transparency_map: [false; PALETTE_SIZE],
+            color_map: [0; PALETTE_SIZE],
             color: 0,
             camera: Camera::new(),
             cliprect: ClipRect::new(),
-            font: &fonts::pico8::FONT,
+            fill_pattern: FillPattern::new(),
+            saved_fill_pattern: FillPattern::new(),
+            font: ScreenFont::Fixed(&fonts::pico8::FONT),
+            fonts: HashMap::new(),
+            ttf_font: None,
+            ttf_size: 8.0,
+            ttf_mode: TtfMode::Threshold,
+            ttf_fonts: HashMap::new(),
         }
     }
 
@@ -327,6 +690,29 @@ impl Screen {
         self._reset_transparency();
         self._reset_cliprect();
         self.color = 0;
+        self.fill_pattern = FillPattern::new();
+    }
+
+    /// Change the resolution, used by the cartridges that declare their own
+    /// one. The content of the screen is lost.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        info!("[GFX] Resize Screen. width:{} height:{}", width, height);
+
+        self.width = width;
+        self.height = height;
+        self.frame_buffer = vec![0; width * height];
+        self.saved_frame_buffer = vec![0; width * height];
+        self.aspect_ratio = width as f32 / height as f32;
+        self._reset_cliprect();
+    }
+
+    /// The sprite sheet has the size of the screen
+    pub fn sprites_per_row(&self) -> usize {
+        self.width / 8
     }
 
     pub fn mode_width(&mut self) -> usize {
@@ -339,13 +725,13 @@ impl Screen {
     }
 
     pub fn _reset_transparency(&mut self) {
-        self.transparency_map = [false; 256];
+        self.transparency_map = [false; PALETTE_SIZE];
         self.transparency_map[0] = true;
     }
 
     pub fn _reset_colors(&mut self) {
-        for i in 0..0xFFF {
-            self.color_map[i] = i as u32;
+        for i in 0..PALETTE_SIZE {
+            self.color_map[i] = i as Color;
         }
     }
 
@@ -361,16 +747,67 @@ impl Screen {
     pub fn save(&mut self) {
         info!("[GFX] SAVE SCREEN");
         self.saved_frame_buffer.copy_from_slice(&self.frame_buffer);
+        self.saved_fill_pattern = self.fill_pattern;
+        self.fill_pattern = FillPattern::new();
     }
 
     pub fn restore(&mut self) {
         info!("[GFX] Restore SCREEN");
         self.frame_buffer.copy_from_slice(&self.saved_frame_buffer);
+        self.fill_pattern = self.saved_fill_pattern;
+    }
+
+    pub fn save_state(&self) -> ScreenState {
+        ScreenState {
+            frame_buffer: self.frame_buffer.clone(),
+            sprites: self.sprites.clone(),
+            dyn_sprites: self.dyn_sprites.clone(),
+            map: self.map.clone(),
+            transparency_map: self.transparency_map,
+            color: self.color,
+            color_map: self.color_map,
+            camera: self.camera,
+            cliprect: self.cliprect,
+            fill_pattern: self.fill_pattern,
+            font: self.font.clone(),
+            ttf_font: self.ttf_font.clone(),
+            ttf_size: self.ttf_size,
+            ttf_mode: self.ttf_mode,
+        }
     }
 
+    pub fn restore_state(&mut self, state: &ScreenState) {
+        info!("[GFX] Restore SCREEN state");
+
+        if state.frame_buffer.len() == self.frame_buffer.len() {
+            self.frame_buffer.copy_from_slice(&state.frame_buffer);
+        }
+        self.sprites = state.sprites.clone();
+        self.dyn_sprites = state.dyn_sprites.clone();
+        self.map = state.map.clone();
+        self.transparency_map = state.transparency_map;
+        self.color = state.color;
+        self.color_map = state.color_map;
+        self.camera = state.camera;
+        self.cliprect = state.cliprect;
+        self.fill_pattern = state.fill_pattern;
+        self.font = state.font.clone();
+        self.ttf_font = state.ttf_font.clone();
+        self.ttf_size = state.ttf_size;
+        self.ttf_mode = state.ttf_mode;
+    }
+
+    /// Color to draw with, the current one for -1 and None for the colors
+    /// outside of the palette, which draw nothing
     #[inline]
-    pub fn _find_color(&mut self, col: i32) -> u32 {
-        if col == -1 { self.color } else { col as u32 }
+    pub fn _find_color(&mut self, col: i32) -> Option<Color> {
+        if col == -1 {
+            Some(self.color)
+        } else if col >= 0 {
+            to_color(col as u32)
+        } else {
+            None
+        }
     }
 
     pub fn camera(&mut self, x: i32, y: i32) {
@@ -382,8 +819,12 @@ impl Screen {
         self.sprites = sprites;
     }
 
-    pub fn set_map(&mut self, map: Vec<u32>) {
+    pub fn set_map(&mut self, map: Vec<Vec<u32>>) {
         self.map = map;
+        self.map.resize(MAP_LAYERS, Vec::new());
+        for layer in self.map.iter_mut() {
+            layer.resize(unicorn::MAP_WIDTH * unicorn::MAP_HEIGHT, 0);
+        }
     }
 
     pub fn set_sprites_flags(&mut self, flags: Vec<u8>) {
@@ -407,7 +848,7 @@ impl Screen {
     }
 
     #[inline]
-    pub fn putpixel_direct(&mut self, x: i32, y: i32, col: u32) {
+    pub fn putpixel_direct(&mut self, x: i32, y: i32, col: Color) {
         if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
             return;
         }
@@ -417,7 +858,7 @@ impl Screen {
     }
 
     #[inline]
-    pub fn putpixel_(&mut self, x: i32, y: i32, col: u32) {
+    pub fn putpixel_(&mut self, x: i32, y: i32, col: Color) {
         // Make camera adjustment
         let x = x - self.camera.x;
         let y = y - self.camera.y;
@@ -427,36 +868,95 @@ impl Screen {
             return;
         }
 
-        let draw_col = self.color_map[col as usize];
-
         let offset = self.pixel_offset(x, y);
-        self.frame_buffer[offset] = draw_col as u32;
+        self.frame_buffer[offset] = self.color_map[col as usize];
     }
 
     #[inline]
     pub fn color(&mut self, col: i32) {
-        if (col >= 0) && (col <= 255) {
-            self.color = col as u32;
+        if (col >= 0) && (col < PALETTE_SIZE as i32) {
+            self.color = col as Color;
         }
     }
 
+    /// Select a builtin font or one added with `add_font`, pico-8 is used
+    /// for the unknown names
     pub fn font(&mut self, name: &str) {
         self.font = match name {
-            "pico-8" => &fonts::pico8::FONT,
-            "bbc" => &fonts::bbc::FONT,
-            "cbmII" => &fonts::cbmii::FONT,
-            "appleII" => &fonts::appleii::FONT,
-            "trollmini" => &fonts::trollmini::FONT,
-            _ => &fonts::pico8::FONT,
+            "pico-8" => ScreenFont::Fixed(&fonts::pico8::FONT),
+            "bbc" => ScreenFont::Fixed(&fonts::bbc::FONT),
+            "cbmII" => ScreenFont::Fixed(&fonts::cbmii::FONT),
+            "appleII" => ScreenFont::Fixed(&fonts::appleii::FONT),
+            "trollmini" => ScreenFont::Fixed(&fonts::trollmini::FONT),
+            _ => {
+                match self.fonts.get(name) {
+                    Some(font) => ScreenFont::Bitmap(font.clone()),
+                    None => ScreenFont::Fixed(&fonts::pico8::FONT),
+                }
+            }
         }
     }
 
     pub fn get_font(&mut self) -> String {
-        self.font.name.to_string().clone()
+        self.font.name()
+    }
+
+    /// Make a bitmap font available to `font`, under its own name
+    pub fn add_font(&mut self, font: BitmapFont) {
+        info!("[GFX] Add font {:?}, {:?} glyphs", font.name, font.glyphs.len());
+        self.fonts.insert(font.name.clone(), Arc::new(font));
+    }
+
+    /// Remove the bitmap and TTF fonts, the current one falls back to pico-8
+    pub fn clear_fonts(&mut self) {
+        self.fonts.clear();
+        if let ScreenFont::Bitmap(_) = self.font {
+            self.font = ScreenFont::Fixed(&fonts::pico8::FONT);
+        }
+
+        self.ttf_fonts.clear();
+        self.ttf_font = None;
+    }
+
+    /// Make a TTF font available to `font_ttf`, under its own name
+    pub fn add_ttf(&mut self, font: TtfFont) {
+        info!("[GFX] Add TTF font {:?}", font.name);
+        self.ttf_fonts.insert(font.name.clone(), Arc::new(font));
+    }
+
+    /// Select the font of print_ttf, one of the TTF fonts of the cartridge
+    /// (the files are not read, a cartridge can't open any path). Returns
+    /// false if there is no font with this name.
+    pub fn font_ttf(&mut self, name: &str, size: f32, mode: TtfMode) -> bool {
+        if !self.ttf_fonts.contains_key(name) {
+            warn!("[GFX] Unknown TTF font {:?}", name);
+            return false;
+        }
+
+        self.ttf_font = self.ttf_fonts.get(name).cloned();
+        self.ttf_size = size.max(1.0);
+        self.ttf_mode = mode;
+
+        true
+    }
+
+    /// Print with the font selected by font_ttf, or the current font if
+    /// there is none
+    pub fn print_ttf(&mut self, string: String, x: i32, y: i32, col: i32) {
+        let font = match self.ttf_font.clone() {
+            Some(font) => font,
+            None => return self._print(string, x, y, col, false),
+        };
+
+        for (dx, dy, coverage) in font.rasterize(&string, self.ttf_size) {
+            if fonts::ttf::is_set(self.ttf_mode, x + dx, y + dy, coverage) {
+                self.pset(x + dx, y + dy, col);
+            }
+        }
     }
 
     #[inline]
-    pub fn putpixel(&mut self, x: i32, y: i32, col: u32) {
+    pub fn putpixel(&mut self, x: i32, y: i32, col: Color) {
         self.putpixel_(x, y, col);
     }
 
@@ -477,22 +977,36 @@ impl Screen {
     }
 
     pub fn pset(&mut self, x: i32, y: i32, col: i32) {
-        let color = self._find_color(col);
+        let color = match self._find_color(col) {
+            Some(color) => color,
+            None => return,
+        };
         self.putpixel_(x, y, color);
     }
 
     pub fn sget(&mut self, x: u32, y: u32) -> u32 {
-        let idx_sprite = (x / 8) + 50 * (y / 8);
+        self._sget(x, y) as u32
+    }
+
+    pub fn _sget(&self, x: u32, y: u32) -> Color {
+        let idx_sprite = (x / 8) + self.sprites_per_row() as u32 * (y / 8);
         let sprite = &self.sprites[idx_sprite as usize];
-        sprite.data[((x % 8) + (y % 8) * 8) as usize] as u32
+        sprite.data[((x % 8) + (y % 8) * 8) as usize]
     }
 
+    /// The colors outside of the palette are ignored
     pub fn sset(&mut self, x: u32, y: u32, col: i32) {
-        let col = self._find_color(col);
+        let col = match self._find_color(col) {
+            Some(col) => col,
+            None => {
+                warn!("[GFX] sset with a color outside of the palette {:?}", col);
+                return;
+            }
+        };
 
-        let idx_sprite = (x / 8) + 50 * (y / 8);
+        let idx_sprite = (x / 8) + self.sprites_per_row() as u32 * (y / 8);
         let sprite = &mut self.sprites[idx_sprite as usize];
-        sprite.set_data(((x % 8) + (y % 8) * 8) as usize, col as u32);
+        sprite.set_data(((x % 8) + (y % 8) * 8) as usize, col);
     }
 
     pub fn fget(&mut self, idx: u32, v: u8) -> bool {
@@ -550,41 +1064,178 @@ impl Screen {
 
     #[inline]
     pub fn _print(&mut self, string: String, x: i32, y: i32, col: i32, force: bool) {
+        match self.font.clone() {
+            ScreenFont::Fixed(font) => self._print_fixed(font, &string, x, y, col, force),
+            ScreenFont::Bitmap(font) => self._print_bitmap(&font, &string, x, y, col, force),
+        }
+    }
+
+    #[inline]
+    fn _print_pixel(&mut self, x: i32, y: i32, col: i32, force: bool) {
+        if force {
+            if let Some(col) = self._find_color(col) {
+                self.putpixel_direct(x, y, col);
+            }
+        } else {
+            self.pset(x, y, col);
+        }
+    }
+
+    fn _print_fixed(&mut self, font: &Font, string: &str, x: i32, y: i32, col: i32, force: bool) {
         let mut x = x;
-        let y = y + self.font.top_bearing;
+        let y = y + font.top_bearing;
 
-        for c in string.as_bytes() {
-            let glyph_index = if (*c < 32) || (*c > 126) { 0 } else { *c - 32 } as u32;
+        for c in string.chars() {
+            let c = c as u32;
+            let glyph_index = if (c < 32) || (c > 126) { 0 } else { c - 32 };
 
-            let glyph_start = (glyph_index * (self.font.glyph_height as u32)) as usize;
-            let glyph_end = glyph_start + (self.font.glyph_height as usize);
+            let glyph_start = (glyph_index * (font.glyph_height as u32)) as usize;
+            let glyph_end = glyph_start + (font.glyph_height as usize);
 
-            let glyph_data = &self.font.glyph_data[glyph_start..glyph_end];
+            let glyph_data = &font.glyph_data[glyph_start..glyph_end];
 
             for (i, glyph_row) in glyph_data.iter().enumerate() {
-                let mut dx = self.font.left_bearing;
+                let mut dx = font.left_bearing;
                 let mut row = *glyph_row;
                 while row != 0 {
                     if row & 0x80 != 0 {
-                        if force {
-                            self.putpixel_direct(x + dx, y + (i as i32), col as u32);
-                        } else {
-                            self.pset(x + dx, y + (i as i32), col);
-                        }
+                        self._print_pixel(x + dx, y + (i as i32), col, force);
                     }
                     row <<= 1;
                     dx += 1;
                 }
             }
 
-            x += self.font.advance_width;
+            x += font.advance_width;
+        }
+    }
+
+    /// The top of the line is `ascent` pixels above the baseline
+    fn _print_bitmap(&mut self, font: &BitmapFont, string: &str, x: i32, y: i32, col: i32, force: bool) {
+        let mut x = x;
+        let baseline = y + font.ascent;
+
+        for c in string.chars() {
+            let glyph = match font.glyph(c) {
+                Some(glyph) => glyph,
+                None => {
+                    x += font.advance(c);
+                    continue;
+                }
+            };
+
+            let top = baseline - glyph.y_offset - glyph.height;
+            for (i, pixel) in glyph.pixels.iter().enumerate() {
+                if *pixel {
+                    let dx = glyph.x_offset + (i as i32) % glyph.width;
+                    let dy = (i as i32) / glyph.width;
+                    self._print_pixel(x + dx, top + dy, col, force);
+                }
+            }
+
+            x += glyph.advance;
+        }
+    }
+
+    fn _line_width(&self, line: &str) -> i32 {
+        line.chars().map(|c| self.font.advance(c)).sum()
+    }
+
+    /// Width in pixels of the longest line of the text, with the current font
+    pub fn text_width(&self, string: &str) -> i32 {
+        string
+            .split('\n')
+            .map(|line| self._line_width(line))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Height in pixels of the lines of the text, with the current font
+    pub fn text_height(&self, string: &str) -> i32 {
+        string.split('\n').count() as i32 * self.font.line_height()
+    }
+
+    /// Lines of the text cut at the spaces to fit in `width` pixels, the
+    /// words longer than a line are cut anywhere
+    pub fn wrap_text(&self, string: &str, width: i32) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in string.split('\n') {
+            let mut line = String::new();
+
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", line, word)
+                };
+
+                if self._line_width(&candidate) <= width {
+                    line = candidate;
+                    continue;
+                }
+
+                if !line.is_empty() {
+                    lines.push(line);
+                    line = String::new();
+                }
+
+                for c in word.chars() {
+                    if !line.is_empty() && self._line_width(&line) + self.font.advance(c) > width {
+                        lines.push(line);
+                        line = String::new();
+                    }
+                    line.push(c);
+                }
+            }
+
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Print the text wrapped in the box, the lines that don't fit in the
+    /// height are skipped (no limit if `h` <= 0). Returns the number of lines
+    /// drawn.
+    pub fn print_box(&mut self,
+                     string: String,
+                     x: i32,
+                     y: i32,
+                     w: i32,
+                     h: i32,
+                     align: TextAlign,
+                     col: i32)
+                     -> i32 {
+        let line_height = self.font.line_height();
+        let mut drawn = 0;
+
+        for line in self.wrap_text(&string, w) {
+            let dy = drawn * line_height;
+            if h > 0 && dy + line_height > h {
+                break;
+            }
+
+            let dx = match align {
+                TextAlign::Left => 0,
+                TextAlign::Center => (w - self._line_width(&line)) / 2,
+                TextAlign::Right => w - self._line_width(&line),
+            };
+
+            self._print(line, x + dx, y + dy, col, false);
+            drawn += 1;
         }
+
+        drawn
     }
 
     pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: i32) {
         // debug!("LINE {:?} {:?} {:?} {:?} {:?}", x0, y0, x1, y1, col);
 
-        let color = self._find_color(col);
+        let color = match self._find_color(col) {
+            Some(color) => color,
+            None => return,
+        };
 
         let (mut x0, mut y0) = (x0, y0);
         let (x1, y1) = (x1, y1);
@@ -614,12 +1265,36 @@ impl Screen {
         }
     }
 
+    /// Pixel of a filled shape, drawn with the fill pattern
+    #[inline]
+    fn _fill_pixel(&mut self, x: i32, y: i32, col: Color) {
+        if self.fill_pattern.is_set(x - self.camera.x, y - self.camera.y) {
+            if let Some(secondary) = self.fill_pattern.color {
+                self.putpixel_(x, y, secondary);
+            }
+        } else {
+            self.putpixel_(x, y, col);
+        }
+    }
+
     pub fn hline(&mut self, x1: i32, x2: i32, y: i32, col: i32) {
+        let color = match self._find_color(col) {
+            Some(color) => color,
+            None => return,
+        };
+        self._hline(x1, x2, y, color, false);
+    }
+
+    fn _hline(&mut self, x1: i32, x2: i32, y: i32, color: Color, pattern: bool) {
         let x_min = cmp::min(x1, x2);
         let x_max = cmp::max(x1, x2);
 
         for x in x_min..(x_max + 1) {
-            self.putpixel(x, y, col as u32);
+            if pattern {
+                self._fill_pixel(x, y, color);
+            } else {
+                self.putpixel(x, y, color);
+            }
         }
     }
 
@@ -629,13 +1304,18 @@ impl Screen {
         let y_min = cmp::min(y0, y1);
         let y_max = cmp::max(y0, y1);
 
+        let color = match self._find_color(col) {
+            Some(color) => color,
+            None => return,
+        };
+
         for x in x_min..(x_max + 1) {
-            self.putpixel(x, y_min, col as u32);
-            self.putpixel(x, y_max, col as u32);
+            self.putpixel(x, y_min, color);
+            self.putpixel(x, y_max, color);
         }
         for y in (y_min + 1)..y_max {
-            self.putpixel(x0, y, col as u32);
-            self.putpixel(x1, y, col as u32);
+            self.putpixel(x0, y, color);
+            self.putpixel(x1, y, color);
         }
     }
 
@@ -645,10 +1325,13 @@ impl Screen {
         let y_min = cmp::min(y0, y1);
         let y_max = cmp::max(y0, y1);
 
+        let color = match self._find_color(col) {
+            Some(color) => color,
+            None => return,
+        };
+
         for y in y_min..(y_max + 1) {
-            for x in x_min..(x_max + 1) {
-                self.putpixel(x, y, col as u32);
-            }
+            self._hline(x_min, x_max, y, color, true);
         }
     }
 
@@ -668,6 +1351,23 @@ impl Screen {
         self.ellipsefill(x, y, r, r, col);
     }
 
+    /// Pattern of the filled shapes, a 4x4 (size 4) or 8x8 (size 8) one.
+    /// The set bits are drawn with col, or not at all if col is -1. A 0
+    /// pattern draws the shapes with their color only.
+    pub fn fillp(&mut self, pattern: u64, size: u32, col: i32) {
+        let size = if size == 8 { 8 } else { 4 };
+
+        self.fill_pattern = FillPattern {
+            bits: if size == 4 { pattern & 0xFFFF } else { pattern },
+            size: size,
+            color: if col >= 0 && col < PALETTE_SIZE as i32 {
+                Some(col as Color)
+            } else {
+                None
+            },
+        };
+    }
+
     pub fn clip(&mut self, x: i32, y: i32, w: i32, h: i32) {
         self._reset_cliprect();
 
@@ -725,7 +1425,10 @@ impl Screen {
         let mut ymk: i32;
         let mut ypk: i32;
 
-        let col = self._find_color(col);
+        let col = match self._find_color(col) {
+            Some(col) => col,
+            None => return,
+        };
 
         if rx > ry {
             ix = 0;
@@ -859,6 +1562,11 @@ impl Screen {
         let mut xmk: i32;
         let mut xpk: i32;
 
+        let col = match self._find_color(col) {
+            Some(col) => col,
+            None => return,
+        };
+
         if rx > ry {
             ix = 0;
             iy = rx * 64;
@@ -876,10 +1584,10 @@ impl Screen {
                     xph = x + h;
                     xmh = x - h;
                     if k > 0 {
-                        self.hline(xmh, xph, y + k, col);
-                        self.hline(xmh, xph, y - k, col);
+                        self._hline(xmh, xph, y + k, col, true);
+                        self._hline(xmh, xph, y - k, col, true);
                     } else {
-                        self.hline(xmh, xph, y, col);
+                        self._hline(xmh, xph, y, col, true);
                     }
                     ok = k;
                 }
@@ -887,10 +1595,10 @@ impl Screen {
                     xmi = x - i;
                     xpi = x + i;
                     if j > 0 {
-                        self.hline(xmi, xpi, y + j, col);
-                        self.hline(xmi, xpi, y - j, col);
+                        self._hline(xmi, xpi, y + j, col, true);
+                        self._hline(xmi, xpi, y - j, col, true);
                     } else {
-                        self.hline(xmi, xpi, y, col);
+                        self._hline(xmi, xpi, y, col, true);
                     }
                     oj = j;
                 }
@@ -915,10 +1623,10 @@ impl Screen {
                     xmj = x - j;
                     xpj = x + j;
                     if i > 0 {
-                        self.hline(xmj, xpj, y + i, col);
-                        self.hline(xmj, xpj, y - i, col);
+                        self._hline(xmj, xpj, y + i, col, true);
+                        self._hline(xmj, xpj, y - i, col, true);
                     } else {
-                        self.hline(xmj, xpj, y, col);
+                        self._hline(xmj, xpj, y, col, true);
                     }
                     oi = i;
                 }
@@ -926,10 +1634,10 @@ impl Screen {
                     xmk = x - k;
                     xpk = x + k;
                     if h > 0 {
-                        self.hline(xmk, xpk, y + h, col);
-                        self.hline(xmk, xpk, y - h, col);
+                        self._hline(xmk, xpk, y + h, col, true);
+                        self._hline(xmk, xpk, y - h, col, true);
                     } else {
-                        self.hline(xmk, xpk, y, col);
+                        self._hline(xmk, xpk, y, col, true);
                     }
                     oh = h;
                 }
@@ -975,6 +1683,8 @@ impl Screen {
         self.line(vx[idx], vy[idx], vx[0], vy[0], col);
     }
 
+    /// Register a sprite of any size, -1 if it can't be registered (8x8
+    /// sprite, or a color outside of the palette)
     pub fn spr_reg(&mut self, n: i64, data: Vec<u32>, width: u32, height: u32) -> i64 {
         let mut dynamic_sprite = false;
 
@@ -983,6 +1693,15 @@ impl Screen {
         }
 
         if dynamic_sprite {
+            let data: Option<Vec<Color>> = data.into_iter().map(to_color).collect();
+            let data = match data {
+                Some(data) => data,
+                None => {
+                    warn!("[GFX] Sprite with a color outside of the palette");
+                    return -1;
+                }
+            };
+
             let dyn_sprite = DynamicSprite::new(data, width, height);
             if n == -1 {
                 self.dyn_sprites.push(dyn_sprite);
@@ -1123,8 +1842,9 @@ impl Screen {
 
                     let mut index = 0;
                     for (_, c) in sprite.data.iter_mut().enumerate() {
-                        if !self.is_transparent(*c as u32) {
-                            self.putpixel_(new_x, new_y, *c as u32);
+                        let c = *c;
+                        if !self.is_transparent(c) {
+                            self.putpixel_(new_x, new_y, c);
                         }
 
                         index += 1;
@@ -1152,7 +1872,8 @@ impl Screen {
                    sy: i32,
                    cel_w: u32,
                    cel_h: u32,
-                   layer: u8) {
+                   layer: u8,
+                   map_layer: u32) {
         let mut idx_x;
         let mut idx_y: i32 = 0;
 
@@ -1183,16 +1904,18 @@ impl Screen {
                 let mut new_x = orig_x;
                 let mut new_y = sy + 8 * idx_y;
 
-                let map_x = cel_x as i32 + idx_x;
-                let map_y = cel_y as i32 + idx_y;
+                // The map wraps around, the negative cels too
+                let width = unicorn::MAP_WIDTH as i32;
+                let height = unicorn::MAP_HEIGHT as i32;
+                let map_x = (((cel_x as i32 + idx_x) % width) + width) % width;
+                let map_y = (((cel_y as i32 + idx_y) % height) + height) % height;
 
                 //debug!("MAP X {:?} MAP Y {:?}", map_x, map_y);
 
-                let idx_sprite: u32 = *self.map.get(((map_x as usize) % unicorn::MAP_WIDTH) * unicorn::MAP_WIDTH + (map_y as usize) % unicorn::MAP_HEIGHT).unwrap_or(&0);
-                //self.map[(map_x as usize) % unicorn::MAP_WIDTH][(map_y as usize) % unicorn::MAP_HEIGHT];
+                let idx_sprite = self.mget(map_x, map_y, map_layer);
 
                 // Skip the sprite 0
-                if idx_sprite != 0 {
+                if idx_sprite != 0 && (idx_sprite as usize) < self.sprites.len() {
                     let mut sprite = self.sprites[idx_sprite as usize].clone();
                     //debug!("GET SPRITE {:?}, {:?} {:?}", idx_sprite, map_x, map_y);
 
@@ -1201,8 +1924,9 @@ impl Screen {
                         let mut index = 0;
 
                         for (_, c) in sprite.data.iter_mut().enumerate() {
-                            if !self.is_transparent(*c as u32) {
-                                self.putpixel_(new_x, new_y, *c as u32);
+                            let c = *c;
+                            if !self.is_transparent(c) {
+                                self.putpixel_(new_x, new_y, c);
                             }
 
                             index += 1;
@@ -1224,33 +1948,44 @@ impl Screen {
         }
     }
 
-    pub fn mget(&mut self, x: i32, y: i32) -> u32 {
+    pub fn mget(&mut self, x: i32, y: i32, map_layer: u32) -> u32 {
         //debug!("MGET x {:?} y {:?}", x, y);
 
         if x < 0 || y < 0 {
             return 0;
         }
 
-        if x as usize > unicorn::MAP_WIDTH || y as usize >= unicorn::MAP_HEIGHT {
+        if x as usize >= unicorn::MAP_WIDTH || y as usize >= unicorn::MAP_HEIGHT {
             return 0;
         }
 
-        0
-        //self.map[x as usize][y as usize]
+        match self.map.get(map_layer as usize) {
+            Some(map) => *map.get(map_index(x as usize, y as usize)).unwrap_or(&0),
+            None => 0,
+        }
     }
 
-    pub fn mset(&mut self, x: i32, y: i32, v: u32) {
+    pub fn mset(&mut self, x: i32, y: i32, v: u32, map_layer: u32) {
         //info!("MSET x {:?} y {:?} v {:?}", x, y, v);
 
         if x < 0 || y < 0 {
             return;
         }
 
-        if x as usize > unicorn::MAP_WIDTH || y as usize >= unicorn::MAP_HEIGHT {
+        if x as usize >= unicorn::MAP_WIDTH || y as usize >= unicorn::MAP_HEIGHT {
             return;
         }
 
-  //      self.map[x as usize][y as usize] = v;
+        if v > MAP_MAX_VALUE {
+            warn!("[GFX] mset with a value bigger than {:?}: {:?}", MAP_MAX_VALUE, v);
+            return;
+        }
+
+        if let Some(map) = self.map.get_mut(map_layer as usize) {
+            if let Some(cell) = map.get_mut(map_index(x as usize, y as usize)) {
+                *cell = v;
+            }
+        }
     }
 
     pub fn sspr(&mut self,
@@ -1280,7 +2015,7 @@ impl Screen {
 
         for y in sy..sy + sh {
             for x in sx..sx + sw {
-                v.push(self.sget(x, y));
+                v.push(self._sget(x, y));
             }
         }
 
@@ -1345,8 +2080,8 @@ impl Screen {
             for i in 0..w2 {
                 let d = ret[idx];
                 if d != 0 {
-                    if !self.is_transparent(d as u32) {
-                        self.putpixel_(i as i32 + dx, j as i32 + dy, d as u32);
+                    if !self.is_transparent(d) {
+                        self.putpixel_(i as i32 + dx, j as i32 + dy, d);
                     }
                 }
                 idx += 1;
@@ -1354,7 +2089,7 @@ impl Screen {
         }
     }
 
-    pub fn _sprite_rotazoom(&mut self, v: Vec<u32>, 
+    pub fn _sprite_rotazoom(&mut self, v: Vec<Color>, 
                             sw: u32,
                             sh: u32,
                             destx: i32,
@@ -1445,8 +2180,8 @@ impl Screen {
                 if (dx >= 0) && (dy >= 0) && (dx < sw as i32) && (dy < sh as i32) {
                     let d = v[(dy * sw as i32 + dx) as usize];
                     if d != 0 {
-                        if !self.is_transparent(d as u32) {
-                            self.putpixel_(x as i32 + destx, y as i32 + desty, d as u32);
+                        if !self.is_transparent(d) {
+                            self.putpixel_(x as i32 + destx, y as i32 + desty, d);
                         }
                     }
                 }
@@ -1484,7 +2219,7 @@ impl Screen {
 
         for y in sy..sy + sh {
             for x in sx..sx + sw {
-                v.push(self.sget(x, y));
+                v.push(self._sget(x, y));
             }
         }
 
@@ -1492,32 +2227,32 @@ impl Screen {
     }
 
     #[inline]
-    pub fn is_transparent(&self, value: u32) -> bool {
-        if value <= 255 {
-            self.transparency_map[value as usize]
-        } else {
-            false
-        }
+    pub fn is_transparent(&self, value: Color) -> bool {
+        self.transparency_map[value as usize]
     }
 
+    /// Draw the color c0 with c1, the colors outside of the palette reset
+    /// the mapping
     pub fn pal(&mut self, c0: i32, c1: i32) {
-        if c0 < 0 || c1 < 0 {
+        let size = PALETTE_SIZE as i32;
+
+        if c0 < 0 || c1 < 0 || c0 >= size || c1 >= size {
             self._reset_colors();
         } else {
-            self.color_map[c0 as usize] = c1 as u32;
+            self.color_map[c0 as usize] = c1 as Color;
         }
     }
 
     pub fn palt(&mut self, c: i32, t: bool) {
         if c == -1 {
             self._reset_transparency();
-        } else if (c >= 0) && (c <= 255) {
+        } else if (c >= 0) && (c < PALETTE_SIZE as i32) {
             self.transparency_map[c as usize] = t;
         }
     }
 
     pub fn peek(&mut self, addr: u32) -> u8 {
-        self.frame_buffer[addr as usize] as u8
+        self.frame_buffer[addr as usize]
     }
 
     pub fn poke(&mut self, _addr: u32, _val: u16) {}
@@ -1536,9 +2271,7 @@ impl Screen {
         let a = &self.frame_buffer[source_addr as usize..(source_addr + len * 2) as usize].to_vec();
 
         while idx < len * 2 {
-            let value = a[idx as usize] as u32;
-
-            self.frame_buffer[(dest_addr + idx) as usize] = value as u32;
+            self.frame_buffer[(dest_addr + idx) as usize] = a[idx as usize];
 
             idx += 1;
         }
@@ -1,6 +1,7 @@
 #[cfg(feature = "duktape")]
 pub mod plugin {
     use std::sync::{Arc, Mutex};
+    use std::borrow::Cow;
 
     use duktape::*;
     use duktape::types::*;
@@ -10,9 +11,12 @@ pub mod plugin {
 
     use unicorn::info::Info;
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
     use sound::sound::Sound;
 
-    use gfx::Screen;
+    use gfx::{Screen, TextAlign, TtfMode};
 
     /*
         # GFX                   #  Javascript   #    New name   #
@@ -25,13 +29,19 @@ pub mod plugin {
         ellipse                 #               #               #
         ellipsefill             #               #               #
         fget                    #               #               #
+        fillp                   #      X        #               #
         font                    #               #               #
+        font_ttf                #      X        #               #
         line                    #      X        #               #
         pal                     #      X        #               #
         palt                    #               #               #
         pget                    #               #               #
         polygon                 #               #               #
         print                   #      X        #               #
+        print_ttf               #      X        #               #
+        print_box               #      X        #               #
+        text_width              #      X        #               #
+        text_height             #      X        #               #
         pset                    #      X        #               #
         rect                    #               #               #
         rectfill                #               #               #
@@ -51,8 +61,9 @@ pub mod plugin {
         music_stop              #               #               #
         music_position          #               #               #
         # Input                 #               #               #
-        btn                     #               #               #
+        btn                     #      X        #               #
         btnp                    #      X        #               #
+        axis                    #      X        #               #
         mouse_x                 #               #               #
         mouse_y                 #               #               #
         mouse_state             #               #               #
@@ -74,15 +85,21 @@ pub mod plugin {
         cos                     #               #               #
         sin                     #               #               #
         flr                     #               #               #
-        rnd                     #               #               #
-        srand                   #               #               #
+        rnd                     #      X        #               #
+        srand                   #      X        #               #
         mid                     #               #               #
         bxor                    #               #               #
         # Memory                #               #               #
         memcpy                  #               #               #
+        # Cart Data             #               #               #
+        cartdata                #      X        #               #
+        dget                    #      X        #               #
+        dset                    #      X        #               #
         # System                #               #               #
         time                    #      X        # unicorn_time  #
         time_sec                #               #               #
+        meta                    #      X        #               #
+        menuitem                #      X        #               #
         show_mouse              #               #               #
     */
 
@@ -91,6 +108,9 @@ pub mod plugin {
         screen: Vec<Arc<Mutex<Screen>>>,
         players: Vec<Arc<Mutex<Players>>>,
         sound: Vec<Arc<Mutex<Sound>>>,
+        cartdata: Vec<Arc<Mutex<CartData>>>,
+        random: Vec<Arc<Mutex<Random>>>,
+        menuitems: Vec<Arc<Mutex<MenuItems>>>,
     }
 
     impl JavascriptPluginRust {
@@ -100,6 +120,9 @@ pub mod plugin {
                 screen: Vec::new(),
                 players: Vec::new(),
                 sound: Vec::new(),
+                cartdata: Vec::new(),
+                random: Vec::new(),
+                menuitems: Vec::new(),
             }
         }
 
@@ -120,6 +143,98 @@ pub mod plugin {
             self.sound.push(sound);
         }
 
+        pub fn set_cartdata(&mut self, cartdata: Arc<Mutex<CartData>>) {
+            self.cartdata.push(cartdata);
+        }
+
+        pub fn set_random(&mut self, random: Arc<Mutex<Random>>) {
+            self.random.push(random);
+        }
+
+        pub fn set_menuitems(&mut self, menuitems: Arc<Mutex<MenuItems>>) {
+            self.menuitems.push(menuitems);
+        }
+
+        pub fn rnd(&self,
+                   _ctx: &mut Context,
+                   args: &[Value<'static>])
+                   -> DuktapeResult<Value<'static>> {
+            let mut x: f64 = 1.;
+
+            if let Value::Number(arg) = args[0] {
+                x = arg;
+            }
+
+            let value = self.random[0].lock().unwrap().rnd(x);
+            Ok(Value::Number(value))
+        }
+
+        pub fn srand(&self,
+                     _ctx: &mut Context,
+                     args: &[Value<'static>])
+                     -> DuktapeResult<Value<'static>> {
+            let mut seed: u32 = 0;
+
+            if let Value::Number(arg) = args[0] {
+                seed = arg as u32;
+            }
+
+            self.random[0].lock().unwrap().set_seed(seed);
+
+            Ok(Value::Number(0.))
+        }
+
+        pub fn cartdata(&self,
+                        _ctx: &mut Context,
+                        args: &[Value<'static>])
+                        -> DuktapeResult<Value<'static>> {
+            let mut name: String = "".to_string();
+
+            if let Value::String(ref arg) = args[0] {
+                name = arg.to_string();
+            }
+
+            if let Value::Number(arg) = args[0] {
+                name = format!("{}", arg);
+            }
+
+            let value = self.cartdata[0].lock().unwrap().open(&name);
+            Ok(Value::Bool(value))
+        }
+
+        pub fn dget(&self,
+                    _ctx: &mut Context,
+                    args: &[Value<'static>])
+                    -> DuktapeResult<Value<'static>> {
+            let mut idx: i64 = 0;
+
+            if let Value::Number(arg) = args[0] {
+                idx = arg as i64;
+            }
+
+            let value = self.cartdata[0].lock().unwrap().get(idx);
+            Ok(Value::Number(value))
+        }
+
+        pub fn dset(&self,
+                    _ctx: &mut Context,
+                    args: &[Value<'static>])
+                    -> DuktapeResult<Value<'static>> {
+            let mut idx: i64 = 0;
+            let mut value: f64 = 0.;
+
+            if let Value::Number(arg) = args[0] {
+                idx = arg as i64;
+            }
+
+            if let Value::Number(arg) = args[1] {
+                value = arg;
+            }
+
+            self.cartdata[0].lock().unwrap().set(idx, value);
+            Ok(Value::Number(0.))
+        }
+
         pub fn cls(&self,
                    _ctx: &mut Context,
                    args: &[Value<'static>])
@@ -214,6 +329,46 @@ pub mod plugin {
             Ok(Value::Number(0.))
         }
 
+        pub fn btn(&self,
+                   _ctx: &mut Context,
+                   args: &[Value<'static>])
+                   -> DuktapeResult<Value<'static>> {
+            let mut p: u8 = 0;
+            let mut x: u8 = 0;
+
+            if let Value::Number(arg) = args[0] {
+                x = arg as u8;
+            }
+
+            if let Value::Number(arg) = args[1] {
+                p = arg as u8;
+            }
+
+            let value = self.players[0].lock().unwrap().btn(p, x);
+
+            Ok(Value::Bool(value))
+        }
+
+        pub fn axis(&self,
+                    _ctx: &mut Context,
+                    args: &[Value<'static>])
+                    -> DuktapeResult<Value<'static>> {
+            let mut p: u8 = 0;
+            let mut x: usize = 0;
+
+            if let Value::Number(arg) = args[0] {
+                x = arg as usize;
+            }
+
+            if let Value::Number(arg) = args[1] {
+                p = arg as u8;
+            }
+
+            let value = self.players[0].lock().unwrap().axis(p, x);
+
+            Ok(Value::Number(value))
+        }
+
         pub fn btnp(&self,
                     _ctx: &mut Context,
                     args: &[Value<'static>])
@@ -244,6 +399,176 @@ pub mod plugin {
             Ok(Value::Number(self.info[0].lock().unwrap().time() as f64))
         }
 
+        pub fn meta(&self,
+                    _ctx: &mut Context,
+                    args: &[Value<'static>])
+                    -> DuktapeResult<Value<'static>> {
+            let mut key: String = "".to_string();
+
+            if let Value::String(ref arg) = args[0] {
+                key = arg.to_string();
+            }
+
+            match self.info[0].lock().unwrap().meta(&key) {
+                Some(value) => Ok(Value::String(Cow::Owned(value))),
+                None => Ok(Value::Null),
+            }
+        }
+
+        pub fn menuitem(&self,
+                        _ctx: &mut Context,
+                        args: &[Value<'static>])
+                        -> DuktapeResult<Value<'static>> {
+            let mut index: u32 = 0;
+            let mut label: String = "".to_string();
+
+            if let Value::Number(arg) = args[0] {
+                index = arg as u32;
+            }
+
+            if let Value::String(ref arg) = args[1] {
+                label = arg.to_string();
+            }
+
+            let value = self.menuitems[0].lock().unwrap().set(index, &label);
+            Ok(Value::Bool(value))
+        }
+
+        pub fn font_ttf(&self,
+                        _ctx: &mut Context,
+                        args: &[Value<'static>])
+                        -> DuktapeResult<Value<'static>> {
+            let mut name: String = "".to_string();
+            let mut size: f32 = 8.0;
+            let mut mode: String = "".to_string();
+
+            if let Value::String(ref arg) = args[0] {
+                name = arg.to_string();
+            }
+
+            if let Value::Number(arg) = args[1] {
+                size = arg as f32;
+            }
+
+            if let Value::String(ref arg) = args[2] {
+                mode = arg.to_string();
+            }
+
+            let value = self.screen[0]
+                .lock()
+                .unwrap()
+                .font_ttf(&name, size, TtfMode::from_name(&mode));
+            Ok(Value::Bool(value))
+        }
+
+        pub fn print_ttf(&self,
+                         _ctx: &mut Context,
+                         args: &[Value<'static>])
+                         -> DuktapeResult<Value<'static>> {
+            let mut text: String = "".to_string();
+            let mut x: i32 = 0;
+            let mut y: i32 = 0;
+            let mut color: i32 = -1;
+
+            match args[0] {
+                Value::String(ref n) => text = n.to_string(),
+                Value::Number(n) => text = n.to_string(),
+                _ => (),
+            }
+
+            if let Value::Number(n) = args[1] {
+                x = n as i32;
+            }
+
+            if let Value::Number(n) = args[2] {
+                y = n as i32;
+            }
+
+            if let Value::Number(n) = args[3] {
+                color = n as i32;
+            }
+
+            self.screen[0].lock().unwrap().print_ttf(text, x, y, color);
+
+            Ok(Value::Number(0.))
+        }
+
+        pub fn print_box(&self,
+                         _ctx: &mut Context,
+                         args: &[Value<'static>])
+                         -> DuktapeResult<Value<'static>> {
+            let mut text: String = "".to_string();
+            let mut values = [0, 0, 0, 0];
+            let mut align: String = "".to_string();
+            let mut color: i32 = -1;
+
+            match args[0] {
+                Value::String(ref n) => text = n.to_string(),
+                Value::Number(n) => text = n.to_string(),
+                _ => (),
+            }
+
+            // x, y, w, h
+            for (idx, value) in values.iter_mut().enumerate() {
+                if let Value::Number(n) = args[idx + 1] {
+                    *value = n as i32;
+                }
+            }
+
+            if let Value::String(ref n) = args[5] {
+                align = n.to_string();
+            }
+
+            if let Value::Number(n) = args[6] {
+                color = n as i32;
+            }
+
+            let value = self.screen[0]
+                .lock()
+                .unwrap()
+                .print_box(text,
+                           values[0],
+                           values[1],
+                           values[2],
+                           values[3],
+                           TextAlign::from_name(&align),
+                           color);
+
+            Ok(Value::Number(value as f64))
+        }
+
+        pub fn text_width(&self,
+                          _ctx: &mut Context,
+                          args: &[Value<'static>])
+                          -> DuktapeResult<Value<'static>> {
+            let mut text: String = "".to_string();
+
+            match args[0] {
+                Value::String(ref n) => text = n.to_string(),
+                Value::Number(n) => text = n.to_string(),
+                _ => (),
+            }
+
+            let value = self.screen[0].lock().unwrap().text_width(&text);
+            Ok(Value::Number(value as f64))
+        }
+
+        pub fn text_height(&self,
+                           _ctx: &mut Context,
+                           args: &[Value<'static>])
+                           -> DuktapeResult<Value<'static>> {
+            let mut text: String = "".to_string();
+
+            match args[0] {
+                Value::String(ref n) => text = n.to_string(),
+                Value::Number(n) => text = n.to_string(),
+                _ => (),
+            }
+
+            let value = self.screen[0].lock().unwrap().text_height(&text);
+            Ok(Value::Number(value as f64))
+        }
+
         pub fn print(&self,
                      _ctx: &mut Context,
                      args: &[Value<'static>])
@@ -537,6 +862,31 @@ pub mod plugin {
             Ok(Value::Number(0.))
         }
 
+        pub fn fillp(&self,
+                     _ctx: &mut Context,
+                     args: &[Value<'static>])
+                     -> DuktapeResult<Value<'static>> {
+            let mut pattern: u64 = 0;
+            let mut col: i32 = -1;
+            let mut size: u32 = 4;
+
+            if let Value::Number(arg) = args[0] {
+                pattern = arg as u64;
+            }
+
+            if let Value::Number(arg) = args[1] {
+                col = arg as i32;
+            }
+
+            if let Value::Number(arg) = args[2] {
+                size = arg as u32;
+            }
+
+            self.screen[0].lock().unwrap().fillp(pattern, size, col);
+
+            Ok(Value::Number(0.))
+        }
+
         pub fn circ(&self,
                     _ctx: &mut Context,
                     args: &[Value<'static>])
@@ -661,6 +1011,21 @@ pub mod plugin {
                     0x12 => return self.circfill(_ctx, args),
                     0x13 => return self.line(_ctx, args),
                     0x14 => return self.sspr_rotazoom(_ctx, args),
+                    0x15 => return self.cartdata(_ctx, args),
+                    0x16 => return self.dget(_ctx, args),
+                    0x17 => return self.dset(_ctx, args),
+                    0x18 => return self.btn(_ctx, args),
+                    0x19 => return self.axis(_ctx, args),
+                    0x1a => return self.rnd(_ctx, args),
+                    0x1b => return self.srand(_ctx, args),
+                    0x1c => return self.meta(_ctx, args),
+                    0x1d => return self.menuitem(_ctx, args),
+                    0x1e => return self.font_ttf(_ctx, args),
+                    0x1f => return self.print_ttf(_ctx, args),
+                    0x20 => return self.print_box(_ctx, args),
+                    0x21 => return self.text_width(_ctx, args),
+                    0x22 => return self.text_height(_ctx, args),
+                    0x23 => return self.fillp(_ctx, args),
 
                     _ => (),
                 }
@@ -690,12 +1055,18 @@ pub mod plugin {
                     info: Arc<Mutex<Info>>,
                     screen: Arc<Mutex<Screen>>,
                     noise: Arc<Mutex<Noise>>,
-                    sound: Arc<Mutex<Sound>>) {
+                    random: Arc<Mutex<Random>>,
+                    sound: Arc<Mutex<Sound>>,
+                    cartdata: Arc<Mutex<CartData>>,
+                    menuitems: Arc<Mutex<MenuItems>>) {
             info!("[PLUGIN][JAVASCRIPT] Init plugin");
             self.javascript.lock().unwrap().set_info(info.clone());
             self.javascript.lock().unwrap().set_screen(screen.clone());
             self.javascript.lock().unwrap().set_players(players.clone());
             self.javascript.lock().unwrap().set_sound(sound.clone());
+            self.javascript.lock().unwrap().set_cartdata(cartdata.clone());
+            self.javascript.lock().unwrap().set_random(random.clone());
+            self.javascript.lock().unwrap().set_menuitems(menuitems.clone());
 
             self.ctx.register(0x1, "pset", self.javascript.clone(), Some(3));
             self.ctx.register(0x2, "cls", self.javascript.clone(), Some(1));
@@ -711,7 +1082,34 @@ pub mod plugin {
             self.ctx.register(0x12, "circfill", self.javascript.clone(), Some(4));
             self.ctx.register(0x13, "line", self.javascript.clone(), Some(5));
             self.ctx.register(0x14, "sspr_rotazoom", self.javascript.clone(), Some(11));
-
+            self.ctx.register(0x15, "cartdata", self.javascript.clone(), Some(1));
+            self.ctx.register(0x16, "dget", self.javascript.clone(), Some(1));
+            self.ctx.register(0x17, "dset", self.javascript.clone(), Some(2));
+            self.ctx.register(0x18, "btn", self.javascript.clone(), Some(2));
+            self.ctx.register(0x19, "axis", self.javascript.clone(), Some(2));
+            self.ctx.register(0x1a, "rnd", self.javascript.clone(), Some(1));
+            self.ctx.register(0x1b, "srand", self.javascript.clone(), Some(1));
+            self.ctx.register(0x1c, "meta", self.javascript.clone(), Some(1));
+            self.ctx.register(0x1d, "__unicorn_menuitem", self.javascript.clone(), Some(2));
+            self.ctx.register(0x1e, "font_ttf", self.javascript.clone(), Some(3));
+            self.ctx.register(0x1f, "print_ttf", self.javascript.clone(), Some(4));
+            self.ctx.register(0x20, "print_box", self.javascript.clone(), Some(7));
+            self.ctx.register(0x21, "text_width", self.javascript.clone(), Some(1));
+            self.ctx.register(0x22, "text_height", self.javascript.clone(), Some(1));
+            self.ctx.register(0x23, "fillp", self.javascript.clone(), Some(3));
+
+            // The callbacks stay in Javascript, the pause menu only knows the index
+            let menuitem = r#"
+            var __unicorn_menuitems = {};
+            function menuitem(index, label, callback) {
+                index = Math.floor(index);
+                __unicorn_menuitems[index] = callback;
+                return __unicorn_menuitem(index, label === undefined ? "" : String(label));
+            }
+            "#;
+            if let Err(err) = self.ctx.eval(menuitem) {
+                warn!("Error to load the menuitem function {:?}", err);
+            }
         }
 
         pub fn init(&mut self) {
@@ -750,18 +1148,34 @@ pub mod plugin {
             true
         }
 
-        pub fn load_code(&mut self, data: String) -> bool {
+        /// Call the callback of the entry `index` added with menuitem()
+        pub fn menuitem(&mut self, index: u32) {
+            if !self.loaded_code {
+                return;
+            }
+
+            let code = format!("if (__unicorn_menuitems[{0}]) {{ void __unicorn_menuitems[{0}](); }}",
+                               index);
+            match self.ctx.eval(&code) {
+                Result::Ok(_) => (),
+                Result::Err(err) => warn!("Error during the menuitem callback {:?}", err),
+            }
+        }
+
+        pub fn load_code(&mut self, data: String) -> Result<(), String> {
             info!("[PLUGIN][JAVASCRIPT] LOAD CODE");
 
             match self.ctx.eval(&data) {
-                Result::Ok(_) => self.loaded_code = true,
+                Result::Ok(_) => {
+                    self.loaded_code = true;
+                    Ok(())
+                }
                 Result::Err(err) => {
                     self.loaded_code = false;
                     warn!("Error to load the code {:?}", err);
+                    Err(format!("{:?}", err))
                 }
             }
-
-            self.loaded_code
         }
     }
 }
@@ -773,7 +1187,10 @@ pub mod plugin {
     use config::Players;
 
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
     use unicorn::info::Info;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
     use sound::sound::Sound;
 
     use gfx::Screen;
@@ -792,11 +1209,14 @@ pub mod plugin {
                     _info: Arc<Mutex<Info>>,
                     _screen: Arc<Mutex<Screen>>,
                     _noise: Arc<Mutex<Noise>>,
-                    _sound: Arc<Mutex<Sound>>) {
+                    _random: Arc<Mutex<Random>>,
+                    _sound: Arc<Mutex<Sound>>,
+                    _cartdata: Arc<Mutex<CartData>>,
+                    _menuitems: Arc<Mutex<MenuItems>>) {
             error!("Javascript plugin disabled");
         }
-        pub fn load_code(&mut self, _data: String) -> bool {
-            false
+        pub fn load_code(&mut self, _data: String) -> Result<(), String> {
+            Err("Javascript is not supported".to_string())
         }
         pub fn init(&mut self) {}
         pub fn draw(&mut self) -> bool {
@@ -805,5 +1225,6 @@ pub mod plugin {
         pub fn update(&mut self) -> bool {
             false
         }
+        pub fn menuitem(&mut self, _index: u32) {}
     }
 }
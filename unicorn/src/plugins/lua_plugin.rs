@@ -2,9 +2,6 @@
 pub mod plugin {
     use std::sync::{Arc, Mutex};
 
-    use rand;
-    use rand::Rng;
-
     use unicorn_plugin_lua as lua;
     use unicorn_plugin_lua::ffi::lua_State;
     use unicorn_plugin_lua::{State, Function, ThreadStatus};
@@ -14,9 +11,12 @@ pub mod plugin {
 
     use unicorn::info::Info;
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
     use sound::sound::Sound;
 
-    use gfx::Screen;
+    use gfx::{Screen, TextAlign, TtfMode};
 
     /*
         # GFX                   #    Lua        #    New name   #
@@ -29,13 +29,19 @@ pub mod plugin {
         ellipse                 #     X         #               #
         ellipsefill             #     X         #               #
         fget                    #     X         #               #
+        fillp                   #     X         #               #
         font                    #     X         #               #
+        font_ttf                #     X         #               #
         line                    #     X         #               #
         pal                     #     X         #               #
         palt                    #     X         #               #
         pget                    #     X         #               #
         polygon                 #               #               #
         print                   #     X         #               #
+        print_ttf               #     X         #               #
+        print_box               #     X         #               #
+        text_width              #     X         #               #
+        text_height             #     X         #               #
         pset                    #     X         #               #
         rect                    #     X         #               #
         rectfill                #     X         #               #
@@ -57,6 +63,7 @@ pub mod plugin {
         # Input                 #               #               #
         btn                     #     X         #               #
         btnp                    #               #               #
+        axis                    #     X         #               #
         mouse_x                 #               #               #
         mouse_y                 #               #               #
         mouse_state             #               #               #
@@ -79,24 +86,143 @@ pub mod plugin {
         sin                     #               #               #
         flr                     #               #               #
         rnd                     #     X         #               #
-        srand                   #               #               #
+        srand                   #     X         #               #
         mid                     #               #               #
         bxor                    #               #               #
         # Memory                #               #               #
         memcpy                  #               #               #
+        # Cart Data             #               #               #
+        cartdata                #     X         #               #
+        dget                    #     X         #               #
+        dset                    #     X         #               #
         # System                #               #               #
         time                    #     X         #               #
         time_sec                #               #               #
+        meta                    #     X         #               #
+        menuitem                #     X         #               #
         show_mouse              #               #               #
     */
 
+    /// Lua functions saving and restoring the globals of the cartridge
+    static STATE_FUNCTIONS: &'static str = r#"
+    __unicorn_builtins = {}
+    for k, _ in pairs(_G) do
+      __unicorn_builtins[k] = true
+    end
+
+    -- Also the protected metatables
+    local __unicorn_getmetatable = debug and debug.getmetatable or getmetatable
+    function __unicorn_setmetatable(t, mt)
+      if debug and debug.setmetatable then
+        debug.setmetatable(t, mt)
+      else
+        setmetatable(t, mt)
+      end
+    end
+
+    -- A table is saved once under its id, in `tables`
+    function __unicorn_serialize(v, ids, tables)
+      local t = type(v)
+      if t == "number" then
+        if v ~= v then
+          return "0/0"
+        elseif v == math.huge then
+          return "math.huge"
+        elseif v == -math.huge then
+          return "-math.huge"
+        elseif math.type(v) == "integer" then
+          return string.format("%d", v)
+        end
+        return string.format("%.17g", v)
+      elseif t == "string" then
+        return string.format("%q", v)
+      elseif t == "boolean" then
+        return tostring(v)
+      elseif t == "table" then
+        local id = ids[v]
+        if id == nil then
+          id = #tables + 1
+          ids[v] = id
+          tables[id] = v
+        end
+        return "t[" .. id .. "]"
+      end
+      return nil
+    end
+
+    -- The saved state is a chunk creating all the tables first, then
+    -- filling them, so the shared tables and the cycles are kept
+    function __unicorn_save_state()
+      local ids = {}
+      local tables = {}
+
+      local globals = {}
+      for k, v in pairs(_G) do
+        if type(k) == "string" and not __unicorn_builtins[k] then
+          local s = __unicorn_serialize(v, ids, tables)
+          if s ~= nil then
+            globals[#globals + 1] = string.format("[%q]=%s", k, s)
+          end
+        end
+      end
+
+      -- The tables found while filling are appended to `tables`
+      local fields = {}
+      local metatables = {}
+      local id = 1
+      while id <= #tables do
+        local v = tables[id]
+        for k, e in next, v do
+          local sk = __unicorn_serialize(k, ids, tables)
+          local se = __unicorn_serialize(e, ids, tables)
+          if sk ~= nil and se ~= nil then
+            fields[#fields + 1] = string.format("rawset(t[%d],%s,%s)", id, sk, se)
+          end
+        end
+
+        local mt = __unicorn_getmetatable(v)
+        if type(mt) == "table" then
+          metatables[#metatables + 1] = string.format("__unicorn_setmetatable(t[%d],%s)",
+                                                      id,
+                                                      __unicorn_serialize(mt, ids, tables))
+        end
+        id = id + 1
+      end
+
+      return "local t={" .. string.rep("{},", #tables) .. "}\n" ..
+             table.concat(fields, "\n") .. "\n" ..
+             table.concat(metatables, "\n") .. "\n" ..
+             "return {" .. table.concat(globals, ",") .. "}"
+    end
+
+    function __unicorn_restore_state(data)
+      local chunk = load(data)
+      if chunk == nil then
+        return
+      end
+
+      local state = chunk()
+      for k, v in pairs(_G) do
+        if not __unicorn_builtins[k] and type(v) ~= "function" and state[k] == nil then
+          _G[k] = nil
+        end
+      end
+      for k, v in pairs(state) do
+        _G[k] = v
+      end
+    end
+    "#;
+
     pub struct ExtraData {
         /* External objects */
         pub players: Arc<Mutex<Players>>,
         pub screen: Arc<Mutex<Screen>>,
         pub info: Arc<Mutex<Info>>,
         pub noise: Arc<Mutex<Noise>>,
+        pub random: Arc<Mutex<Random>>,
         pub sound: Arc<Mutex<Sound>>,
+        pub cartdata: Arc<Mutex<CartData>>,
+        pub menuitems: Arc<Mutex<MenuItems>>,
     }
 
     pub struct LuaPlugin {
@@ -118,7 +244,10 @@ pub mod plugin {
                     info: Arc<Mutex<Info>>,
                     screen: Arc<Mutex<Screen>>,
                     noise: Arc<Mutex<Noise>>,
-                    sound: Arc<Mutex<Sound>>) {
+                    random: Arc<Mutex<Random>>,
+                    sound: Arc<Mutex<Sound>>,
+                    cartdata: Arc<Mutex<CartData>>,
+                    menuitems: Arc<Mutex<MenuItems>>) {
             info!("[PLUGIN][LUA] Init plugin");
 
             let extra = ExtraData {
@@ -126,7 +255,10 @@ pub mod plugin {
                 info: info.clone(),
                 screen: screen.clone(),
                 noise: noise.clone(),
+                random: random.clone(),
                 sound: sound.clone(),
+                cartdata: cartdata.clone(),
+                menuitems: menuitems.clone(),
             };
 
             let mut lua_state = self.lua_state.lock().unwrap();
@@ -189,6 +321,19 @@ pub mod plugin {
               "#);
             info!("[PLUGIN][LUA][Unicorn][BTNP] = {:?}", value);
 
+            let value = lua_state.do_string(r#"axis = function(x, p)
+
+              x = math.floor(x)
+
+              if p == nil then
+                p = 0
+              end
+
+              return UnicornObject:axis(p, x)
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][AXIS] = {:?}", value);
+
 
             let value = lua_state.do_string(r#"rect = function(x0, y0, x1, y1, color)
 
@@ -390,12 +535,21 @@ pub mod plugin {
                 x = 1
               end
 
-              x = math.floor(x)
               return UnicornObject:rnd(x)
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][RND] = {:?}", value);
 
+            let value = lua_state.do_string(r#"srand = function(seed)
+              if seed == nil then
+                seed = 0
+              end
+
+              UnicornObject:srand(math.floor(seed))
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][SRAND] = {:?}", value);
+
             let value = lua_state.do_string(r#"add = function(t, v)
               t[#t+1] = v
               end
@@ -445,6 +599,24 @@ pub mod plugin {
               "#);
             info!("[PLUGIN][LUA][Unicorn][PAL] = {:?}", value);
 
+            let value = lua_state.do_string(r#"fillp = function(pattern, color, size)
+              if pattern == nil then
+                pattern = 0
+              end
+
+              if color == nil then
+                color = -1
+              end
+
+              if size == nil then
+                size = 4
+              end
+
+              UnicornObject:fillp(math.floor(pattern), math.floor(color), math.floor(size))
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][FILLP] = {:?}", value);
+
             let value = lua_state.do_string(r#"font = function(name)
 
               if name == nil then
@@ -518,7 +690,7 @@ pub mod plugin {
               "#);
             info!("[PLUGIN][LUA][Unicorn][NOISE_SET_SEED] = {:?}", value);
 
-            let value = lua_state.do_string(r#"mapdraw = function(cel_x, cel_y, sx, sy, cel_w, cel_h, layer)
+            let value = lua_state.do_string(r#"mapdraw = function(cel_x, cel_y, sx, sy, cel_w, cel_h, layer, map_layer)
 
               cel_x = math.floor(cel_x)
               cel_y = math.floor(cel_y)
@@ -531,26 +703,38 @@ pub mod plugin {
                 layer = 0
               end
 
-              UnicornObject:mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer)
+              if map_layer == nil then
+                map_layer = 0
+              end
+
+              UnicornObject:mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer, map_layer)
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][MAP] = {:?}", value);
 
-            let value = lua_state.do_string(r#"mget = function(x, y)
+            let value = lua_state.do_string(r#"mget = function(x, y, map_layer)
               x = math.floor(x)
               y = math.floor(y)
 
-              return UnicornObject:mget(x, y)
+              if map_layer == nil then
+                map_layer = 0
+              end
+
+              return UnicornObject:mget(x, y, map_layer)
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][MGET] = {:?}", value);
 
-            let value = lua_state.do_string(r#"mset = function(x, y, v)
+            let value = lua_state.do_string(r#"mset = function(x, y, v, map_layer)
               x = math.floor(x)
               y = math.floor(y)
               v = math.floor(v)
 
-              UnicornObject:mset(x, y, v)
+              if map_layer == nil then
+                map_layer = 0
+              end
+
+              UnicornObject:mset(x, y, v, map_layer)
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][MSET] = {:?}", value);
@@ -663,12 +847,106 @@ pub mod plugin {
               "#);
             info!("[PLUGIN][LUA][Unicorn][PRINT] = {:?}", value);
 
+            let value = lua_state.do_string(r#"font_ttf = function(name, size, mode)
+              if size == nil then
+                size = 8
+              end
+
+              if mode == nil then
+                mode = "threshold"
+              end
+
+              return UnicornObject:font_ttf(name, size, mode)
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][FONT_TTF] = {:?}", value);
+
+            let value = lua_state.do_string(r#"print_ttf = function(str, x, y, col)
+              if x == nil then
+                x = 0
+              end
+
+              if y == nil then
+                y = 0
+              end
+
+              if col == nil then
+                col = -1
+              end
+
+              x = math.floor(x)
+              y = math.floor(y)
+              col = math.floor(col)
+
+              UnicornObject:print_ttf(tostring(str), x, y, col)
+
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][PRINT_TTF] = {:?}", value);
+
+            let value = lua_state.do_string(r#"print_box = function(str, x, y, w, h, align, col)
+              if h == nil then
+                h = 0
+              end
+
+              if align == nil then
+                align = "left"
+              end
+
+              if col == nil then
+                col = -1
+              end
+
+              x = math.floor(x)
+              y = math.floor(y)
+              w = math.floor(w)
+              h = math.floor(h)
+              col = math.floor(col)
+
+              return UnicornObject:print_box(tostring(str), x, y, w, h, align, col)
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][PRINT_BOX] = {:?}", value);
+
+            let value = lua_state.do_string(r#"text_width = function(str)
+              return UnicornObject:text_width(tostring(str))
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][TEXT_WIDTH] = {:?}", value);
+
+            let value = lua_state.do_string(r#"text_height = function(str)
+              return UnicornObject:text_height(tostring(str))
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][TEXT_HEIGHT] = {:?}", value);
+
             let value = lua_state.do_string(r#"time = function()
                 return UnicornObject:time()
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][TIME] = {:?}", value);
 
+            let value = lua_state.do_string(r#"meta = function(key)
+                return UnicornObject:meta(key)
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][META] = {:?}", value);
+
+            /* The callbacks stay in Lua, the pause menu only knows the index */
+            let value = lua_state.do_string(r#"__unicorn_menuitems = {}
+              menuitem = function(index, label, callback)
+                index = math.floor(index)
+
+                if label == nil then
+                  label = ""
+                end
+
+                __unicorn_menuitems[index] = callback
+                return UnicornObject:menuitem(index, tostring(label))
+              end
+              "#);
+            info!("[PLUGIN][LUA][Unicorn][MENUITEM] = {:?}", value);
+
             let value = lua_state.do_string(r#"sfx = function(id, filename, note, panning, rate, loops, channel)
               if filename == nil then
                 filename = ""
@@ -790,8 +1068,8 @@ pub mod plugin {
 
             /* CARTDATA */
             let value = lua_state.do_string(r#"cartdata = function(x)
-              x = math.floor(x)
-              UnicornObject:cartdata(x)
+              x = tostring(x)
+              return UnicornObject:cartdata(x)
               end
               "#);
             info!("[PLUGIN][LUA][Unicorn][CARTDATA] = {:?}", value);
@@ -805,7 +1083,10 @@ pub mod plugin {
 
             let value = lua_state.do_string(r#"dset = function(x, y)
               x = math.floor(x)
-              y = math.floor(y)
+
+              if y == nil then
+                y = 0
+              end
 
               UnicornObject:dset(x, y)
               end
@@ -951,6 +1232,55 @@ pub mod plugin {
             end
             "#);
             info!("[PLUGIN][LUA] LOADED MATH FUNCTIONS = {:?}", value);
+
+            /* STATE: serialize the cartridge globals (numbers, strings, booleans and tables) */
+            let value = lua_state.do_string(STATE_FUNCTIONS);
+            info!("[PLUGIN][LUA] LOADED STATE FUNCTIONS = {:?}", value);
+        }
+
+        /// Serialize the global variables created by the cartridge, with
+        /// the metatables of their tables. Functions and userdata are not
+        /// saved, the shared tables and the cycles are kept.
+        pub fn save_state(&mut self) -> Option<String> {
+            if !self.loaded_code {
+                return None;
+            }
+
+            let mut lua_state = self.lua_state.lock().unwrap();
+
+            let value = lua_state.do_string("__unicorn_state = __unicorn_save_state()");
+            if value != ThreadStatus::Ok {
+                error!("[PLUGIN][LUA] SAVE STATE = {:?}", value);
+                return None;
+            }
+
+            lua_state.get_global("__unicorn_state");
+            let data = lua_state.to_str(-1).map(|s| s.to_string());
+            lua_state.pop(1);
+
+            lua_state.push_nil();
+            lua_state.set_global("__unicorn_state");
+
+            data
+        }
+
+        pub fn restore_state(&mut self, data: String) -> bool {
+            if !self.loaded_code {
+                return false;
+            }
+
+            let mut lua_state = self.lua_state.lock().unwrap();
+
+            lua_state.push_string(&data);
+            lua_state.set_global("__unicorn_state");
+
+            let value = lua_state.do_string("__unicorn_restore_state(__unicorn_state)\n__unicorn_state = nil");
+            if value != ThreadStatus::Ok {
+                error!("[PLUGIN][LUA] RESTORE STATE = {:?}", value);
+                return false;
+            }
+
+            true
         }
 
         pub fn init(&mut self) {
@@ -1002,19 +1332,104 @@ pub mod plugin {
 
         }
 
-        pub fn load_code(&mut self, data: String) -> bool {
-            info!("[PLUGIN][LUA] LOAD CODE");
+        /// Call the callback of the entry `index` added with menuitem()
+        pub fn menuitem(&mut self, index: u32) {
+            if !self.loaded_code {
+                return;
+            }
+
             let mut lua_state = self.lua_state.lock().unwrap();
 
-            self.loaded_code = true;
+            let value = lua_state.do_string(&format!("local callback = __unicorn_menuitems[{}]
+                if callback ~= nil then
+                  callback()
+                end",
+                                                     index));
+            if value != ThreadStatus::Ok {
+                error!("[PLUGIN][LUA] MENUITEM = {:?}", value);
+            }
+        }
+
+        /// The error is the message of Lua, with the line of the code
+        pub fn load_code(&mut self, data: String) -> Result<(), String> {
+            info!("[PLUGIN][LUA] LOAD CODE");
+            let mut lua_state = self.lua_state.lock().unwrap();
 
             let value = lua_state.do_string(&data);
-            if value != ThreadStatus::Ok {
-                error!("[PLUGIN][LUA] LOAD CODE = {:?}", value);
-                self.loaded_code = false;
+            self.loaded_code = value == ThreadStatus::Ok;
+
+            if !self.loaded_code {
+                let message = match lua_state.to_str(-1) {
+                    Some(message) => message.to_string(),
+                    None => format!("{:?}", value),
+                };
+                lua_state.pop(1);
+
+                error!("[PLUGIN][LUA] LOAD CODE = {:?} {}", value, message);
+                return Err(message);
             }
 
-            self.loaded_code
+            Ok(())
+        }
+
+        /// Replace the functions that don't behave like PICO-8 ones, for the
+        /// imported .p8 cartridges. The PICO-8 sprite sheet is 16 sprites wide
+        /// instead of 50, so the sprite numbers are translated.
+        pub fn load_pico8_compat(&mut self) {
+            let mut lua_state = self.lua_state.lock().unwrap();
+
+            let value = lua_state.do_string(r#"
+            function __p8_sprite(n)
+              n = math.floor(n)
+              return (n // 16) * 50 + n % 16
+            end
+
+            function __p8_from_sprite(n)
+              if n % 50 >= 16 then
+                return n
+              end
+              return (n // 50) * 16 + n % 50
+            end
+
+            spr = function(n, x, y, w, h, flip_x, flip_y)
+              n = math.floor(n)
+              w = w or 1
+              h = h or 1
+
+              sspr((n % 16) * 8, (n // 16) * 8, w * 8, h * 8, x, y, w * 8, h * 8, flip_x, flip_y)
+            end
+
+            local __unicorn_fget = fget
+            fget = function(n, flag)
+              if flag == nil then
+                return UnicornObject:fget_all(__p8_sprite(n))
+              end
+              return __unicorn_fget(__p8_sprite(n), flag)
+            end
+
+            local __unicorn_fset = fset
+            fset = function(n, flag, value)
+              __unicorn_fset(__p8_sprite(n), flag, value)
+            end
+
+            local __unicorn_mget = mget
+            mget = function(x, y)
+              return __p8_from_sprite(__unicorn_mget(x, y))
+            end
+
+            local __unicorn_mset = mset
+            mset = function(x, y, v)
+              __unicorn_mset(x, y, __p8_sprite(v))
+            end
+
+            map = function(cel_x, cel_y, sx, sy, cel_w, cel_h, layer)
+              mapdraw(cel_x or 0, cel_y or 0, sx or 0, sy or 0, cel_w or 128, cel_h or 32, layer)
+            end
+
+            tostr = tostring
+            tonum = tonumber
+            "#);
+            info!("[PLUGIN][LUA] LOADED PICO-8 FUNCTIONS = {:?}", value);
         }
     }
 
@@ -1196,6 +1611,29 @@ pub mod plugin {
             1
         }
 
+        unsafe extern "C" fn lua_axis(lua_context: *mut lua_State) -> c_int {
+            let mut state = State::from_ptr(lua_context);
+            let mut state2 = State::from_ptr(lua_context);
+
+            let players = state2.with_extra(|extra| {
+                                                let data = extra
+                                                    .as_ref()
+                                                    .unwrap()
+                                                    .downcast_ref::<ExtraData>()
+                                                    .unwrap();
+                                                data.players.clone()
+                                            });
+
+            let player = state.check_integer(2);
+            let i = state.check_integer(3);
+
+            let value = players.lock().unwrap().axis(player as u8, i as usize);
+
+            state.push_number(value);
+
+            1
+        }
+
         unsafe extern "C" fn lua_cls(lua_context: *mut lua_State) -> c_int {
             let mut state = State::from_ptr(lua_context);
 
@@ -1532,6 +1970,32 @@ pub mod plugin {
             1
         }
 
+        unsafe extern "C" fn lua_fillp(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA FILLP");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let pattern = state.check_integer(2);
+            let col = state.check_integer(3);
+            let size = state.check_integer(4);
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            screen
+                .lock()
+                .unwrap()
+                .fillp(pattern as u64, size as u32, col as i32);
+
+            1
+        }
+
         unsafe extern "C" fn lua_pset(lua_context: *mut lua_State) -> c_int {
             debug!("LUA PSET");
 
@@ -1828,21 +2292,49 @@ pub mod plugin {
 
             let mut state = State::from_ptr(lua_context);
 
-            let x = state.check_integer(2);
+            let x = state.check_number(2);
 
-            let value: f64;
+            let random = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.random.clone()
+                                          });
 
-            if x == 0 {
-                value = rand::thread_rng().gen_range(0.0, 1.0);
+            let value = if x == 0.0 {
+                random.lock().unwrap().rnd(1.0)
             } else {
-                value = rand::thread_rng().gen_range(0.0, x as f64);
-            }
+                random.lock().unwrap().rnd(x as f64)
+            };
 
             state.push_number(value);
 
             1
         }
 
+        unsafe extern "C" fn lua_srand(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA SRAND");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let seed = state.check_integer(2);
+
+            let random = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.random.clone()
+                                          });
+
+            random.lock().unwrap().set_seed(seed as u32);
+
+            0
+        }
+
         // spr n x y [w h] [flip_x] [flip_y]
         unsafe extern "C" fn lua_spr(lua_context: *mut lua_State) -> c_int {
             let mut state = State::from_ptr(lua_context);
@@ -1925,7 +2417,7 @@ pub mod plugin {
             1
         }
 
-        // map cel_x cel_y sx sy cel_w cel_h [layer]
+        // map cel_x cel_y sx sy cel_w cel_h [layer] [map_layer]
         unsafe extern "C" fn lua_mapdraw(lua_context: *mut lua_State) -> c_int {
             debug!("LUA MAP");
 
@@ -1938,6 +2430,7 @@ pub mod plugin {
             let cel_w = state.check_integer(6);
             let cel_h = state.check_integer(7);
             let layer = state.check_integer(8);
+            let map_layer = state.check_integer(9);
 
             let screen = state.with_extra(|extra| {
                                               let data = extra
@@ -1957,7 +2450,8 @@ pub mod plugin {
                          sy as i32,
                          cel_w as u32,
                          cel_h as u32,
-                         layer as u8);
+                         layer as u8,
+                         map_layer as u32);
 
             1
         }
@@ -1969,6 +2463,7 @@ pub mod plugin {
 
             let x = state.check_integer(2);
             let y = state.check_integer(3);
+            let map_layer = state.check_integer(4);
 
             let screen = state.with_extra(|extra| {
                                               let data = extra
@@ -1979,7 +2474,7 @@ pub mod plugin {
                                               data.screen.clone()
                                           });
 
-            let value = screen.lock().unwrap().mget(x as i32, y as i32);
+            let value = screen.lock().unwrap().mget(x as i32, y as i32, map_layer as u32);
 
             state.push_integer(value as i64);
 
@@ -1995,6 +2490,7 @@ pub mod plugin {
             let x = state.check_integer(2);
             let y = state.check_integer(3);
             let v = state.check_integer(4);
+            let map_layer = state.check_integer(5);
 
             let screen = state.with_extra(|extra| {
                                               let data = extra
@@ -2005,7 +2501,7 @@ pub mod plugin {
                                               data.screen.clone()
                                           });
 
-            screen.lock().unwrap().mset(x as i32, y as i32, v as u32);
+            screen.lock().unwrap().mset(x as i32, y as i32, v as u32, map_layer as u32);
 
             1
         }
@@ -2038,6 +2534,142 @@ pub mod plugin {
             1
         }
 
+        unsafe extern "C" fn lua_font_ttf(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA FONT TTF");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let name = state.check_string(2).to_string();
+            let size = state.check_number(3);
+            let mode = state.check_string(4).to_string();
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            let value = screen
+                .lock()
+                .unwrap()
+                .font_ttf(&name, size as f32, TtfMode::from_name(&mode));
+            state.push_bool(value);
+
+            1
+        }
+
+        unsafe extern "C" fn lua_print_ttf(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA PRINT TTF");
+
+            let mut state = State::from_ptr(lua_context);
+            let mut state2 = State::from_ptr(lua_context);
+
+            let str_data = state2.check_string(2);
+            let x = state.check_integer(3);
+            let y = state.check_integer(4);
+            let col = state.check_integer(5);
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            screen
+                .lock()
+                .unwrap()
+                .print_ttf(str_data.to_string(), x as i32, y as i32, col as i32);
+
+            1
+        }
+
+        unsafe extern "C" fn lua_print_box(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA PRINT BOX");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let str_data = state.check_string(2).to_string();
+            let x = state.check_integer(3);
+            let y = state.check_integer(4);
+            let w = state.check_integer(5);
+            let h = state.check_integer(6);
+            let align = state.check_string(7).to_string();
+            let col = state.check_integer(8);
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            let value = screen
+                .lock()
+                .unwrap()
+                .print_box(str_data,
+                           x as i32,
+                           y as i32,
+                           w as i32,
+                           h as i32,
+                           TextAlign::from_name(&align),
+                           col as i32);
+            state.push_integer(value as i64);
+
+            1
+        }
+
+        unsafe extern "C" fn lua_text_width(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA TEXT WIDTH");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let str_data = state.check_string(2).to_string();
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            let value = screen.lock().unwrap().text_width(&str_data);
+            state.push_integer(value as i64);
+
+            1
+        }
+
+        unsafe extern "C" fn lua_text_height(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA TEXT HEIGHT");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let str_data = state.check_string(2).to_string();
+
+            let screen = state.with_extra(|extra| {
+                                              let data = extra
+                                                  .as_ref()
+                                                  .unwrap()
+                                                  .downcast_ref::<ExtraData>()
+                                                  .unwrap();
+                                              data.screen.clone()
+                                          });
+
+            let value = screen.lock().unwrap().text_height(&str_data);
+            state.push_integer(value as i64);
+
+            1
+        }
+
         unsafe extern "C" fn lua_time(lua_context: *mut lua_State) -> c_int {
             debug!("LUA TIME");
 
@@ -2057,6 +2689,54 @@ pub mod plugin {
             1
         }
 
+        unsafe extern "C" fn lua_meta(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA META");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let key = state.check_string(2).to_string();
+
+            let info = state.with_extra(|extra| {
+                                            let data = extra
+                                                .as_ref()
+                                                .unwrap()
+                                                .downcast_ref::<ExtraData>()
+                                                .unwrap();
+                                            data.info.clone()
+                                        });
+
+            let value = info.lock().unwrap().meta(&key);
+            match value {
+                Some(value) => state.push_string(&value),
+                None => state.push_nil(),
+            }
+
+            1
+        }
+
+        unsafe extern "C" fn lua_menuitem(lua_context: *mut lua_State) -> c_int {
+            debug!("LUA MENUITEM");
+
+            let mut state = State::from_ptr(lua_context);
+
+            let index = state.check_integer(2);
+            let label = state.check_string(3).to_string();
+
+            let menuitems = state.with_extra(|extra| {
+                                                 let data = extra
+                                                     .as_ref()
+                                                     .unwrap()
+                                                     .downcast_ref::<ExtraData>()
+                                                     .unwrap();
+                                                 data.menuitems.clone()
+                                             });
+
+            let value = index > 0 && menuitems.lock().unwrap().set(index as u32, &label);
+            state.push_bool(value);
+
+            1
+        }
+
         unsafe extern "C" fn lua_stat(lua_context: *mut lua_State) -> c_int {
             debug!("LUA STAT");
 
@@ -2090,42 +2770,75 @@ pub mod plugin {
 
 
         /***** CARTDATA *****/
-        unsafe extern "C" fn lua_cartdata(_lua_context: *mut lua_State) -> c_int {
+        unsafe extern "C" fn lua_cartdata(lua_context: *mut lua_State) -> c_int {
             debug!("LUA CARTDATA");
 
-            /*
             let mut state = State::from_ptr(lua_context);
-            let x = state.check_integer(2);
-            */
+
+            let name = state.check_string(2).to_string();
+
+            let cartdata = state.with_extra(|extra| {
+                                                let data = extra
+                                                    .as_ref()
+                                                    .unwrap()
+                                                    .downcast_ref::<ExtraData>()
+                                                    .unwrap();
+                                                data.cartdata.clone()
+                                            });
+
+            let value = cartdata.lock().unwrap().open(&name);
+            state.push_bool(value);
 
             1
         }
 
-        unsafe extern "C" fn lua_dget(_lua_context: *mut lua_State) -> c_int {
+        unsafe extern "C" fn lua_dget(lua_context: *mut lua_State) -> c_int {
             debug!("LUA DGET");
 
-            /*
             let mut state = State::from_ptr(lua_context);
-            let x = state.check_integer(2);
-            */
+
+            let idx = state.check_integer(2);
+
+            let cartdata = state.with_extra(|extra| {
+                                                let data = extra
+                                                    .as_ref()
+                                                    .unwrap()
+                                                    .downcast_ref::<ExtraData>()
+                                                    .unwrap();
+                                                data.cartdata.clone()
+                                            });
+
+            let value = cartdata.lock().unwrap().get(idx as i64);
+            state.push_number(value);
 
             1
         }
 
 
-        unsafe extern "C" fn lua_dset(_lua_context: *mut lua_State) -> c_int {
+        unsafe extern "C" fn lua_dset(lua_context: *mut lua_State) -> c_int {
             debug!("LUA DSET");
 
-            /*
             let mut state = State::from_ptr(lua_context);
-            let x = state.check_integer(2);
-            */
 
-            1
+            let idx = state.check_integer(2);
+            let value = state.check_number(3);
+
+            let cartdata = state.with_extra(|extra| {
+                                                let data = extra
+                                                    .as_ref()
+                                                    .unwrap()
+                                                    .downcast_ref::<ExtraData>()
+                                                    .unwrap();
+                                                data.cartdata.clone()
+                                            });
+
+            cartdata.lock().unwrap().set(idx as i64, value);
+
+            0
         }
     }
 
-    pub const UNICORN_LUA_LIB: [(&'static str, Function); 41] =
+    pub const UNICORN_LUA_LIB: [(&'static str, Function); 51] =
         [("new", Some(UnicornLua::lua_new)),
 
          ("music", Some(UnicornLua::lua_music)),
@@ -2136,6 +2849,7 @@ pub mod plugin {
 
          ("btn", Some(UnicornLua::lua_btn)),
          ("btnp", Some(UnicornLua::lua_btnp)),
+         ("axis", Some(UnicornLua::lua_axis)),
 
          ("cls", Some(UnicornLua::lua_cls)),
 
@@ -2165,6 +2879,7 @@ pub mod plugin {
 
          ("palt", Some(UnicornLua::lua_palt)),
          ("pal", Some(UnicornLua::lua_pal)),
+         ("fillp", Some(UnicornLua::lua_fillp)),
 
          ("pget", Some(UnicornLua::lua_pget)),
          ("pset", Some(UnicornLua::lua_pset)),
@@ -2176,16 +2891,60 @@ pub mod plugin {
          ("noise_set_seed", Some(UnicornLua::lua_noise_set_seed)),
 
          ("rnd", Some(UnicornLua::lua_rnd)),
+         ("srand", Some(UnicornLua::lua_srand)),
 
          ("print", Some(UnicornLua::lua_print)),
+         ("font_ttf", Some(UnicornLua::lua_font_ttf)),
+         ("print_ttf", Some(UnicornLua::lua_print_ttf)),
+         ("print_box", Some(UnicornLua::lua_print_box)),
+         ("text_width", Some(UnicornLua::lua_text_width)),
+         ("text_height", Some(UnicornLua::lua_text_height)),
 
          ("time", Some(UnicornLua::lua_time)),
+         ("meta", Some(UnicornLua::lua_meta)),
+         ("menuitem", Some(UnicornLua::lua_menuitem)),
 
          ("stat", Some(UnicornLua::lua_stat)),
 
          ("cartdata", Some(UnicornLua::lua_cartdata)),
          ("dget", Some(UnicornLua::lua_dget)),
          ("dset", Some(UnicornLua::lua_dset))];
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_state_shared_table() {
+            let mut state = lua::State::new();
+            state.open_libs();
+            assert_eq!(state.do_string(STATE_FUNCTIONS), ThreadStatus::Ok);
+
+            let script = r#"
+            shared = {x = 1}
+            a = {left = shared, right = shared}
+            cycle = {n = 2}
+            cycle.me = cycle
+            keys = {[shared] = "shared"}
+            point = setmetatable({x = 3}, {__index = {y = 4}})
+
+            local data = __unicorn_save_state()
+            shared = nil
+            a = nil
+            cycle = nil
+            keys = nil
+            point = nil
+            __unicorn_restore_state(data)
+
+            assert(shared.x == 1)
+            assert(a.left == a.right and a.left == shared)
+            assert(cycle.n == 2 and cycle.me == cycle)
+            assert(keys[shared] == "shared")
+            assert(point.x == 3 and point.y == 4)
+            "#;
+            assert_eq!(state.do_string(script), ThreadStatus::Ok);
+        }
+    }
 }
 
 #[cfg(not(feature = "unicorn_plugin_lua"))]
@@ -2195,7 +2954,10 @@ pub mod plugin {
     use config::Players;
 
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
     use unicorn::info::Info;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
     use sound::sound::Sound;
 
     use gfx::Screen;
@@ -2214,12 +2976,16 @@ pub mod plugin {
                     _info: Arc<Mutex<Info>>,
                     _screen: Arc<Mutex<Screen>>,
                     _noise: Arc<Mutex<Noise>>,
-                    _sound: Arc<Mutex<Sound>>) {
+                    _random: Arc<Mutex<Random>>,
+                    _sound: Arc<Mutex<Sound>>,
+                    _cartdata: Arc<Mutex<CartData>>,
+                    _menuitems: Arc<Mutex<MenuItems>>) {
             error!("LUA plugin disabled");
         }
-        pub fn load_code(&mut self, _data: String) -> bool {
-            false
+        pub fn load_code(&mut self, _data: String) -> Result<(), String> {
+            Err("Lua is not supported".to_string())
         }
+        pub fn load_pico8_compat(&mut self) {}
         pub fn init(&mut self) {}
         pub fn draw(&mut self) -> bool {
             false
@@ -2227,5 +2993,12 @@ pub mod plugin {
         pub fn update(&mut self) -> bool {
             false
         }
+        pub fn menuitem(&mut self, _index: u32) {}
+        pub fn save_state(&mut self) -> Option<String> {
+            None
+        }
+        pub fn restore_state(&mut self, _data: String) -> bool {
+            false
+        }
     }
 }
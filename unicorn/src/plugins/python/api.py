@@ -1,5 +1,4 @@
 import math
-import random
 from math import frexp, copysign
 from sys import float_info
 
@@ -46,6 +45,23 @@ globals()["music_position"] = music_position
 
 # Cart Data
 
+
+def cartdata(name):
+    return unicorn_cartdata.cartdata(str(name))
+
+
+def dget(idx):
+    return unicorn_cartdata.dget(math.floor(idx))
+
+
+def dset(idx, value=0):
+    unicorn_cartdata.dset(math.floor(idx), value)
+
+
+globals()["cartdata"] = cartdata
+globals()["dget"] = dget
+globals()["dset"] = dset
+
 # Collision
 class CollisionRect(object):
     def __init__(self, x, y, w, h):
@@ -462,6 +478,10 @@ def font(name="pico8"):
     unicorn_graphic.font(name)
 
 
+def font_ttf(name, size=8, mode="threshold"):
+    return unicorn_graphic.font_ttf(name, size, mode)
+
+
 def fset(idx_sprite, flag, value=-1):
     if value == -1:
         unicorn_graphic.fset_all(flag)
@@ -483,6 +503,10 @@ def palt(c=-1, t=False):
     unicorn_graphic.palt(math.floor(c), t)
 
 
+def fillp(pattern=0, color=-1, size=4):
+    unicorn_graphic.fillp(math.floor(pattern), math.floor(color), math.floor(size))
+
+
 def pget(x, y):
     return unicorn_graphic.pget(math.floor(x), math.floor(y))
 
@@ -495,6 +519,24 @@ def unicorn_print(str, x=-1, y=-1, col=-1):
     return unicorn_graphic.print(str, x, y, col)
 
 
+def print_ttf(text, x=0, y=0, col=-1):
+    unicorn_graphic.print_ttf(str(text), math.floor(x), math.floor(y), math.floor(col))
+
+
+def print_box(text, x, y, w, h=0, align="left", col=-1):
+    return unicorn_graphic.print_box(str(text), math.floor(x), math.floor(y),
+                                     math.floor(w), math.floor(h),
+                                     align, math.floor(col))
+
+
+def text_width(text):
+    return unicorn_graphic.text_width(str(text))
+
+
+def text_height(text):
+    return unicorn_graphic.text_height(str(text))
+
+
 def rect(x1, y1, x2, y2, color=-1):
     unicorn_graphic.rect(math.floor(x1), math.floor(y1),
                          math.floor(x2), math.floor(y2),
@@ -567,12 +609,18 @@ globals()["ellipse"] = ellipse
 globals()["ellipsefill"] = ellipsefill
 globals()["fget"] = fget
 globals()["fset"] = fset
+globals()["fillp"] = fillp
+globals()["font_ttf"] = font_ttf
 globals()["line"] = line
 globals()["pal"] = pal
 globals()["palt"] = palt
 globals()["pset"] = pset
 globals()["pget"] = pget
 globals()["unicorn_print"] = unicorn_print
+globals()["print_ttf"] = print_ttf
+globals()["print_box"] = print_box
+globals()["text_width"] = text_width
+globals()["text_height"] = text_height
 globals()["rect"] = rect
 globals()["rectfill"] = rectfill
 globals()["sget"] = sget
@@ -599,6 +647,10 @@ def btnp(x, p=0):
     return unicorn_input.btnp_raw(ord(x))
 
 
+def axis(x, p=0):
+    return unicorn_input.axis(x, p)
+
+
 def mouse_x():
     return unicorn_input.btn_mouse(0)
 
@@ -617,6 +669,7 @@ def mouse_statep():
 
 globals()["btn"] = btn
 globals()["btnp"] = btnp
+globals()["axis"] = axis
 
 globals()["mouse_x"] = mouse_x
 globals()["mouse_y"] = mouse_y
@@ -626,16 +679,16 @@ globals()["mouse_statep"] = mouse_statep
 # Map
 
 
-def mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer=0):
-    unicorn_map.mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer)
+def mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer=0, map_layer=0):
+    unicorn_map.mapdraw(cel_x, cel_y, sx, sy, cel_w, cel_h, layer, map_layer)
 
 
-def mget(x, y):
-    return unicorn_map.mget(math.floor(x), math.floor(y))
+def mget(x, y, map_layer=0):
+    return unicorn_map.mget(math.floor(x), math.floor(y), map_layer)
 
 
-def mset(x, y, v):
-    unicorn_map.mset(math.floor(x), math.floor(y), math.floor(v))
+def mset(x, y, v, map_layer=0):
+    unicorn_map.mset(math.floor(x), math.floor(y), math.floor(v), map_layer)
 
 
 globals()["mapdraw"] = mapdraw
@@ -662,12 +715,12 @@ def flr(x):
     return math.floor(x)
 
 
-def rnd(x):
-    return random.random() * x
+def rnd(x=1):
+    return unicorn_random.rnd(x)
 
 
 def srand(x):
-    return random.seed(x)
+    return unicorn_random.srand(int(x))
 
 
 def mid(x,y,z):
@@ -687,6 +740,7 @@ globals()["cos"] = cos
 globals()["sin"] = sin
 globals()["flr"] = flr
 globals()["rnd"] = rnd
+globals()["srand"] = srand
 globals()["sqrt"] = math.sqrt
 globals()["mid"] = mid
 globals()["bxor"] = bxor
@@ -755,6 +809,29 @@ def show_mouse(value=True):
     unicorn_sys.show_mouse(value)
 
 
+def meta(key):
+    return unicorn_sys.meta(key)
+
+
+# The callbacks stay in Python, the pause menu only knows the index
+_unicorn_menuitems = {}
+
+
+def menuitem(index, label="", callback=None):
+    index = math.floor(index)
+    _unicorn_menuitems[index] = callback
+    return unicorn_sys.menuitem(index, str(label))
+
+
+def _unicorn_menuitem_call(index):
+    callback = _unicorn_menuitems.get(index)
+    if callback is not None:
+        callback()
+
+
 globals()["unicorn_time"] = unicorn_time
 globals()["unicorn_time_sec"] = unicorn_time_sec
 globals()["show_mouse"] = show_mouse
+globals()["meta"] = meta
+globals()["menuitem"] = menuitem
+globals()["_unicorn_menuitem_call"] = _unicorn_menuitem_call
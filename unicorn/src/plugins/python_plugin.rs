@@ -9,8 +9,11 @@ pub mod plugin {
     use unicorn::info::Info;
     use unicorn::Palettes;
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
     use unicorn::UnicornConfig;
-    use gfx::Screen;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
+    use gfx::{Screen, TextAlign, TtfMode};
     use sound::sound::Sound;
 
     /*
@@ -24,13 +27,19 @@ pub mod plugin {
         ellipse                 #       X       #                   #
         ellipsefill             #       X       #                   #
         fget                    #       X       #                   #
+        fillp                   #       X       #                   #
         font                    #       X       #                   #
+        font_ttf                #       X       #                   #
         line                    #       X       #                   #
         pal                     #       X       #                   #
         palt                    #       X       #                   #
         pget                    #       X       #                   #
         polygon                 #       X       #                   #
         print                   #       X       # unicorn_print     #
+        print_ttf               #       X       #                   #
+        print_box               #       X       #                   #
+        text_width              #       X       #                   #
+        text_height             #       X       #                   #
         pset                    #       X       #                   #
         rect                    #       X       #                   #
         rectfill                #       X       #                   #
@@ -52,6 +61,7 @@ pub mod plugin {
         # Input                 #               #                   #
         btn                     #       X       #                   #
         btnp                    #       X       #                   #
+        axis                    #       X       #                   #
         mouse_x                 #       X       #                   #
         mouse_y                 #       X       #                   #
         mouse_state             #       X       #                   #
@@ -79,10 +89,15 @@ pub mod plugin {
         bxor                    #       X       #                   #
         # Memory                #               #                   #
         memcpy                  #       X       #                   #
+        # Cart Data             #               #                   #
+        cartdata                #       X       #                   #
+        dget                    #       X       #                   #
+        dset                    #       X       #                   #
         # System                #               #                   #
         time                    #       X       # unicorn_time      #
         time_sec                #       X       # unicorn_time_sec  #
         show_mouse              #       X       #                   #
+        menuitem                #       X       #                   #
     */
 
     // Audio
@@ -126,6 +141,25 @@ pub mod plugin {
 
     });
 
+    // Cart Data
+    py_class!(class UnicornCartData |py| {
+    data cartdata: Arc<Mutex<CartData>>;
+
+    def cartdata(&self, name: String) -> PyResult<bool> {
+        Ok(self.cartdata(py).lock().unwrap().open(&name))
+    }
+
+    def dget(&self, idx: i64) -> PyResult<f64> {
+        Ok(self.cartdata(py).lock().unwrap().get(idx))
+    }
+
+    def dset(&self, idx: i64, value: f64) -> PyResult<i32> {
+        self.cartdata(py).lock().unwrap().set(idx, value);
+        Ok(0)
+    }
+
+    });
+
     // Palettes
     py_class!(class UnicornPalette |py| {
     data palettes: Arc<Mutex<Palettes>>;
@@ -206,6 +240,10 @@ pub mod plugin {
         Ok(0)
     }
 
+    def font_ttf(&self, name: String, size: f64, mode: String) -> PyResult<bool> {
+        Ok(self.screen(py).lock().unwrap().font_ttf(&name, size as f32, TtfMode::from_name(&mode)))
+    }
+
     def fset(&self, idx: u32, flag: u8, value: bool) -> PyResult<i32> {
         self.screen(py).lock().unwrap().fset(idx, flag, value);
         Ok(0)
@@ -243,6 +281,11 @@ pub mod plugin {
         Ok(0)
     }
 
+    def fillp(&self, pattern: u64, color: i32, size: u32) -> PyResult<i32> {
+        self.screen(py).lock().unwrap().fillp(pattern, size, color);
+        Ok(0)
+    }
+
     def pset(&self, x: i32, y: i32, color: i32) -> PyResult<i32> {
         self.screen(py).lock().unwrap().pset(x, y, color);
         Ok(0)
@@ -253,6 +296,23 @@ pub mod plugin {
         Ok(0)
     }
 
+    def print_ttf(&self, str: String, x: i32, y: i32, color: i32) -> PyResult<i32> {
+        self.screen(py).lock().unwrap().print_ttf(str, x, y, color);
+        Ok(0)
+    }
+
+    def print_box(&self, str: String, x: i32, y: i32, w: i32, h: i32, align: String, color: i32) -> PyResult<i32> {
+        Ok(self.screen(py).lock().unwrap().print_box(str, x, y, w, h, TextAlign::from_name(&align), color))
+    }
+
+    def text_width(&self, str: String) -> PyResult<i32> {
+        Ok(self.screen(py).lock().unwrap().text_width(&str))
+    }
+
+    def text_height(&self, str: String) -> PyResult<i32> {
+        Ok(self.screen(py).lock().unwrap().text_height(&str))
+    }
+
     def pget(&self, x: i32, y: i32) -> PyResult<u32> {
         let value = self.screen(py).lock().unwrap().pget(x as u32, y as u32);
         Ok(value)
@@ -389,6 +449,11 @@ pub mod plugin {
         Ok(value)
     }
 
+    def axis(&self, x: i32, p: i32) -> PyResult<f64> {
+        let value = self.players(py).lock().unwrap().axis(p as u8, x as usize);
+        Ok(value)
+    }
+
     def btnp_raw(&self, x: i32) -> PyResult<bool> {
         let value = self.players(py).lock().unwrap().btnp_raw(x);
         Ok(value)
@@ -415,23 +480,23 @@ pub mod plugin {
     py_class!(class UnicornMap |py| {
     data screen: Arc < Mutex < Screen > >;
 
-    def mapdraw(&self, cel_x: i32, cel_y: i32, sx: i32, sy: i32, cel_w: i32, cel_h: i32, layer: u8) -> PyResult<i32> {
+    def mapdraw(&self, cel_x: i32, cel_y: i32, sx: i32, sy: i32, cel_w: i32, cel_h: i32, layer: u8, map_layer: u32) -> PyResult<i32> {
         self.screen(py).lock().unwrap().mapdraw(cel_x as u32, cel_y as u32,
                                                 sx, sy,
                                                 cel_w as u32, cel_h as u32,
-                                                layer);
+                                                layer, map_layer);
 
         Ok(0)
     }
 
 
-    def mget(&self, x: i32, y: i32) -> PyResult<u32> {
-        let value = self.screen(py).lock().unwrap().mget(x, y);
+    def mget(&self, x: i32, y: i32, map_layer: u32) -> PyResult<u32> {
+        let value = self.screen(py).lock().unwrap().mget(x, y, map_layer);
         Ok(value)
     }
 
-    def mset(&self, x: i32, y: i32, v: u32) -> PyResult<i32> {
-        self.screen(py).lock().unwrap().mset(x, y, v);
+    def mset(&self, x: i32, y: i32, v: u32, map_layer: u32) -> PyResult<i32> {
+        self.screen(py).lock().unwrap().mset(x, y, v, map_layer);
         Ok(0)
     }
 
@@ -464,10 +529,25 @@ pub mod plugin {
     });
 
 
+    // Random
+    py_class!(class UnicornRandom |py| {
+    data _random: Arc < Mutex < Random > >;
+        def rnd(&self, x: f64) -> PyResult<f64> {
+            Ok(self._random(py).lock().unwrap().rnd(x))
+        }
+
+        def srand(&self, seed: u32) -> PyResult<u32> {
+            self._random(py).lock().unwrap().set_seed(seed);
+            Ok(0)
+        }
+    });
+
+
     // Others
     py_class!(class UnicornSys |py| {
     data info: Arc < Mutex <Info > >;
     data config: Arc<Mutex<UnicornConfig>>;
+    data menuitems: Arc<Mutex<MenuItems>>;
 
         def show_mouse(&self, value: bool) -> PyResult<u32> {
             self.config(py).lock().unwrap().toggle_mouse(value);
@@ -481,6 +561,14 @@ pub mod plugin {
         def time_sec(&self) -> PyResult<f64> {
             Ok(self.info(py).lock().unwrap().time_sec())
         }
+
+        def meta(&self, key: String) -> PyResult<Option<String>> {
+            Ok(self.info(py).lock().unwrap().meta(&key))
+        }
+
+        def menuitem(&self, index: u32, label: String) -> PyResult<bool> {
+            Ok(self.menuitems(py).lock().unwrap().set(index, &label))
+        }
     });
 
     pub struct PythonPlugin {
@@ -509,7 +597,10 @@ pub mod plugin {
                     screen: Arc<Mutex<Screen>>,
                     sound: Arc<Mutex<Sound>>,
                     noise: Arc<Mutex<Noise>>,
-                    config: Arc<Mutex<UnicornConfig>>) {
+                    random: Arc<Mutex<Random>>,
+                    config: Arc<Mutex<UnicornConfig>>,
+                    cartdata: Arc<Mutex<CartData>>,
+                    menuitems: Arc<Mutex<MenuItems>>) {
             info!("[PLUGIN][PYTHON] Init plugin");
 
             let gil = Python::acquire_gil();
@@ -530,6 +621,11 @@ pub mod plugin {
                 .set_item(py, "unicorn_audio", unicorn_audio_obj)
                 .unwrap();
 
+            let unicorn_cartdata_obj = UnicornCartData::create_instance(py, cartdata.clone()).unwrap();
+            self.mydict
+                .set_item(py, "unicorn_cartdata", unicorn_cartdata_obj)
+                .unwrap();
+
             let unicorn_input_obj = UnicornInput::create_instance(py, players.clone()).unwrap();
             self.mydict
                 .set_item(py, "unicorn_input", unicorn_input_obj)
@@ -538,7 +634,7 @@ pub mod plugin {
             let unicorn_map_obj = UnicornMap::create_instance(py, screen.clone()).unwrap();
             self.mydict.set_item(py, "unicorn_map", unicorn_map_obj).unwrap();
 
-            let unicorn_sys_obj = UnicornSys::create_instance(py, info.clone(), config.clone()).unwrap();
+            let unicorn_sys_obj = UnicornSys::create_instance(py, info.clone(), config.clone(), menuitems.clone()).unwrap();
             self.mydict.set_item(py, "unicorn_sys", unicorn_sys_obj).unwrap();
 
             let unicorn_mem_obj = UnicornMemory::create_instance(py, screen.clone()).unwrap();
@@ -549,6 +645,11 @@ pub mod plugin {
                 .set_item(py, "unicorn_noise", unicorn_noise_obj)
                 .unwrap();
 
+            let unicorn_random_obj = UnicornRandom::create_instance(py, random.clone()).unwrap();
+            self.mydict
+                .set_item(py, "unicorn_random", unicorn_random_obj)
+                .unwrap();
+
             py.run(r###"globals()["unicorn_graphic"] = unicorn_graphic;"###,
                      None,
                      Some(&self.mydict))
@@ -561,6 +662,10 @@ pub mod plugin {
                      None,
                      Some(&self.mydict))
                 .unwrap();
+            py.run(r###"globals()["unicorn_cartdata"] = unicorn_cartdata;"###,
+                     None,
+                     Some(&self.mydict))
+                .unwrap();
             py.run(r###"globals()["unicorn_input"] = unicorn_input;"###,
                      None,
                      Some(&self.mydict))
@@ -581,6 +686,10 @@ pub mod plugin {
                      None,
                      Some(&self.mydict))
                 .unwrap();
+            py.run(r###"globals()["unicorn_random"] = unicorn_random;"###,
+                     None,
+                     Some(&self.mydict))
+                .unwrap();
 
             let data = include_str!("python/api.py").to_string();
 
@@ -673,7 +782,25 @@ pub mod plugin {
         }
 
 
-        pub fn load_code(&mut self, data: String) -> bool {
+        /// Call the callback of the entry `index` added with menuitem()
+        pub fn menuitem(&mut self, index: u32) {
+            if !self.loaded_code {
+                return;
+            }
+
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+
+            let result = py.run(&format!("_unicorn_menuitem_call({})", index),
+                                None,
+                                Some(&self.mydict));
+            if let Err(v) = result {
+                warn!("[PLUGIN][PYTHON] MENUITEM = {:?}", v);
+            }
+        }
+
+        /// The error is the Python exception, its type and its value
+        pub fn load_code(&mut self, data: String) -> Result<(), String> {
             info!("[PLUGIN][PYTHON] Load the code");
             let gil = Python::acquire_gil();
             let py = gil.python();
@@ -684,15 +811,21 @@ pub mod plugin {
             match result {
                 Ok(_) => {
                     debug!("[PLUGIN][PYTHON] Code loaded successfully");
-                    self.loaded_code = true
+                    self.loaded_code = true;
+                    Ok(())
                 }
-                Err(err) => {
+                Err(mut err) => {
                     error!("[PLUGIN][PYTHON] Load code error => {:?}", err);
-                    self.loaded_code = false
+                    self.loaded_code = false;
+
+                    let name = err.get_type(py).name(py).into_owned();
+                    let message = match err.instance(py).str(py) {
+                        Ok(value) => format!("{}: {}", name, value.to_string_lossy(py)),
+                        Err(_) => name,
+                    };
+                    Err(message)
                 }
             }
-
-            self.loaded_code
         }
     }
 }
@@ -709,7 +842,10 @@ pub mod plugin {
     use unicorn::Palettes;
     use sound::sound::Sound;
     use unicorn::noise::Noise;
+    use unicorn::random::Random;
     use unicorn::UnicornConfig;
+    use unicorn::cartdata::CartData;
+    use unicorn::menuitems::MenuItems;
 
     pub struct PythonPlugin {}
 
@@ -726,7 +862,10 @@ pub mod plugin {
                     _screen: Arc<Mutex<Screen>>,
                     _sound: Arc<Mutex<Sound>>,
                     _noise: Arc<Mutex<Noise>>,
-                    _config: Arc<Mutex<UnicornConfig>>) {
+                    _random: Arc<Mutex<Random>>,
+                    _config: Arc<Mutex<UnicornConfig>>,
+                    _cartdata: Arc<Mutex<CartData>>,
+                    _menuitems: Arc<Mutex<MenuItems>>) {
             error!("[PLUGIN][PYTHON] plugin disabled");
         }
         pub fn init(&mut self) {}
@@ -736,8 +875,9 @@ pub mod plugin {
         pub fn update(&mut self) -> bool {
             false
         }
-        pub fn load_code(&mut self, _data: String) -> bool {
-            false
+        pub fn menuitem(&mut self, _index: u32) {}
+        pub fn load_code(&mut self, _data: String) -> Result<(), String> {
+            Err("Python is not supported".to_string())
         }
     }
 }
@@ -1,3 +1,185 @@
-/*pub struct CartData {
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
 
-}*/
+use config::files;
+
+/// Number of values available in one cartdata slot
+pub const CARTDATA_SIZE: usize = 64;
+
+pub fn default_directory() -> String {
+    files::config_path("cartdata")
+}
+
+/// Persistent storage of numbered values, one file per slot name.
+pub struct CartData {
+    pub directory: String,
+    pub name: String,
+    values: Vec<f64>,
+    dirty: bool,
+}
+
+impl CartData {
+    pub fn new() -> CartData {
+        CartData {
+            directory: default_directory(),
+            name: "".to_string(),
+            values: vec![0.0; CARTDATA_SIZE],
+            dirty: false,
+        }
+    }
+
+    pub fn set_directory(&mut self, directory: &str) {
+        info!("[Unicorn][CartData] Save directory {:?}", directory);
+
+        self.flush();
+        self.directory = directory.to_string();
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn filename(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.directory);
+        path.push(format!("{}.cartdata", self.name));
+        path
+    }
+
+    /// Open the slot `name`, loading the values already saved for it.
+    /// The name is used as a filename, so only [a-zA-Z0-9_-] is allowed.
+    pub fn open(&mut self, name: &str) -> bool {
+        let valid = name.chars().all(|c| match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => true,
+            _ => false,
+        });
+
+        if name.is_empty() || name.len() > 64 || !valid {
+            error!("[Unicorn][CartData] Invalid cartdata name {:?}", name);
+            return false;
+        }
+
+        self.flush();
+
+        self.name = name.to_string();
+        self.values = vec![0.0; CARTDATA_SIZE];
+        self.dirty = false;
+
+        let filename = self.filename();
+        info!("[Unicorn][CartData] Open {:?}", filename);
+
+        if let Ok(f) = File::open(&filename) {
+            for (idx, line) in BufReader::new(f).lines().enumerate() {
+                if idx >= CARTDATA_SIZE {
+                    break;
+                }
+
+                if let Ok(line) = line {
+                    self.values[idx] = line.trim().parse::<f64>().unwrap_or(0.0);
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn close(&mut self) {
+        self.flush();
+        self.name = "".to_string();
+        self.values = vec![0.0; CARTDATA_SIZE];
+    }
+
+    /// The indices outside of the slot (negative ones included) read 0
+    pub fn get(&self, idx: i64) -> f64 {
+        if !self.is_open() || idx < 0 {
+            return 0.0;
+        }
+
+        match self.values.get(idx as usize) {
+            Some(value) => *value,
+            None => 0.0,
+        }
+    }
+
+    /// The indices outside of the slot (negative ones included) are ignored
+    pub fn set(&mut self, idx: i64, value: f64) {
+        if !self.is_open() || idx < 0 {
+            return;
+        }
+
+        if let Some(v) = self.values.get_mut(idx as usize) {
+            if *v != value {
+                *v = value;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Write the values on disk if they changed since the last flush.
+    pub fn flush(&mut self) {
+        if !self.is_open() || !self.dirty {
+            return;
+        }
+
+        let filename = self.filename();
+        info!("[Unicorn][CartData] Flush {:?}", filename);
+
+        if let Err(e) = fs::create_dir_all(&self.directory) {
+            error!("[Unicorn][CartData] Impossible to create {:?}: {:?}",
+                   self.directory,
+                   e);
+            return;
+        }
+
+        let mut data = String::new();
+        for value in self.values.iter() {
+            data.push_str(&format!("{}\n", value));
+        }
+
+        match File::create(&filename) {
+            Ok(mut f) => {
+                match f.write_all(data.as_bytes()) {
+                    Ok(_) => self.dirty = false,
+                    Err(e) => error!("[Unicorn][CartData] Impossible to write {:?}: {:?}", filename, e),
+                }
+            }
+            Err(e) => error!("[Unicorn][CartData] Impossible to create {:?}: {:?}", filename, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_cartdata_round_trip() {
+        let mut dir = env::temp_dir();
+        dir.push("unicorn-cartdata-test");
+        let dir = dir.to_string_lossy().to_string();
+
+        let mut cartdata = CartData::new();
+        cartdata.set_directory(&dir);
+
+        assert!(!cartdata.open("../escape"));
+        assert!(cartdata.open("highscores"));
+        cartdata.set(3, 1234.5);
+        cartdata.set(CARTDATA_SIZE as i64, 1.0);
+        cartdata.set(-1, 1.0);
+        cartdata.close();
+
+        let mut cartdata = CartData::new();
+        cartdata.set_directory(&dir);
+        assert!(cartdata.open("highscores"));
+        assert_eq!(cartdata.get(3), 1234.5);
+        assert_eq!(cartdata.get(0), 0.0);
+        assert_eq!(cartdata.get(CARTDATA_SIZE as i64), 0.0);
+        assert_eq!(cartdata.get(-1), 0.0);
+        assert_eq!(cartdata.get(CARTDATA_SIZE as i64 - 1), 0.0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
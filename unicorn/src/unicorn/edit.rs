@@ -14,6 +14,10 @@ pub mod edit {
     use sound::sound::{SoundInternal, Sound};
     use unicorn::{UnicornCartridge, UnicornConfig, Palettes};
 
+    /// The editors are laid out for a 400x240 screen at least
+    pub const MIN_WIDTH: usize = 400;
+    pub const MIN_HEIGHT: usize = 240;
+
     #[derive(Clone, Copy)]
     pub struct State {
         pub mouse_x: i32,
@@ -194,6 +198,10 @@ pub mod edit {
             }
         }
 
+        pub fn supports_resolution(width: usize, height: usize) -> bool {
+            width >= MIN_WIDTH && height >= MIN_HEIGHT
+        }
+
         pub fn init(&mut self, config: Arc<Mutex<UnicornConfig>>, palettes: Arc<Mutex<Palettes>>, screen: &mut Screen, filename: String, code: String) {
             info!("[EDITOR] Init {:?}", filename);
             self.filename = filename.clone();
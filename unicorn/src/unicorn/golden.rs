@@ -0,0 +1,556 @@
+use std::io::prelude::*;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+#[cfg(feature = "image")]
+use image;
+
+use config::Players;
+use config::keys::PX8Key;
+use config::scancode::Scancode;
+use unicorn::headless::Headless;
+
+/// One input applied to the players before the update of a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    KeyDown(u8, PX8Key),
+    KeyUp(u8, PX8Key),
+    MouseMove(i32, i32),
+    MouseDown(bool, bool, bool),
+    MouseUp,
+    Text(String),
+    /// Raw keyboard state, read by btn_raw and btnp_raw
+    ScancodeDown(Scancode),
+    ScancodeUp(Scancode),
+    /// Analog axis (player, index, value)
+    Axis(u8, usize, f64),
+    ReleaseKeys,
+}
+
+impl InputEvent {
+    pub fn apply(&self, players: &mut Players, elapsed: f64) {
+        match *self {
+            InputEvent::KeyDown(player, key) => players.key_down_direct(player, key),
+            InputEvent::KeyUp(player, key) => players.key_up_direct(player, key),
+            InputEvent::MouseMove(x, y) => {
+                players.set_mouse_x(x);
+                players.set_mouse_y(y);
+            }
+            InputEvent::MouseDown(left, right, middle) => {
+                players.mouse_button_down(left, right, middle, elapsed)
+            }
+            InputEvent::MouseUp => players.mouse_button_up(),
+            InputEvent::Text(ref text) => players.set_text(text.clone()),
+            InputEvent::ScancodeDown(scancode) => players.scancode_down(scancode),
+            InputEvent::ScancodeUp(scancode) => players.scancode_up(scancode),
+            InputEvent::Axis(player, index, value) => players.set_axis(player, index, value),
+            InputEvent::ReleaseKeys => players.release_keys(),
+        }
+    }
+}
+
+/// Same syntax as the timeline files, without the frame number
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputEvent::KeyDown(player, key) => write!(f, "down {} {}", player, key.name()),
+            InputEvent::KeyUp(player, key) => write!(f, "up {} {}", player, key.name()),
+            InputEvent::MouseMove(x, y) => write!(f, "mouse {} {}", x, y),
+            InputEvent::MouseDown(left, right, _) => {
+                write!(f,
+                       "click {}",
+                       if left {
+                           "left"
+                       } else if right {
+                           "right"
+                       } else {
+                           "middle"
+                       })
+            }
+            InputEvent::MouseUp => write!(f, "release"),
+            InputEvent::Text(ref text) => write!(f, "text {}", text),
+            InputEvent::ScancodeDown(scancode) => write!(f, "key {:?}", scancode),
+            InputEvent::ScancodeUp(scancode) => write!(f, "keyup {:?}", scancode),
+            InputEvent::Axis(player, index, value) => {
+                write!(f, "axis {} {} {}", player, index, value)
+            }
+            InputEvent::ReleaseKeys => write!(f, "releasekeys"),
+        }
+    }
+}
+
+pub fn parse_key(name: &str) -> Option<PX8Key> {
+    PX8Key::from_name(&name.to_lowercase())
+}
+
+/// Scripted inputs, indexed by the frame where they are injected.
+///
+/// Text format, one event per line ('#' starts a comment):
+///
+/// ```text
+/// 10 down 0 right
+/// 40 up 0 right
+/// 50 mouse 120 64
+/// 51 click left
+/// 52 release
+/// 60 text hello
+/// 70 key LShift
+/// 80 keyup LShift
+/// 90 axis 0 0 -0.5
+/// 95 releasekeys
+/// ```
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub events: Vec<(u64, InputEvent)>,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline { events: Vec::new() }
+    }
+
+    pub fn push(&mut self, frame: u64, event: InputEvent) {
+        let pos = self.events.iter().position(|e| e.0 > frame).unwrap_or(self.events.len());
+        self.events.insert(pos, (frame, event));
+    }
+
+    pub fn parse(data: &str) -> Result<Timeline, String> {
+        let mut timeline = Timeline::new();
+
+        for (line_idx, line) in data.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            let mut iter = line.split_whitespace();
+            let frame = match iter.next() {
+                Some(value) => {
+                    match value.parse::<u64>() {
+                        Ok(v) => v,
+                        Err(_) => return Err(format!("line {}: bad frame {:?}", line_idx + 1, value)),
+                    }
+                }
+                None => continue,
+            };
+
+            let command = iter.next().unwrap_or("");
+            let args: Vec<&str> = iter.collect();
+
+            let event = match Timeline::parse_event(command, &args) {
+                Some(event) => event,
+                None => return Err(format!("line {}: bad event {:?}", line_idx + 1, line.trim())),
+            };
+
+            timeline.push(frame, event);
+        }
+
+        Ok(timeline)
+    }
+
+    pub fn parse_event(command: &str, args: &[&str]) -> Option<InputEvent> {
+        match (command, args.len()) {
+            ("down", 2) | ("up", 2) => {
+                let player = match args[0].parse::<u8>() {
+                    Ok(v) => v,
+                    Err(_) => return None,
+                };
+                parse_key(args[1]).map(|key| if command == "down" {
+                    InputEvent::KeyDown(player, key)
+                } else {
+                    InputEvent::KeyUp(player, key)
+                })
+            }
+            ("mouse", 2) => {
+                match (args[0].parse::<i32>(), args[1].parse::<i32>()) {
+                    (Ok(x), Ok(y)) => Some(InputEvent::MouseMove(x, y)),
+                    _ => None,
+                }
+            }
+            ("click", 1) => {
+                match args[0] {
+                    "left" => Some(InputEvent::MouseDown(true, false, false)),
+                    "right" => Some(InputEvent::MouseDown(false, true, false)),
+                    "middle" => Some(InputEvent::MouseDown(false, false, true)),
+                    _ => None,
+                }
+            }
+            ("release", 0) => Some(InputEvent::MouseUp),
+            ("text", _) => Some(InputEvent::Text(args.join(" "))),
+            ("key", 1) => Scancode::from_name(args[0]).map(InputEvent::ScancodeDown),
+            ("keyup", 1) => Scancode::from_name(args[0]).map(InputEvent::ScancodeUp),
+            ("axis", 3) => {
+                match (args[0].parse::<u8>(), args[1].parse::<usize>(), args[2].parse::<f64>()) {
+                    (Ok(player), Ok(index), Ok(value)) => {
+                        Some(InputEvent::Axis(player, index, value))
+                    }
+                    _ => None,
+                }
+            }
+            ("releasekeys", 0) => Some(InputEvent::ReleaseKeys),
+            _ => None,
+        }
+    }
+
+    pub fn from_file(filename: &str) -> Result<Timeline, String> {
+        let mut data = String::new();
+        match File::open(filename) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_string(&mut data) {
+                    return Err(format!("{}: {}", filename, e));
+                }
+            }
+            Err(e) => return Err(format!("{}: {}", filename, e)),
+        }
+
+        Timeline::parse(&data)
+    }
+
+    pub fn apply(&self, frame: u64, players: &mut Players, elapsed: f64) {
+        for &(event_frame, ref event) in self.events.iter() {
+            if event_frame == frame {
+                debug!("[GOLDEN] Frame {:?} -> {:?}", frame, event);
+                event.apply(players, elapsed);
+            }
+        }
+    }
+}
+
+/// RGB image, 3 bytes per pixel, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, data: Vec<u8>) -> Image {
+        Image {
+            width: width,
+            height: height,
+            data: data,
+        }
+    }
+
+    pub fn from_headless(headless: &Headless) -> Image {
+        Image::new(headless.width(), headless.height(), headless.rgb_buffer())
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let idx = (x + y * self.width) * 3;
+        [self.data[idx], self.data[idx + 1], self.data[idx + 2]]
+    }
+
+    /// Load a reference image. PPM is always available, PNG needs the `image` feature.
+    pub fn load(filename: &str) -> Result<Image, String> {
+        if filename.ends_with(".png") {
+            return Image::load_png(filename);
+        }
+
+        let mut data = Vec::new();
+        match File::open(filename) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_end(&mut data) {
+                    return Err(format!("{}: {}", filename, e));
+                }
+            }
+            Err(e) => return Err(format!("{}: {}", filename, e)),
+        }
+
+        Image::from_ppm(&data).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    pub fn from_ppm(data: &[u8]) -> Result<Image, String> {
+        let mut fields = Vec::new();
+        let mut idx = 0;
+
+        // P6 <width> <height> <maxval>, with optional comments
+        while fields.len() < 4 && idx < data.len() {
+            let c = data[idx];
+            if c == b'#' {
+                while idx < data.len() && data[idx] != b'\n' {
+                    idx += 1;
+                }
+            } else if (c as char).is_whitespace() {
+                idx += 1;
+            } else {
+                let start = idx;
+                while idx < data.len() && !(data[idx] as char).is_whitespace() {
+                    idx += 1;
+                }
+                fields.push(String::from_utf8_lossy(&data[start..idx]).to_string());
+            }
+        }
+
+        if fields.len() < 4 || fields[0] != "P6" || fields[3] != "255" {
+            return Err("not a binary 8 bits PPM".to_string());
+        }
+
+        let width = try!(fields[1].parse::<usize>().map_err(|e| e.to_string()));
+        let height = try!(fields[2].parse::<usize>().map_err(|e| e.to_string()));
+
+        // One whitespace between the header and the pixels
+        idx += 1;
+        let size = width * height * 3;
+        if data.len() < idx + size {
+            return Err(format!("truncated PPM, {} bytes of pixels expected", size));
+        }
+
+        Ok(Image::new(width, height, data[idx..idx + size].to_vec()))
+    }
+
+    #[cfg(feature = "image")]
+    fn load_png(filename: &str) -> Result<Image, String> {
+        match image::open(&Path::new(filename)) {
+            Ok(img) => {
+                let img = img.to_rgb();
+                let (width, height) = img.dimensions();
+                Ok(Image::new(width as usize, height as usize, img.into_raw()))
+            }
+            Err(e) => Err(format!("{}: {:?}", filename, e)),
+        }
+    }
+
+    #[cfg(not(feature = "image"))]
+    fn load_png(filename: &str) -> Result<Image, String> {
+        Err(format!("{}: PNG support needs the image feature, use a .ppm reference",
+                    filename))
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        if filename.ends_with(".png") {
+            return self.save_png(filename);
+        }
+
+        let mut output = try!(File::create(&Path::new(filename)).map_err(|e| e.to_string()));
+        try!(write!(output, "P6\n{} {}\n255\n", self.width, self.height)
+            .map_err(|e| e.to_string()));
+        output.write_all(&self.data).map_err(|e| e.to_string())
+    }
+
+    #[cfg(feature = "image")]
+    fn save_png(&self, filename: &str) -> Result<(), String> {
+        let buffer: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> =
+            match image::ImageBuffer::from_raw(self.width as u32,
+                                               self.height as u32,
+                                               self.data.clone()) {
+                Some(buffer) => buffer,
+                None => return Err("invalid image size".to_string()),
+            };
+
+        let mut output = try!(File::create(&Path::new(filename)).map_err(|e| e.to_string()));
+        image::DynamicImage::ImageRgb8(buffer)
+            .save(&mut output, image::ImageFormat::PNG)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    #[cfg(not(feature = "image"))]
+    fn save_png(&self, filename: &str) -> Result<(), String> {
+        Err(format!("{}: PNG support needs the image feature, use a .ppm reference",
+                    filename))
+    }
+}
+
+/// Result of a per-pixel comparison between a reference and a captured frame.
+#[derive(Debug)]
+pub struct Diff {
+    pub width: usize,
+    pub height: usize,
+    pub mismatches: usize,
+    /// First mismatching pixels: (x, y, expected, actual)
+    pub pixels: Vec<(usize, usize, [u8; 3], [u8; 3])>,
+    pub image: Image,
+}
+
+const DIFF_MAX_PIXELS: usize = 16;
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.mismatches == 0
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!("{} / {} pixels differ",
+                                 self.mismatches,
+                                 self.width * self.height);
+
+        for &(x, y, expected, actual) in self.pixels.iter() {
+            report.push_str(&format!("\n  ({}, {}): expected #{:02x}{:02x}{:02x} got #{:02x}{:02x}{:02x}",
+                                     x,
+                                     y,
+                                     expected[0],
+                                     expected[1],
+                                     expected[2],
+                                     actual[0],
+                                     actual[1],
+                                     actual[2]));
+        }
+
+        if self.mismatches > self.pixels.len() {
+            report.push_str(&format!("\n  ... and {} more",
+                                     self.mismatches - self.pixels.len()));
+        }
+
+        report
+    }
+}
+
+/// Compare two images pixel by pixel. The diff image shows the actual
+/// frame darkened, with every mismatching pixel in red.
+pub fn compare(expected: &Image, actual: &Image) -> Result<Diff, String> {
+    if expected.width != actual.width || expected.height != actual.height {
+        return Err(format!("size mismatch: expected {}x{} got {}x{}",
+                           expected.width,
+                           expected.height,
+                           actual.width,
+                           actual.height));
+    }
+
+    let mut mismatches = 0;
+    let mut pixels = Vec::new();
+    let mut data = Vec::with_capacity(actual.data.len());
+
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let e = expected.pixel(x, y);
+            let a = actual.pixel(x, y);
+
+            if e != a {
+                mismatches += 1;
+                if pixels.len() < DIFF_MAX_PIXELS {
+                    pixels.push((x, y, e, a));
+                }
+                data.extend_from_slice(&[255, 0, 0]);
+            } else {
+                data.extend_from_slice(&[a[0] / 4, a[1] / 4, a[2] / 4]);
+            }
+        }
+    }
+
+    Ok(Diff {
+        width: actual.width,
+        height: actual.height,
+        mismatches: mismatches,
+        pixels: pixels,
+        image: Image::new(actual.width, actual.height, data),
+    })
+}
+
+/// Parse a FRAME=FILE checkpoint argument. The frames start at 1, the
+/// frame buffer is empty before the first step.
+pub fn parse_checkpoint(value: &str) -> Result<(u64, String), String> {
+    let mut iter = value.splitn(2, '=');
+    let frame = iter.next().unwrap().parse::<u64>();
+
+    match (frame, iter.next()) {
+        (Ok(0), Some(_)) => Err(format!("Bad checkpoint {:?}, the frames start at 1", value)),
+        (Ok(frame), Some(filename)) if !filename.is_empty() => Ok((frame, filename.to_string())),
+        _ => Err(format!("Bad golden argument {:?}, expected FRAME=FILE", value)),
+    }
+}
+
+/// Run the loaded cartridge with the scripted inputs, and compare the frame
+/// buffer after each checkpoint frame with its reference image.
+///
+/// With `update`, missing or different references are (re)written instead.
+pub fn run(headless: &mut Headless,
+           timeline: &Timeline,
+           checkpoints: &[(u64, String)],
+           update: bool)
+           -> Result<Vec<(u64, Diff)>, String> {
+    if checkpoints.iter().any(|c| c.0 == 0) {
+        return Err("checkpoint at frame 0, the frames start at 1".to_string());
+    }
+
+    let last = checkpoints.iter().map(|c| c.0).max().unwrap_or(0);
+    let mut diffs = Vec::new();
+
+    while headless.frame < last {
+        let elapsed = headless.uc.info.lock().unwrap().elapsed_time;
+        timeline.apply(headless.frame,
+                       &mut headless.uc.players.lock().unwrap(),
+                       elapsed);
+
+        if !headless.step() {
+            return Err(format!("cartridge stopped at frame {}", headless.frame));
+        }
+
+        for &(frame, ref filename) in checkpoints.iter() {
+            if frame != headless.frame {
+                continue;
+            }
+
+            let actual = Image::from_headless(headless);
+
+            if update {
+                info!("[GOLDEN] Update reference {:?} for frame {:?}", filename, frame);
+                try!(actual.save(filename));
+                continue;
+            }
+
+            let expected = try!(Image::load(filename));
+            diffs.push((frame, try!(compare(&expected, &actual))));
+        }
+    }
+
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeline_parse() {
+        let timeline = Timeline::parse("# start\n\
+                                        20 up 0 right\n\
+                                        10 down 0 right # hold\n\
+                                        15 mouse 12 34\n\
+                                        16 click left\n")
+            .unwrap();
+
+        assert_eq!(timeline.events,
+                   vec![(10, InputEvent::KeyDown(0, PX8Key::Right)),
+                        (15, InputEvent::MouseMove(12, 34)),
+                        (16, InputEvent::MouseDown(true, false, false)),
+                        (20, InputEvent::KeyUp(0, PX8Key::Right))]);
+
+        assert!(Timeline::parse("10 jump 0").is_err());
+    }
+
+    #[test]
+    fn test_parse_checkpoint() {
+        assert_eq!(parse_checkpoint("12=ref.ppm").unwrap(), (12, "ref.ppm".to_string()));
+        assert!(parse_checkpoint("0=ref.ppm").is_err());
+        assert!(parse_checkpoint("12").is_err());
+        assert!(parse_checkpoint("12=").is_err());
+        assert!(parse_checkpoint("x=ref.ppm").is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = Image::new(2, 2, vec![0; 12]);
+        let mut actual = expected.clone();
+        assert!(compare(&expected, &actual).unwrap().is_empty());
+
+        actual.data[9] = 255;
+        let diff = compare(&expected, &actual).unwrap();
+        assert_eq!(diff.mismatches, 1);
+        assert_eq!(diff.pixels[0], (1, 1, [0, 0, 0], [255, 0, 0]));
+
+        assert!(compare(&expected, &Image::new(1, 1, vec![0; 3])).is_err());
+    }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let mut data = b"P6\n# comment\n2 1\n255\n".to_vec();
+        data.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let image = Image::from_ppm(&data).unwrap();
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 1);
+        assert_eq!(image.pixel(1, 0), [4, 5, 6]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use gfx;
+use unicorn::{Unicorn, PALETTE};
+
+/// Seed of the random generators, unless another one is given
+pub const DEFAULT_SEED: u32 = 0;
+
+/// Drive a `Unicorn` console without any window, audio device or input backend.
+///
+/// Every frame is stepped with the same `dt`, so running the same cartridge
+/// twice for the same number of frames gives the same frame buffer.
+pub struct Headless {
+    pub uc: Unicorn,
+    pub dt: Duration,
+    pub frame: u64,
+}
+
+impl Headless {
+    pub fn new(dt: Duration) -> Headless {
+        info!("[Headless] Creating headless host, dt = {:?}", dt);
+
+        let mut uc = Unicorn::new();
+        uc.setup();
+        uc.set_seed(DEFAULT_SEED);
+
+        Headless {
+            uc: uc,
+            dt: dt,
+            frame: 0,
+        }
+    }
+
+    pub fn load_cartridge(&mut self, filename: &str) -> bool {
+        info!("[Headless] Load cartridge {:?}", filename);
+
+        self.frame = 0;
+        self.uc.load_cartridge(filename, filename, false)
+    }
+
+    pub fn load_cartridge_raw(&mut self, filename: &str, data: Vec<u8>) -> bool {
+        info!("[Headless] Load raw cartridge {:?}", filename);
+
+        self.frame = 0;
+        self.uc.load_cartridge_raw(filename, data, false)
+    }
+
+    /// Replay an input movie, on the cartridge it was recorded with
+    pub fn replay_movie(&mut self, filename: &str) -> bool {
+        info!("[Headless] Replay movie {:?}", filename);
+
+        self.frame = 0;
+        self.uc.replay_movie(filename)
+    }
+
+    /// Run one frame: update, draw then advance the console time by `dt`.
+    /// Returns false when the cartridge asked to stop, the console is
+    /// stopped by the caller once it is done with it.
+    pub fn step(&mut self) -> bool {
+        if !self.uc.update() {
+            info!("[Headless] End requested at frame {:?}", self.frame);
+            return false;
+        }
+
+        self.uc.draw();
+        self.drain_sound();
+        self.uc.update_time(self.dt);
+
+        self.frame += 1;
+
+        true
+    }
+
+    /// Step at most `frames` frames and return the number of frames done.
+    pub fn run(&mut self, frames: u64) -> u64 {
+        let start = self.frame;
+
+        for _ in 0..frames {
+            if !self.step() {
+                break;
+            }
+        }
+
+        self.frame - start
+    }
+
+    /// There is nobody to play the sound packets, so just drop them
+    /// instead of letting the channel grow forever.
+    fn drain_sound(&mut self) {
+        let sound_internal = self.uc.sound_internal.lock().unwrap();
+        for _ in sound_internal.crecv.try_iter() {}
+    }
+
+    pub fn width(&self) -> usize {
+        self.uc.screen.lock().unwrap().width
+    }
+
+    pub fn height(&self) -> usize {
+        self.uc.screen.lock().unwrap().height
+    }
+
+    pub fn frame_buffer(&self) -> Vec<gfx::Color> {
+        self.uc.screen.lock().unwrap().frame_buffer.clone()
+    }
+
+    /// Frame buffer converted with the current palette, 3 bytes per pixel, row by row.
+    pub fn rgb_buffer(&self) -> Vec<u8> {
+        let screen = self.uc.screen.lock().unwrap();
+
+        let mut buffer = vec![0; screen.frame_buffer.len() * 3];
+        PALETTE.lock().unwrap().to_rgb(&screen.frame_buffer, &mut buffer);
+
+        buffer
+    }
+
+    /// Write the current frame as a binary PPM, which needs no image codec.
+    pub fn save_ppm(&self, filename: &str) -> io::Result<()> {
+        info!("[Headless] Saving frame {:?} in {:?}", self.frame, filename);
+
+        let mut output = try!(File::create(&Path::new(filename)));
+        try!(write!(output, "P6\n{} {}\n255\n", self.width(), self.height()));
+        try!(output.write_all(&self.rgb_buffer()));
+
+        Ok(())
+    }
+}
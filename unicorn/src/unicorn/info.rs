@@ -1,9 +1,14 @@
 use std::time::Duration;
 
+use cartridge::CartridgeConfig;
+
+#[derive(Clone)]
 pub struct Info {
     pub current: Duration,
     pub milliseconds: u64,
     pub elapsed_time: f64,
+    /// Metadata of the running cartridge, see `meta`
+    pub config: CartridgeConfig,
 }
 
 
@@ -13,9 +18,19 @@ impl Info {
             current: Duration::from_millis(0),
             milliseconds: 0,
             elapsed_time: 0.0,
+            config: CartridgeConfig::empty(),
         }
     }
 
+    pub fn set_config(&mut self, config: CartridgeConfig) {
+        self.config = config;
+    }
+
+    /// Value of a `__config__` setting of the running cartridge
+    pub fn meta(&self, key: &str) -> Option<String> {
+        self.config.get(key)
+    }
+
     pub fn update(&mut self, dt: Duration) {
         if self.current > dt {
             let nanoseconds = dt.subsec_nanos();
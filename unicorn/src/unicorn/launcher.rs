@@ -0,0 +1,422 @@
+use std::cmp::{max, min};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use glob::{glob, Pattern};
+
+use cartridge::Cartridge;
+use config::files;
+use config::Players;
+use gfx::Screen;
+use super::draw_logo;
+
+/* FAVORITES FILE (JSON)
+
+{
+  "favorites": ["/home/user/games/demo.uni", ...]
+}
+
+*/
+
+/// Extensions of the cartridges listed by the launcher
+pub const EXTENSIONS: [&'static str; 6] = ["uni", "uc", "duc", "unb", "png", "p8"];
+
+/// The label is the top left corner of the sprite sheet, 4x4 sprites
+pub const LABEL_SIZE: usize = 32;
+
+const ENTRIES_PER_PAGE: usize = 6;
+const ENTRY_HEIGHT: i32 = 36;
+const TOP: i32 = 12;
+
+pub fn default_favorites_filename() -> String {
+    files::config_path("favorites.json")
+}
+
+#[derive(Serialize, Deserialize)]
+struct FavoritesFile {
+    favorites: Vec<String>,
+}
+
+/// Pixels of the label of a cartridge, LABEL_SIZE x LABEL_SIZE colors
+pub fn label(cartridge: &Cartridge) -> Vec<u32> {
+    let mut label = vec![0; LABEL_SIZE * LABEL_SIZE];
+    let sprites_per_row = cartridge.config.sprites_per_row();
+
+    for y in 0..LABEL_SIZE {
+        for x in 0..LABEL_SIZE {
+            let idx_sprite = (y / 8) * sprites_per_row + x / 8;
+            if let Some(sprite) = cartridge.gfx.sprites.get(idx_sprite) {
+                label[y * LABEL_SIZE + x] = sprite.data[(y % 8) * 8 + x % 8] as u32;
+            }
+        }
+    }
+
+    label
+}
+
+/// Cartridge found by the launcher, it is only read to get its metadata
+pub struct Entry {
+    pub filename: String,
+    pub full_filename: String,
+    pub title: String,
+    pub author: String,
+    pub label: Vec<u32>,
+    pub favorite: bool,
+    /// Why the cartridge can't be read, it is listed anyway
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn from_file(full_filename: &str) -> Entry {
+        let filename = match Path::new(full_filename).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => full_filename.to_string(),
+        };
+
+        let mut entry = Entry {
+            filename: filename.clone(),
+            full_filename: full_filename.to_string(),
+            title: filename,
+            author: "".to_string(),
+            label: vec![0; LABEL_SIZE * LABEL_SIZE],
+            favorite: false,
+            error: None,
+        };
+
+        match Cartridge::from_file(full_filename) {
+            Ok(cartridge) => {
+                if !cartridge.config.title.is_empty() {
+                    entry.title = cartridge.config.title.clone();
+                }
+                entry.author = cartridge.config.author.clone();
+                entry.label = label(&cartridge);
+            }
+            Err(e) => {
+                warn!("[Unicorn][Launcher] Impossible to read {:?}: {}", full_filename, e);
+                entry.error = Some(format!("{}", e));
+            }
+        }
+
+        entry
+    }
+}
+
+/// Cartridges browser of the INTERACTIVE state: the cartridges of a
+/// directory, with the favorite ones first.
+pub struct Launcher {
+    pub directory: String,
+    pub entries: Vec<Entry>,
+    pub favorites_filename: String,
+    idx: usize,
+}
+
+impl Launcher {
+    pub fn new() -> Launcher {
+        Launcher {
+            directory: "".to_string(),
+            entries: Vec::new(),
+            favorites_filename: default_favorites_filename(),
+            idx: 0,
+        }
+    }
+
+    pub fn scan(&mut self, directory: &str) {
+        info!("[Unicorn][Launcher] Scan {:?}", directory);
+
+        self.directory = directory.to_string();
+        self.entries.clear();
+        self.idx = 0;
+
+        let favorites = self.load_favorites();
+
+        for extension in EXTENSIONS.iter() {
+            let pattern = Path::new(&Pattern::escape(directory)).join(format!("*.{}", extension));
+            let paths = match glob(&pattern.to_string_lossy()) {
+                Ok(paths) => paths,
+                Err(e) => {
+                    error!("[Unicorn][Launcher] Bad pattern {:?}: {:?}", pattern, e);
+                    continue;
+                }
+            };
+
+            for path in paths.filter_map(|path| path.ok()) {
+                // The favorites don't depend on the current directory
+                let path = fs::canonicalize(&path).unwrap_or(path);
+
+                let mut entry = Entry::from_file(&path.to_string_lossy());
+                entry.favorite = favorites.contains(&entry.full_filename);
+                self.entries.push(entry);
+            }
+        }
+
+        self.sort();
+
+        info!("[Unicorn][Launcher] {:?} cartridges", self.entries.len());
+    }
+
+    pub fn current(&self) -> Option<&Entry> {
+        self.entries.get(self.idx)
+    }
+
+    pub fn get_current_idx(&self) -> usize {
+        self.idx
+    }
+
+    pub fn page(&self) -> usize {
+        self.idx / ENTRIES_PER_PAGE
+    }
+
+    pub fn nb_pages(&self) -> usize {
+        max((self.entries.len() + ENTRIES_PER_PAGE - 1) / ENTRIES_PER_PAGE, 1)
+    }
+
+    pub fn toggle_favorite(&mut self) {
+        if let Some(entry) = self.entries.get_mut(self.idx) {
+            entry.favorite = !entry.favorite;
+            info!("[Unicorn][Launcher] Favorite {:?} {:?}",
+                  entry.full_filename,
+                  entry.favorite);
+        }
+
+        self.save_favorites();
+        self.sort();
+    }
+
+    /// Favorites first, the selected cartridge stays selected
+    fn sort(&mut self) {
+        let selected = self.current().map(|entry| entry.full_filename.clone());
+
+        self.entries.sort_by_key(|entry| (!entry.favorite, entry.filename.to_lowercase()));
+
+        if let Some(selected) = selected {
+            if let Some(idx) = self.entries.iter().position(|entry| entry.full_filename == selected) {
+                self.idx = idx;
+            }
+        }
+    }
+
+    fn load_favorites(&self) -> Vec<String> {
+        match files::load_json::<FavoritesFile>("Launcher", &self.favorites_filename) {
+            Some(file) => file.favorites,
+            None => Vec::new(),
+        }
+    }
+
+    /// The favorites of the other directories are kept
+    fn save_favorites(&self) {
+        let mut favorites: Vec<String> = self.load_favorites()
+            .into_iter()
+            .filter(|filename| !self.entries.iter().any(|entry| &entry.full_filename == filename))
+            .collect();
+        favorites.extend(self.entries.iter().filter(|entry| entry.favorite).map(|entry| entry.full_filename.clone()));
+
+        files::save_json("Launcher",
+                         &self.favorites_filename,
+                         &FavoritesFile { favorites: favorites });
+    }
+
+    /// Up/down select a cartridge, left/right (or L/R) change the page and X
+    /// toggles the favorite. Starting the cartridge is done by `Unicorn`.
+    pub fn update(&mut self, players: Arc<Mutex<Players>>) -> bool {
+        if self.entries.is_empty() {
+            return true;
+        }
+
+        let last = self.entries.len() - 1;
+        let players = players.lock().unwrap();
+
+        if players.btnp(0, 2) {
+            self.idx = self.idx.saturating_sub(1);
+        } else if players.btnp(0, 3) {
+            self.idx = min(self.idx + 1, last);
+        } else if players.btnp(0, 0) || players.btnp(0, 10) {
+            self.idx = self.idx.saturating_sub(ENTRIES_PER_PAGE);
+        } else if players.btnp(0, 1) || players.btnp(0, 11) {
+            self.idx = min(self.idx + ENTRIES_PER_PAGE, last);
+        } else if players.btnp(0, 8) {
+            self.toggle_favorite();
+        }
+
+        true
+    }
+
+    pub fn draw(&mut self, screen: &mut Screen) {
+        screen.cls(-1);
+
+        let width = screen.width as i32;
+        let height = screen.height as i32;
+
+        screen.print(self.directory.clone(), 2, 2, 7);
+        let pages = format!("{}/{}", self.page() + 1, self.nb_pages());
+        screen.print(pages.clone(), width - 2 - 4 * pages.len() as i32, 2, 7);
+
+        if self.entries.is_empty() {
+            screen.print("No cartridge found".to_string(), 2, TOP + 4, 6);
+        }
+
+        let first = self.page() * ENTRIES_PER_PAGE;
+        for (i, entry) in self.entries.iter().enumerate().skip(first).take(ENTRIES_PER_PAGE) {
+            let y = TOP + (i - first) as i32 * ENTRY_HEIGHT;
+
+            if i == self.idx {
+                screen.rectfill(0, y, width - 1, y + ENTRY_HEIGHT - 2, 1);
+            }
+
+            for (pixel, color) in entry.label.iter().enumerate() {
+                let x = 2 + (pixel % LABEL_SIZE) as i32;
+                let label_y = y + 1 + (pixel / LABEL_SIZE) as i32;
+                screen.pset(x, label_y, *color as i32);
+            }
+
+            let color = if i == self.idx { 7 } else { 6 };
+            screen.print(entry.title.clone(), 40, y + 4, color);
+
+            if !entry.author.is_empty() {
+                screen.print(format!("by {}", entry.author), 40, y + 14, 6);
+            }
+
+            if let Some(ref error) = entry.error {
+                screen.print(error.clone(), 40, y + 24, 8);
+            }
+
+            if entry.favorite {
+                screen.print("*".to_string(), width - 10, y + 4, 10);
+            }
+        }
+
+        screen.print("A: play  X: favorite  LEFT/RIGHT: page".to_string(),
+                     2,
+                     height - 8,
+                     5);
+
+        draw_logo(screen);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    use serde_json;
+
+    use super::*;
+
+    use gfx::Sprite;
+
+    fn test_directory(name: &str, filenames: &[&str]) -> String {
+        let mut dir = env::temp_dir();
+        dir.push(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for filename in filenames {
+            File::create(dir.join(filename)).unwrap().write_all(b"__code__\n").unwrap();
+        }
+
+        fs::canonicalize(&dir).unwrap().to_string_lossy().to_string()
+    }
+
+    fn test_launcher(dir: &str) -> Launcher {
+        let mut launcher = Launcher::new();
+        launcher.favorites_filename = Path::new(dir).join("favorites.json").to_string_lossy().to_string();
+        launcher
+    }
+
+    #[test]
+    fn test_label() {
+        let mut cartridge = Cartridge::empty();
+
+        let mut sprites = vec![Sprite::new([0; 64]); cartridge.config.nb_sprites()];
+        let mut data = [0; 64];
+        data[9] = 7;
+        sprites[1] = Sprite::new(data);
+        // second row of sprites
+        sprites[cartridge.config.sprites_per_row()] = Sprite::new([3; 64]);
+        cartridge.gfx.sprites = sprites;
+
+        let label = label(&cartridge);
+        assert_eq!(label.len(), LABEL_SIZE * LABEL_SIZE);
+        assert_eq!(label[LABEL_SIZE + 8 + 1], 7);
+        assert_eq!(label[8 * LABEL_SIZE], 3);
+        assert_eq!(label[0], 0);
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = test_directory("unicorn-launcher-scan",
+                                 &["e.uni", "D.uc", "c.duc", "b.unb", "a.png", "f.p8", "notes.txt"]);
+
+        let mut launcher = test_launcher(&dir);
+        launcher.scan(&dir);
+
+        let filenames: Vec<&str> = launcher.entries.iter().map(|entry| entry.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["a.png", "b.unb", "c.duc", "D.uc", "e.uni", "f.p8"]);
+        assert_eq!(launcher.current().unwrap().full_filename,
+                   Path::new(&dir).join("a.png").to_string_lossy().to_string());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pages() {
+        let filenames: Vec<String> = (0..ENTRIES_PER_PAGE + 2).map(|i| format!("{}.uni", i)).collect();
+        let filenames: Vec<&str> = filenames.iter().map(|filename| filename.as_str()).collect();
+        let dir = test_directory("unicorn-launcher-pages", &filenames);
+
+        let mut launcher = test_launcher(&dir);
+        assert_eq!(launcher.nb_pages(), 1);
+
+        launcher.scan(&dir);
+        assert_eq!(launcher.nb_pages(), 2);
+        assert_eq!(launcher.page(), 0);
+
+        launcher.idx = ENTRIES_PER_PAGE - 1;
+        assert_eq!(launcher.page(), 0);
+        launcher.idx = ENTRIES_PER_PAGE;
+        assert_eq!(launcher.page(), 1);
+
+        // A new scan starts from the first page
+        launcher.scan(&dir);
+        assert_eq!(launcher.get_current_idx(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_favorites() {
+        let dir = test_directory("unicorn-launcher-favorites", &["a.uni", "b.uni", "c.uni"]);
+
+        let mut launcher = test_launcher(&dir);
+        launcher.scan(&dir);
+
+        launcher.idx = 2;
+        launcher.toggle_favorite();
+
+        // Favorites first, and still selected
+        assert_eq!(launcher.current().unwrap().filename, "c.uni");
+        assert!(launcher.current().unwrap().favorite);
+        assert_eq!(launcher.get_current_idx(), 0);
+
+        // The favorites of the other directories are kept
+        let other = "/elsewhere/other.uni".to_string();
+        let mut favorites = launcher.load_favorites();
+        favorites.push(other.clone());
+        let data = serde_json::to_string(&FavoritesFile { favorites: favorites }).unwrap();
+        File::create(&launcher.favorites_filename).unwrap().write_all(data.as_bytes()).unwrap();
+
+        let mut launcher = test_launcher(&dir);
+        launcher.scan(&dir);
+        let filenames: Vec<&str> = launcher.entries.iter().map(|entry| entry.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["c.uni", "a.uni", "b.uni"]);
+        assert!(launcher.entries[0].favorite);
+        assert!(!launcher.entries[1].favorite);
+
+        launcher.toggle_favorite();
+        assert_eq!(launcher.load_favorites(), vec![other]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
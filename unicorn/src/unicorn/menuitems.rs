@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+/// Number of entries a cartridge can add to the pause menu
+pub const MAX_MENUITEMS: u32 = 5;
+
+/// Entries added by the cartridge to the pause menu with menuitem(), shown
+/// between "Continue" and "Options". The callbacks stay in the plugins, they
+/// are called with the index of the selected entry.
+pub struct MenuItems {
+    items: BTreeMap<u32, String>,
+}
+
+impl MenuItems {
+    pub fn new() -> MenuItems {
+        MenuItems { items: BTreeMap::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Add or replace the entry `index` (1 to MAX_MENUITEMS), an empty label
+    /// removes it.
+    pub fn set(&mut self, index: u32, label: &str) -> bool {
+        if index < 1 || index > MAX_MENUITEMS {
+            warn!("[Unicorn][MenuItems] Invalid index {:?}", index);
+            return false;
+        }
+
+        if label.is_empty() {
+            self.items.remove(&index);
+        } else {
+            self.items.insert(index, label.to_string());
+        }
+
+        true
+    }
+
+    /// Entries sorted by index
+    pub fn items(&self) -> Vec<(u32, String)> {
+        self.items.iter().map(|(index, label)| (*index, label.clone())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menuitems() {
+        let mut menuitems = MenuItems::new();
+
+        assert!(menuitems.set(3, "Toggle music"));
+        assert!(menuitems.set(1, "Restart level"));
+        assert!(!menuitems.set(0, "Invalid"));
+        assert!(!menuitems.set(MAX_MENUITEMS + 1, "Invalid"));
+        assert_eq!(menuitems.items(),
+                   vec![(1, "Restart level".to_string()), (3, "Toggle music".to_string())]);
+
+        assert!(menuitems.set(1, ""));
+        assert_eq!(menuitems.items(), vec![(3, "Toggle music".to_string())]);
+
+        menuitems.clear();
+        assert!(menuitems.items().is_empty());
+    }
+}
@@ -3,11 +3,18 @@ pub mod info;
 pub mod cartdata;
 pub mod emscripten;
 pub mod noise;
+pub mod random;
 pub mod math;
 pub mod packet;
 pub mod wfc;
 pub mod bump;
 pub mod utils;
+pub mod headless;
+pub mod golden;
+pub mod snapshot;
+pub mod movie;
+pub mod launcher;
+pub mod menuitems;
 
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -21,10 +28,13 @@ use image;
 use gif;
 use gif::SetParameter;
 
+use rand;
+
 use std::io::prelude::*;
 use std::time::Duration;
 
 use std::path::Path;
+use std::fs;
 use std::fs::File;
 
 use plugins::lua_plugin::plugin::LuaPlugin;
@@ -32,14 +42,28 @@ use plugins::python_plugin::plugin::PythonPlugin;
 use plugins::javascript_plugin::plugin::JavascriptPlugin;
 
 use config::Players;
+use config::keys::PX8Key;
+use config::bindings::Bindings;
+use config::settings::{Settings, MAX_VOLUME};
 use self::noise::Noise;
+use self::random::Random;
+use self::cartdata::CartData;
+use self::snapshot::Snapshot;
+use self::movie::{Movie, MovieState};
+use self::launcher::Launcher;
+use self::menuitems::MenuItems;
 use gfx;
-use cartridge::{Cartridge, CartridgeFormat};
+use cartridge::{Cartridge, CartridgeConfig, CartridgeFormat};
+use cartridge::png;
 use sound::sound::{Sound, SoundInternal};
 use chiptune::chiptune;
 
 include!(concat!(env!("OUT_DIR"), "/parameters.rs"));
 
+/// Resolution of the screen, unless the cartridge declares its own
+pub const SCREEN_WIDTH: usize = 400;
+pub const SCREEN_HEIGHT: usize = 240;
+
 #[inline]
 pub fn clamp<T: PartialOrd>(val: T, min: T, max: T) -> T {
     if val > min {
@@ -55,50 +79,52 @@ pub fn clamp<T: PartialOrd>(val: T, min: T, max: T) -> T {
     }
 }
 
+/// RGB value of each of the PALETTE_SIZE palette indexes of the screen,
+/// the colors that are not set are black
+#[derive(Clone)]
 pub struct Palette {
-    colors: HashMap<u32, RGB>,
-    rcolors: HashMap<u32, u32>,
-    cached_colors: [u32; 16],
+    colors: Vec<RGB>,
+    defined: Vec<bool>,
 }
 
 impl Palette {
     pub fn new() -> Palette {
         Palette {
-            colors: HashMap::new(),
-            rcolors: HashMap::new(),
-            cached_colors: [0; 16],
+            colors: vec![RGB::new(0, 0, 0); gfx::PALETTE_SIZE],
+            defined: vec![false; gfx::PALETTE_SIZE],
         }
     }
 
-    pub fn get_rgb(&mut self, value: u32) -> RGB {
-        if value < 16 {
-            let v = self.cached_colors[value as usize];
-
-            let r = ((v & 0xff0000) >> 16) as u8;
-            let g = ((v & 0x00ff00) >> 8) as u8;
-            let b = (v & 0x0000ff) as u8;
-
-            return RGB::new(r, g, b);
-        }
+    #[inline]
+    pub fn get_rgb(&self, color: gfx::Color) -> RGB {
+        self.colors[color as usize]
+    }
 
-        match self.colors.get(&value) {
-            Some(rgb_value) => RGB::new(rgb_value.r, rgb_value.g, rgb_value.b),
-            _ => RGB::new(0, 0, 0),
+    /// Convert the palette indexes of `pixels` to 3 bytes per pixel in `buffer`
+    pub fn to_rgb(&self, pixels: &[gfx::Color], buffer: &mut [u8]) {
+        for (pixel, rgb) in pixels.iter().zip(buffer.chunks_mut(3)) {
+            let value = self.colors[*pixel as usize];
+            rgb[0] = value.r;
+            rgb[1] = value.g;
+            rgb[2] = value.b;
         }
     }
 
     pub fn reset(&mut self) {
-        self.colors.clear();
+        for idx in 0..gfx::PALETTE_SIZE {
+            self.colors[idx] = RGB::new(0, 0, 0);
+            self.defined[idx] = false;
+        }
     }
 
     pub fn _set_color(&mut self, color: u32, r: u8, g: u8, b: u8) {
-        let u32_color = (r as u32) << 16 | (g as u32) << 8 | (b as u32);
-
-        self.colors.insert(color, RGB::new(r, g, b));
-        self.rcolors.insert(u32_color, color);
-        if color < 16 {
-            self.cached_colors[color as usize] = u32_color;
+        if color as usize >= gfx::PALETTE_SIZE {
+            warn!("[Unicorn][Palette] Color {:?} out of the palette", color);
+            return;
         }
+
+        self.colors[color as usize] = RGB::new(r, g, b);
+        self.defined[color as usize] = true;
     }
 
     pub fn set_color(&mut self, color: u32, r: u8, g: u8, b: u8) {
@@ -114,13 +140,26 @@ impl Palette {
     }
 
     pub fn get_color(&mut self, color: u32) -> u32 {
-        match self.colors.get(&color) {
+        match self.colors.get(color as usize) {
             Some(rgb_value) => {
                 (rgb_value.r as u32) << 16 | (rgb_value.g as u32) << 8 | (rgb_value.b as u32)
             }
             _ => 0,
         }
     }
+
+    /// Colors that have been set, by palette index
+    pub fn get_colors(&self) -> HashMap<u32, RGB> {
+        let mut colors = HashMap::new();
+
+        for (idx, rgb_value) in self.colors.iter().enumerate() {
+            if self.defined[idx] {
+                colors.insert(idx as u32, *rgb_value);
+            }
+        }
+
+        colors
+    }
 }
 
 lazy_static! {
@@ -129,7 +168,7 @@ lazy_static! {
     };
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct RGB {
     pub r: u8,
     pub g: u8,
@@ -156,12 +195,13 @@ pub trait RustPlugin {
     fn draw(&mut self, screen: &mut gfx::Screen, info: &mut info::Info) -> f64;
 }
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,PartialEq,Clone,Copy)]
 pub enum UnicornState {
     RUN,
     PAUSE,
     EDITOR,
     INTERACTIVE,
+    ERROR,
 }
 
 #[derive(Debug)]
@@ -191,65 +231,295 @@ pub fn array_to_vec(arr: &[u8]) -> Vec<u8> {
 }
 
 
-pub struct Menu {
-    idx: i32,
+/// Keys that can be remapped from the options menu, the pause key is only
+/// available in the bindings file.
+const CONFIG_KEYS: [PX8Key; 12] = [PX8Key::Left,
+                                   PX8Key::Right,
+                                   PX8Key::Up,
+                                   PX8Key::Down,
+                                   PX8Key::A,
+                                   PX8Key::B,
+                                   PX8Key::X,
+                                   PX8Key::Y,
+                                   PX8Key::L,
+                                   PX8Key::R,
+                                   PX8Key::Enter,
+                                   PX8Key::Select];
+
+/// Remapping of the keys of each player, from the "Keys" item of the options menu
+pub struct ConfigMenu {
+    idx: usize,
+    player: u8,
+    waiting: bool,
 }
 
-impl Menu {
-    pub fn new() -> Menu {
-        Menu {
+impl ConfigMenu {
+    pub fn new() -> ConfigMenu {
+        ConfigMenu {
             idx: 0,
+            player: 0,
+            waiting: false,
         }
     }
 
-    pub fn update(&mut self, cartridges: &mut Vec<UnicornCartridge>, players: Arc<Mutex<Players>>) -> bool {
-        if players.lock().unwrap().btnp(0, 2) {
-            self.idx = clamp(self.idx - 1, 0, (cartridges.len() as i32) - 1);
-        } else if players.lock().unwrap().btnp(0, 3) {
-            self.idx = clamp(self.idx + 1, 0, (cartridges.len() as i32) - 1);
+    pub fn reset(&mut self) {
+        self.idx = 0;
+        self.player = 0;
+        self.waiting = false;
+    }
+
+    /// Return false when leaving the menu, the bindings are saved after each change
+    pub fn update(&mut self, players: Arc<Mutex<Players>>) -> bool {
+        let mut plrs = players.lock().unwrap();
+
+        if self.waiting {
+            if let Some(input) = plrs.last_input.take() {
+                info!("[Unicorn][ConfigMenu] Player {:?} {:?} -> {:?}",
+                      self.player,
+                      CONFIG_KEYS[self.idx],
+                      input);
+
+                if plrs.bindings.bind(self.player, CONFIG_KEYS[self.idx], input) {
+                    plrs.bindings.save();
+                }
+                plrs.release_keys();
+
+                self.waiting = false;
+            }
+            return true;
+        }
+
+        let nb_players = max(plrs.bindings.players.len(), 1) as u8;
+
+        if plrs.btnp(0, 4) {
+            self.waiting = true;
+            plrs.last_input = None;
+        } else if plrs.btnp(0, 5) {
+            return false;
+        } else if plrs.btnp(0, 2) {
+            self.idx = if self.idx == 0 { 0 } else { self.idx - 1 };
+        } else if plrs.btnp(0, 3) {
+            self.idx = clamp(self.idx + 1, 0, CONFIG_KEYS.len() - 1);
+        } else if plrs.btnp(0, 0) {
+            self.player = if self.player == 0 { 0 } else { self.player - 1 };
+        } else if plrs.btnp(0, 1) {
+            self.player = clamp(self.player + 1, 0, nb_players - 1);
         }
 
         true
     }
 
-    pub fn get_current_idx(&mut self) -> i32 {
-        self.idx
-    }
+    pub fn draw(&mut self, players: Arc<Mutex<Players>>, screen: &mut gfx::Screen) {
+        let plrs = players.lock().unwrap();
 
-    pub fn draw(&mut self, cartridges: &mut Vec<UnicornCartridge>, screen: &mut gfx::Screen) {
         screen.cls(-1);
 
-        let cartridges_info = format!("{:?}", cartridges.len());
-        screen.print(cartridges_info, 0, 0, 7);
+        screen.print(format!("CONFIG PLAYER {:?}", self.player + 1), 10, 10, 7);
 
-        let offset_x = 2;
-        let offset_y = 10;
+        for (pos, key) in CONFIG_KEYS.iter().enumerate() {
+            let y = 30 + (pos as i32) * 10;
+            let color = if pos == self.idx { 7 } else { 6 };
 
-        if cartridges.len() > 0 {
-            let mut idx : i32 = 0;
-            let min_x = max(self.idx as i32-5, 0) as i32;
-            let max_x = max(self.idx as i32+5, 10) as i32;
-            
-            let mut current_idx = 0;
-            for cartridge in cartridges.iter_mut() {
-                if idx >= min_x && idx <= max_x {
-                    let filename = cartridge.filename.clone();
-
-                    let data_to_print = format!("{:<width$}", filename, width = 10);
-                    let mut color = 6;
-                    if self.idx == idx {
-                        color = 7;
-                    }
-                    screen.print(data_to_print, offset_x, offset_y + current_idx * 8, color);
+            if pos == self.idx {
+                screen.print(">".to_string(), 4, y, 3);
+            }
+
+            screen.print(key.name().to_uppercase(), 10, y, color);
 
-                    current_idx += 1;
+            if self.waiting && pos == self.idx {
+                screen.print("PRESS A KEY OR A BUTTON".to_string(), 60, y, 8);
+            } else {
+                screen.print(plrs.bindings.inputs(self.player, *key).join(" "), 60, y, color);
+            }
+        }
+
+        let height = screen.height as i32;
+        screen.print("LEFT/RIGHT: PLAYER  A: REMAP  B: BACK".to_string(),
+                     10,
+                     height - 12,
+                     5);
+    }
+}
+
+const SCALES: [usize; 8] = [1, 2, 3, 4, 5, 6, 8, 10];
+
+const OPTIONS: [&'static str; 6] = ["VOLUME", "PALETTE", "SCALE", "FULLSCREEN", "FPS OVERLAY", "KEYS"];
+
+fn on_off(value: bool) -> String {
+    if value { "ON".to_string() } else { "OFF".to_string() }
+}
+
+/// Next (or previous) position in a list of `len` values, looping at the ends
+fn cycle(pos: usize, len: usize, forward: bool) -> usize {
+    if forward { (pos + 1) % len } else { (pos + len - 1) % len }
+}
+
+/// Options of the console, from the "Options" item of the pause menu. The
+/// settings are applied by `Unicorn` and saved when leaving the menu.
+pub struct OptionsMenu {
+    idx: usize,
+    keys: bool,
+    config: ConfigMenu,
+    changed: bool,
+    modified: bool,
+}
+
+impl OptionsMenu {
+    pub fn new() -> OptionsMenu {
+        OptionsMenu {
+            idx: 0,
+            keys: false,
+            config: ConfigMenu::new(),
+            changed: false,
+            modified: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.idx = 0;
+        self.keys = false;
+        self.changed = false;
+        self.config.reset();
+    }
+
+    /// True once after a change of the settings
+    pub fn take_modified(&mut self) -> bool {
+        let modified = self.modified;
+        self.modified = false;
+        modified
+    }
+
+    /// Return false when leaving the menu, the settings are saved if they changed
+    pub fn update(&mut self,
+                  players: Arc<Mutex<Players>>,
+                  settings: Arc<Mutex<Settings>>,
+                  palettes: Arc<Mutex<Palettes>>)
+                  -> bool {
+        if self.keys {
+            if !self.config.update(players.clone()) {
+                self.keys = false;
+                self.config.reset();
+            }
+            return true;
+        }
+
+        let plrs = players.lock().unwrap();
+        let mut settings = settings.lock().unwrap();
+
+        if plrs.btnp(0, 5) {
+            if self.changed {
+                settings.save();
+                self.changed = false;
+            }
+            return false;
+        } else if plrs.btnp(0, 2) {
+            self.idx = if self.idx == 0 { 0 } else { self.idx - 1 };
+            return true;
+        } else if plrs.btnp(0, 3) {
+            self.idx = clamp(self.idx + 1, 0, OPTIONS.len() - 1);
+            return true;
+        }
+
+        let left = plrs.btnp(0, 0);
+        let right = plrs.btnp(0, 1) || plrs.btnp(0, 4);
+        if !left && !right {
+            return true;
+        }
+
+        match self.idx {
+            0 => {
+                let step = if right { MAX_VOLUME / 8 } else { -MAX_VOLUME / 8 };
+                settings.volume = clamp(settings.volume + step, 0, MAX_VOLUME);
+            }
+            1 => {
+                // The empty name keeps the palette of the cartridge
+                let mut names = vec!["".to_string()];
+                names.extend(palettes.lock().unwrap().palettes_list.iter().cloned());
+
+                let pos = names.iter().position(|name| *name == settings.palette).unwrap_or(0);
+                settings.palette = names[cycle(pos, names.len(), right)].clone();
+            }
+            2 => {
+                let pos = match settings.scale {
+                    Some(factor) => SCALES.iter().position(|scale| *scale == factor).unwrap_or(0),
+                    None => 0,
+                };
+                settings.scale = Some(SCALES[cycle(pos, SCALES.len(), right)]);
+            }
+            3 => {
+                settings.fullscreen = Some(!settings.fullscreen.unwrap_or(false));
+            }
+            4 => {
+                settings.info_overlay = !settings.info_overlay;
+            }
+            _ => {
+                if plrs.btnp(0, 4) {
+                    self.keys = true;
                 }
-                idx += 1;
+                return true;
             }
+        }
 
+        info!("[Unicorn][OptionsMenu] {:?} changed", OPTIONS[self.idx]);
 
-            draw_logo(screen);
+        self.changed = true;
+        self.modified = true;
+
+        true
+    }
+
+    pub fn draw(&mut self,
+                players: Arc<Mutex<Players>>,
+                settings: Arc<Mutex<Settings>>,
+                screen: &mut gfx::Screen) {
+        if self.keys {
+            self.config.draw(players, screen);
+            return;
+        }
+
+        let settings = settings.lock().unwrap();
+
+        screen.cls(-1);
+
+        screen.print("OPTIONS".to_string(), 10, 10, 7);
+
+        for (pos, name) in OPTIONS.iter().enumerate() {
+            let y = 30 + (pos as i32) * 10;
+            let color = if pos == self.idx { 7 } else { 6 };
+
+            if pos == self.idx {
+                screen.print(">".to_string(), 4, y, 3);
+            }
+
+            screen.print(name.to_string(), 10, y, color);
+
+            let value = match pos {
+                0 => format!("{}%", settings.volume * 100 / MAX_VOLUME),
+                1 => {
+                    if settings.palette.is_empty() {
+                        "CARTRIDGE".to_string()
+                    } else {
+                        settings.palette.to_uppercase()
+                    }
+                }
+                2 => {
+                    match settings.scale {
+                        Some(factor) => format!("{}X", factor),
+                        None => "DEFAULT".to_string(),
+                    }
+                }
+                3 => on_off(settings.fullscreen.unwrap_or(false)),
+                4 => on_off(settings.info_overlay),
+                _ => "".to_string(),
+            };
+            screen.print(value, 70, y, color);
         }
+
+        let height = screen.height as i32;
+        screen.print("LEFT/RIGHT: CHANGE  A: KEYS  B: BACK".to_string(),
+                     10,
+                     height - 12,
+                     5);
     }
 }
 
@@ -257,22 +527,46 @@ pub struct PauseMenu {
     idx: i32,
     selected_idx: i32,
     items: Vec<String>,
+    options: OptionsMenu,
+    // indexes of the entries added by the cartridge, after "Continue"
+    menuitems: Vec<u32>,
+    menuitem: Option<u32>,
 }
 
 impl PauseMenu {
     pub fn new() -> PauseMenu {
+        let mut menu = PauseMenu {
+            idx: 0,
+            selected_idx: -1,
+            items: Vec::new(),
+            options: OptionsMenu::new(),
+            menuitems: Vec::new(),
+            menuitem: None,
+        };
+        menu.set_menuitems(Vec::new());
+
+        menu
+    }
+
+    /// Entries of the cartridge (index, label), between "Continue" and "Options"
+    pub fn set_menuitems(&mut self, menuitems: Vec<(u32, String)>) {
         let mut items = Vec::new();
 
         items.push("Continue".to_string());
-        items.push("Config".to_string());
+        for &(_, ref label) in &menuitems {
+            items.push(label.clone());
+        }
+        items.push("Options".to_string());
         items.push("Menu".to_string());
         items.push("Exit".to_string());
 
-        PauseMenu {
-            idx: 0,
-            selected_idx: -1,
-            items: items.clone(),
-        }
+        self.idx = clamp(self.idx, 0, (items.len() as i32) - 1);
+        self.items = items;
+        self.menuitems = menuitems.iter().map(|&(index, _)| index).collect();
+    }
+
+    fn options_idx(&self) -> i32 {
+        1 + self.menuitems.len() as i32
     }
 
     pub fn reset(&mut self) {
@@ -280,6 +574,8 @@ impl PauseMenu {
 
         self.selected_idx = -1;
         self.idx = 0;
+        self.menuitem = None;
+        self.options.reset();
     }
 
     pub fn stop(&mut self) -> bool {
@@ -291,13 +587,40 @@ impl PauseMenu {
         self.selected_idx == self.items.len() as i32 - 2
     }
 
-    pub fn update(&mut self, players: Arc<Mutex<Players>>) -> bool {
+    /// True once after a change of the settings in the options
+    pub fn take_modified(&mut self) -> bool {
+        self.options.take_modified()
+    }
+
+    /// Index of the entry of the cartridge chosen, its callback has to be called
+    pub fn take_menuitem(&mut self) -> Option<u32> {
+        self.menuitem.take()
+    }
+
+    pub fn update(&mut self,
+                  players: Arc<Mutex<Players>>,
+                  settings: Arc<Mutex<Settings>>,
+                  palettes: Arc<Mutex<Palettes>>)
+                  -> bool {
+        if self.selected_idx == self.options_idx() {
+            if !self.options.update(players.clone(), settings, palettes) {
+                self.selected_idx = -1;
+                self.options.reset();
+            }
+            return true;
+        }
+
         let plrs = players.lock().unwrap();
         if plrs.btnp(0, 4) {
             self.selected_idx = self.idx as i32;
             if self.selected_idx == (self.items.len() as i32) - 1 {
                 return false;
             }
+
+            if self.selected_idx > 0 && self.selected_idx < self.options_idx() {
+                self.menuitem = Some(self.menuitems[self.selected_idx as usize - 1]);
+                self.selected_idx = -1;
+            }
         } else {
             if plrs.btnp(0, 2) {
                 self.idx = clamp(self.idx - 1, 0, (self.items.len() as i32) - 1);
@@ -311,21 +634,28 @@ impl PauseMenu {
         true
     }
 
-    pub fn draw(&mut self, screen: &mut gfx::Screen) {
+    pub fn draw(&mut self,
+                players: Arc<Mutex<Players>>,
+                settings: Arc<Mutex<Settings>>,
+                screen: &mut gfx::Screen) {
         if self.selected_idx == -1 {
-            let idx_x = (screen.width / 2 - 20) as i32;
+            // The labels of the cartridge can be longer than the system ones
+            let longest = self.items.iter().map(|item| item.len()).max().unwrap_or(0) as i32;
+            let width = max(40, longest * 4 + 8);
+
+            let idx_x = (screen.width / 2) as i32 - width / 2;
             let idx_y = (screen.height / 2 - 10) as i32;
 
             screen.rectfill(idx_x,
                             idx_y - 5,
-                            idx_x + 40,
+                            idx_x + width,
                             idx_y + 10 * self.items.len() as i32,
                             11);
 
 
             screen.rect(idx_x - 1,
                         idx_y - 6,
-                        idx_x + 41,
+                        idx_x + width + 1,
                         idx_y + 1 + 10 * self.items.len() as i32,
                         0);
 
@@ -339,8 +669,8 @@ impl PauseMenu {
 
         }
 
-        if self.selected_idx == 1 {
-            screen.cls(-1);
+        if self.selected_idx == self.options_idx() {
+            self.options.draw(players, settings, screen);
         }
     }
 }
@@ -526,7 +856,7 @@ impl Palettes {
     pub fn get_colors(&mut self) -> HashMap<u32, RGB> {
         let mut colors = HashMap::new();
 
-        for (key, value) in PALETTE.lock().unwrap().colors.clone() {
+        for (key, value) in PALETTE.lock().unwrap().get_colors() {
             if key >= 16 {
                 colors.insert(key, value);
             }
@@ -597,7 +927,7 @@ impl UnicornCartridge {
             filename: filename.clone(),
             full_filename: cartridge.filename.clone(),
             loaded: true,
-            font_name: "pico-8".to_string(),
+            font_name: cartridge.config.font.clone(),
             cartridge: cartridge,
             lua_plugin: LuaPlugin::new(),
             python_plugin: PythonPlugin::new(),
@@ -656,24 +986,34 @@ pub struct Unicorn {
     pub palettes: Arc<Mutex<Palettes>>,
     pub players: Arc<Mutex<Players>>,
     pub configuration: Arc<Mutex<UnicornConfig>>,
+    pub settings: Arc<Mutex<Settings>>,
     pub noise: Arc<Mutex<Noise>>,
+    pub random: Arc<Mutex<Random>>,
+    pub cartdata: Arc<Mutex<CartData>>,
+    pub menuitems: Arc<Mutex<MenuItems>>,
+    pub snapshots: HashMap<u32, Snapshot>,
     pub cartridges: Vec<UnicornCartridge>,
     pub editor: edit::edit::Editor,
     pub editing: bool,
-    pub menu: Menu,
+    pub launcher: Launcher,
     pub current_cartridge: usize,
     pub current_code_type: Code,
     pub interactive: bool,
     pub state: UnicornState,
+    /// State to go back to when leaving the pause menu
+    pub paused_state: UnicornState,
     pub pause_menu: PauseMenu,
     pub fps: f64,
     pub record: Record,
+    pub movie: MovieState,
     pub draw_return: bool,
     pub update_return: bool,
     pub mouse_spr: Vec<u8>,
     pub version: u32,
     pub major_version: u32,
     pub minor_version: u32,
+    /// Why the last cartridge failed to load, shown in the ERROR state
+    pub error: Option<String>,
 }
 
 impl Unicorn {
@@ -682,7 +1022,7 @@ impl Unicorn {
 
         let sound_internal = Arc::new(Mutex::new(SoundInternal::new()));
         let csend = sound_internal.lock().unwrap().csend.clone();
-        let screen = Arc::new(Mutex::new(gfx::Screen::new(400, 240)));
+        let screen = Arc::new(Mutex::new(gfx::Screen::new(SCREEN_WIDTH, SCREEN_HEIGHT)));
 
         Unicorn {
             screen: screen.clone(),
@@ -692,7 +1032,12 @@ impl Unicorn {
             palettes: Arc::new(Mutex::new(Palettes::new())),
             players: Arc::new(Mutex::new(Players::new())),
             configuration: Arc::new(Mutex::new(UnicornConfig::new())),
+            settings: Arc::new(Mutex::new(Settings::new())),
             noise: Arc::new(Mutex::new(Noise::new())),
+            random: Arc::new(Mutex::new(Random::new())),
+            cartdata: Arc::new(Mutex::new(CartData::new())),
+            menuitems: Arc::new(Mutex::new(MenuItems::new())),
+            snapshots: HashMap::new(),
             cartridges: Vec::new(),
             editor: edit::edit::Editor::new(screen.clone()),
             editing: false,
@@ -700,16 +1045,19 @@ impl Unicorn {
             current_code_type: Code::UNKNOWN,
             interactive: false,
             state: UnicornState::RUN,
+            paused_state: UnicornState::RUN,
             pause_menu: PauseMenu::new(),
-            menu: Menu::new(),
+            launcher: Launcher::new(),
             fps: 0.0,
             record: Record::new(),
+            movie: MovieState::new(),
             draw_return: true,
             update_return: true,
             mouse_spr: Unicorn::mouse_sprite(),
             version: VERSION,
             major_version: MAJOR_VERSION,
             minor_version: MINOR_VERSION,
+            error: None,
         }
     }
 
@@ -723,12 +1071,63 @@ impl Unicorn {
     }
 
     pub fn update_sound(&mut self) {
-        let mut cartridge = self.cartridges.get_mut(self.current_cartridge).unwrap();
-        self.sound_internal.lock().unwrap().update(&mut cartridge, self.sound.clone());
+        if let Some(mut cartridge) = self.cartridges.get_mut(self.current_cartridge) {
+            self.sound_internal.lock().unwrap().update(&mut cartridge, self.sound.clone());
+        }
     }
 
     pub fn stop(&mut self) {
+        self.stop_movie();
         self.sound_internal.lock().unwrap().stop();
+        self.cartdata.lock().unwrap().flush();
+    }
+
+    /// Use the keyboard and controller mapping saved in `filename`, the
+    /// changes made in the options menu are saved in the same file.
+    pub fn load_bindings(&mut self, filename: &str) {
+        self.players.lock().unwrap().bindings = Bindings::load(filename);
+    }
+
+    /// Use the options saved in `filename`, the changes made in the options
+    /// menu are saved in the same file.
+    pub fn load_settings(&mut self, filename: &str) {
+        *self.settings.lock().unwrap() = Settings::load(filename);
+        self._apply_settings();
+    }
+
+    /// Scale and fullscreen of the frontend when they are not in the settings
+    pub fn init_display(&mut self, scale: usize, fullscreen: bool) {
+        let mut settings = self.settings.lock().unwrap();
+
+        if settings.scale.is_none() {
+            settings.scale = Some(scale);
+        }
+        if settings.fullscreen.is_none() {
+            settings.fullscreen = Some(fullscreen);
+        }
+    }
+
+    /// Scale and fullscreen given on the command line, they replace the
+    /// settings
+    pub fn force_display(&mut self, scale: Option<usize>, fullscreen: Option<bool>) {
+        let mut settings = self.settings.lock().unwrap();
+
+        if scale.is_some() {
+            settings.scale = scale;
+        }
+        if fullscreen.is_some() {
+            settings.fullscreen = fullscreen;
+        }
+    }
+
+    /// Scale and fullscreen chosen in the options, polled by the frontend
+    pub fn get_display(&self) -> (Option<usize>, Option<bool>) {
+        let settings = self.settings.lock().unwrap();
+        (settings.scale, settings.fullscreen)
+    }
+
+    pub fn set_save_directory(&mut self, directory: &str) {
+        self.cartdata.lock().unwrap().set_directory(directory);
     }
 
     pub fn toggle_debug(&mut self) {
@@ -761,8 +1160,12 @@ impl Unicorn {
         self.draw_return = true;
     }
 
-    pub fn init_interactive(&mut self) {
+    /// Start with the launcher, listing the cartridges of `directory`
+    pub fn init_interactive(&mut self, directory: &str) {
         self.interactive = true;
+        self.launcher.scan(directory);
+        self.state = UnicornState::INTERACTIVE;
+        self._reset_screen();
     }
 
     pub fn debug_draw(&mut self) {
@@ -790,6 +1193,8 @@ impl Unicorn {
     }
 
     pub fn update_time(&mut self, dt: Duration) {
+        let dt = self.movie.frame_time(dt);
+
         self.info.lock().unwrap().update(dt);
 
         self.players
@@ -808,18 +1213,41 @@ impl Unicorn {
     }
 
     pub fn update(&mut self) -> bool {
+        {
+            let running = self.state == UnicornState::RUN;
+            let elapsed = self.info.lock().unwrap().elapsed_time;
+            self.movie.update(&mut self.players.lock().unwrap(), elapsed, running);
+        }
+
         match self.state {
             UnicornState::PAUSE => {
+                let menuitems = self.menuitems.lock().unwrap().items();
+                self.pause_menu.set_menuitems(menuitems);
+
                 if self.pause_menu.stop() {
-                    self.state = UnicornState::RUN;
+                    self.state = self.paused_state;
                 }
 
                 if self.pause_menu.quit() {
                     self.state = UnicornState::INTERACTIVE;
+                    self._reset_screen();
                     self.sound_internal.lock().unwrap().stop();
+                    self.cartdata.lock().unwrap().close();
+                    self.menuitems.lock().unwrap().clear();
                 }
 
-                return self.pause_menu.update(self.players.clone());
+                let return_value = self.pause_menu
+                    .update(self.players.clone(), self.settings.clone(), self.palettes.clone());
+                if self.pause_menu.take_modified() {
+                    self._apply_settings();
+                }
+
+                if let Some(index) = self.pause_menu.take_menuitem() {
+                    self.switch_pause();
+                    self.call_menuitem(index);
+                }
+
+                return return_value;
             }
             UnicornState::RUN => {
                 if self.is_end() {
@@ -829,15 +1257,29 @@ impl Unicorn {
                 self.call_update();
             }
             UnicornState::INTERACTIVE => {
-                let return_value = self.menu.update(&mut self.cartridges, self.players.clone());
+                let return_value = self.launcher.update(self.players.clone());
                 if self.players.lock().unwrap().btnp(0, 4) {
-                    let filename = self.cartridges[self.menu.get_current_idx() as usize].filename.clone();
-                    let full_filename = self.cartridges[self.menu.get_current_idx() as usize].full_filename.clone();
-                    self.load_cartridge(filename.as_str(), full_filename.as_str(), false);
+                    let selected = self.launcher
+                        .current()
+                        .map(|entry| (entry.filename.clone(), entry.full_filename.clone()));
+                    if let Some((filename, full_filename)) = selected {
+                        self.load_cartridge(filename.as_str(), full_filename.as_str(), false);
+                    }
                 }
 
                 return return_value;
             }
+            UnicornState::ERROR => {
+                let back = {
+                    let plrs = self.players.lock().unwrap();
+                    plrs.btnp(0, 4) || plrs.btnp(0, 5)
+                };
+                if self.interactive && back {
+                    self.error = None;
+                    self.state = UnicornState::INTERACTIVE;
+                    self._reset_screen();
+                }
+            }
             UnicornState::EDITOR => {
                 #[cfg(feature = "editor")]
                 {
@@ -852,13 +1294,18 @@ impl Unicorn {
     pub fn draw(&mut self) {
         match self.state {
             UnicornState::PAUSE => {
-                self.pause_menu.draw(&mut self.screen.lock().unwrap());
+                self.pause_menu.draw(self.players.clone(),
+                                     self.settings.clone(),
+                                     &mut self.screen.lock().unwrap());
             }
             UnicornState::RUN => {
                 self.call_draw();
             }
             UnicornState::INTERACTIVE => {
-                self.menu.draw(&mut self.cartridges, &mut self.screen.lock().unwrap());
+                self.launcher.draw(&mut self.screen.lock().unwrap());
+            }
+            UnicornState::ERROR => {
+                self.draw_error();
             }
             UnicornState::EDITOR => {
                 #[cfg(feature = "editor")]
@@ -880,7 +1327,7 @@ impl Unicorn {
                         self.screen
                             .lock()
                             .unwrap()
-                            .putpixel_direct(mouse_x + x as i32, mouse_y + y as i32, pixel as u32);
+                            .putpixel_direct(mouse_x + x as i32, mouse_y + y as i32, pixel);
                     }
                 }
             }
@@ -893,6 +1340,11 @@ impl Unicorn {
         self.debug_draw();
     }
 
+    /// Frame rate wanted by the running cartridge
+    pub fn target_fps(&self) -> u32 {
+        self.info.lock().unwrap().config.fps
+    }
+
     pub fn is_end(&self) -> bool {
         !self.update_return
     }
@@ -915,11 +1367,11 @@ impl Unicorn {
         if self.record.nb % 4 == 0 {
             let mut buffer: Vec<u8> = Vec::new();
             let screen = &mut self.screen.lock().unwrap();
+            let palette = PALETTE.lock().unwrap();
 
             for x in 0..screen.width {
                 for y in 0..screen.height {
-                    let value = screen.pget(x as u32, y as u32);
-                    let rgb_value = PALETTE.lock().unwrap().get_rgb(value);
+                    let rgb_value = palette.get_rgb(screen.frame_buffer[x + y * screen.width]);
 
                     buffer.push(rgb_value.r);
                     buffer.push(rgb_value.g);
@@ -1008,11 +1460,12 @@ impl Unicorn {
 
         let mut buffer: Vec<u8> = vec![0; (screen.width*screen.height) * 3];
 
+        let palette = PALETTE.lock().unwrap();
+
         let mut idx = 0;
         for x in 0..screen.width {
             for y in 0..screen.height {
-                let value = screen.pget(x as u32, y as u32);
-                let rgb_value = PALETTE.lock().unwrap().get_rgb(value);
+                let rgb_value = palette.get_rgb(screen.frame_buffer[x + y * screen.width]);
 
                 buffer[idx] = rgb_value.r;
                 buffer[idx + 1] = rgb_value.g;
@@ -1032,18 +1485,42 @@ impl Unicorn {
         image.save(&mut output, image::ImageFormat::PNG).unwrap();
     }
 
-    pub fn save_current_cartridge(&mut self) {
-        if !self.editing {
-            return;
+    /// Current screen as a label for the PNG cartridges
+    pub fn label(&mut self) -> png::Label {
+        let screen = &mut self.screen.lock().unwrap();
+
+        let mut data = vec![0; screen.width * screen.height * 3];
+        PALETTE.lock().unwrap().to_rgb(&screen.frame_buffer, &mut data);
+
+        png::Label::new(screen.width as u32, screen.height as u32, data)
+    }
+
+    /// Save the current cartridge as a PNG image, with the current screen as label.
+    pub fn save_png_cartridge(&mut self, filename: &str) -> bool {
+        if self.cartridges.is_empty() {
+            return false;
         }
 
-        let screen = &self.screen.lock().unwrap();
+        info!("[Unicorn] Saving the current cartridge as PNG in {:?}", filename);
+
+        let label = self.label();
+        self.update_cartridge();
 
         let cartridge = &mut self.cartridges[self.current_cartridge].cartridge;
+        match cartridge.save_in_png(filename, Some(label)) {
+            Ok(_) => true,
+            Err(e) => {
+                error!("[Unicorn] Impossible to save the PNG cartridge {:?}: {:?}", filename, e);
+                false
+            }
+        }
+    }
 
-        let output_filename = &cartridge.filename.clone();
-        info!("[Unicorn][SAVE] Saving the current cartridge in {:?}",
-              output_filename);
+    /// Copy the sprites, map, flags and palette of the console in the current cartridge
+    fn update_cartridge(&mut self) {
+        let screen = &self.screen.lock().unwrap();
+
+        let cartridge = &mut self.cartridges[self.current_cartridge].cartridge;
 
         info!("[Unicorn][SAVE] Set the new sprites");
         cartridge.gfx.set_sprites(screen.sprites.clone());
@@ -1053,6 +1530,20 @@ impl Unicorn {
         cartridge.gff.set_flags(screen.sprites.clone());
         info!("[Unicorn][SAVE] Set the new palette");
         cartridge.palette.set_colors(self.palettes.lock().unwrap().get_colors());
+    }
+
+    pub fn save_current_cartridge(&mut self) {
+        if !self.editing {
+            return;
+        }
+
+        self.update_cartridge();
+
+        let cartridge = &mut self.cartridges[self.current_cartridge].cartridge;
+
+        let output_filename = &cartridge.filename.clone();
+        info!("[Unicorn][SAVE] Saving the current cartridge in {:?}",
+              output_filename);
 
         match cartridge.format {
             CartridgeFormat::UnicornFormat => {
@@ -1066,33 +1557,215 @@ impl Unicorn {
             CartridgeFormat::UnicornSplittedFormat => {
                 cartridge.save_in_unicorn_splitted();
             }
-            _ => (),
+            CartridgeFormat::UnicornBinaryFormat => {
+                if let Err(e) = cartridge.save_in_binary(output_filename) {
+                    error!("[Unicorn][SAVE] Impossible to save {:?}: {:?}", output_filename, e);
+                }
+            }
+            CartridgeFormat::UnicornPngFormat => {
+                if let Err(e) = cartridge.save_in_png(output_filename, None) {
+                    error!("[Unicorn][SAVE] Impossible to save {:?}: {:?}", output_filename, e);
+                }
+            }
+            CartridgeFormat::Pico8Format => {
+                error!("[Unicorn][SAVE] PICO-8 cartridges are imported only, convert {:?} to save it",
+                       output_filename);
+            }
         }
     }
 
-    pub fn save_state() {
+    pub fn is_recording_movie(&self) -> bool {
+        self.movie.recording
+    }
 
+    pub fn is_replaying_movie(&self) -> bool {
+        self.movie.replaying
     }
 
-    pub fn restore_state() {
+    /// Restart the current cartridge and record all its inputs in `filename`
+    pub fn start_movie(&mut self, filename: &str) -> bool {
+        if self.state != UnicornState::RUN || self.cartridges.is_empty() || self.movie.replaying {
+            info!("[Unicorn][MOVIE] A movie can only be recorded when a cartridge is running");
+            return false;
+        }
+
+        let name = self.cartridges[self.current_cartridge].filename.clone();
+        let full_filename = self.cartridges[self.current_cartridge].full_filename.clone();
+
+        // Absolute path, to replay the movie from any directory
+        let full_filename = match fs::canonicalize(&full_filename) {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(_) => {
+                info!("[Unicorn][MOVIE] {:?} is not a file, it can't be replayed", full_filename);
+                return false;
+            }
+        };
+
+        if full_filename.contains('\n') || full_filename.contains('\r') {
+            info!("[Unicorn][MOVIE] {:?} can't be saved in a movie", full_filename);
+            return false;
+        }
+
+        let seed = rand::random::<u32>();
+        if !self.load_movie_cartridge(&name, &full_filename, seed) {
+            return false;
+        }
 
+        self.movie.start_record(filename,
+                                Movie::new(&full_filename, seed),
+                                &mut self.players.lock().unwrap());
+        true
+    }
+
+    /// Load the cartridge of the movie and feed it the recorded inputs,
+    /// the inputs of the frontend are ignored until the end of the movie.
+    pub fn replay_movie(&mut self, filename: &str) -> bool {
+        self.stop_movie();
+
+        let movie = match Movie::from_file(filename) {
+            Ok(movie) => movie,
+            Err(e) => {
+                error!("[Unicorn][MOVIE] {}", e);
+                return false;
+            }
+        };
+
+        let cartridge = movie.cartridge.clone();
+        if !self.load_movie_cartridge(&cartridge, &cartridge, movie.seed) {
+            return false;
+        }
+
+        self.movie.start_replay(filename, movie, &mut self.players.lock().unwrap());
+        true
+    }
+
+    pub fn stop_movie(&mut self) {
+        if let Err(e) = self.movie.stop(&mut self.players.lock().unwrap()) {
+            error!("[Unicorn][MOVIE] Impossible to save {:?}: {:?}", self.movie.filename, e);
+        }
+    }
+
+    /// Seed of the random generators (noise and rnd) of the cartridges
+    pub fn set_seed(&mut self, seed: u32) {
+        self.noise.lock().unwrap().set_seed(seed);
+        self.random.lock().unwrap().set_seed(seed);
+    }
+
+    /// A movie starts from a fresh cartridge, with the same seed and no key pressed
+    fn load_movie_cartridge(&mut self, filename: &str, full_filename: &str, seed: u32) -> bool {
+        self.set_seed(seed);
+
+        {
+            let players = &mut self.players.lock().unwrap();
+            players.set_replaying(false);
+            players.release_keys();
+        }
+
+        self.load_cartridge(filename, full_filename, false)
+    }
+
+    pub fn save_state(&mut self, slot: u32) -> bool {
+        if self.state != UnicornState::RUN || self.cartridges.is_empty() {
+            info!("[Unicorn] Save state is only available when a cartridge is running");
+            return false;
+        }
+
+        info!("[Unicorn] Save state in slot {:?}", slot);
+
+        let idx = self.current_cartridge;
+        let script = match self.current_code_type {
+            Code::LUA => self.cartridges[idx].lua_plugin.save_state(),
+            _ => {
+                info!("[Unicorn] Globals of {:?} cartridges are not saved", self.current_code_type);
+                None
+            }
+        };
+
+        let snapshot = Snapshot {
+            filename: self.cartridges[idx].filename.clone(),
+            screen: self.screen.lock().unwrap().save_state(),
+            palette: PALETTE.lock().unwrap().clone(),
+            palette_name: self.palettes.lock().unwrap().get_name(),
+            players: self.players.lock().unwrap().save_state(),
+            info: self.info.lock().unwrap().clone(),
+            random: self.random.lock().unwrap().clone(),
+            script: script,
+        };
+
+        self.snapshots.insert(slot, snapshot);
+
+        true
+    }
+
+    pub fn restore_state(&mut self, slot: u32) -> bool {
+        if self.state != UnicornState::RUN || self.cartridges.is_empty() {
+            info!("[Unicorn] Restore state is only available when a cartridge is running");
+            return false;
+        }
+
+        let idx = self.current_cartridge;
+
+        let snapshot = match self.snapshots.get(&slot) {
+            Some(snapshot) => snapshot,
+            None => {
+                info!("[Unicorn] No state in slot {:?}", slot);
+                return false;
+            }
+        };
+
+        if snapshot.filename != self.cartridges[idx].filename {
+            info!("[Unicorn] State in slot {:?} belongs to {:?}", slot, snapshot.filename);
+            return false;
+        }
+
+        info!("[Unicorn] Restore state from slot {:?}", slot);
+
+        if self.movie.recording || self.movie.replaying {
+            info!("[Unicorn][MOVIE] The movie can't follow a restored state, stop it");
+            if let Err(e) = self.movie.stop(&mut self.players.lock().unwrap()) {
+                error!("[Unicorn][MOVIE] Impossible to save {:?}: {:?}", self.movie.filename, e);
+            }
+        }
+
+        self.screen.lock().unwrap().restore_state(&snapshot.screen);
+        *PALETTE.lock().unwrap() = snapshot.palette.clone();
+        self.palettes.lock().unwrap().name = snapshot.palette_name.clone();
+        self.players.lock().unwrap().restore_state(&snapshot.players);
+        *self.info.lock().unwrap() = snapshot.info.clone();
+        *self.random.lock().unwrap() = snapshot.random.clone();
+
+        if let Some(ref data) = snapshot.script {
+            match self.current_code_type {
+                Code::LUA => {
+                    self.cartridges[idx].lua_plugin.restore_state(data.clone());
+                }
+                _ => (),
+            }
+        }
+
+        true
     }
 
     pub fn switch_pause(&mut self) {
         info!("[Unicorn] Switch pause");
 
+        // The overlay can also be toggled with a key of the frontend
+        self.settings.lock().unwrap().info_overlay = self.configuration.lock().unwrap().show_info_overlay;
+
         let screen = &mut self.screen.lock().unwrap();
 
         match self.state {
             UnicornState::PAUSE => {
-                if self.editing {
-                    self.state = UnicornState::EDITOR;
-                } else {
-                    self.state = UnicornState::RUN;
-                }
+                self.state = self.paused_state;
+
                 /* Restore previous state */
                 screen.restore();
-                screen.font(&self.cartridges[self.current_cartridge].font_name.clone());
+                if self.state == UnicornState::RUN {
+                    // The launcher can be paused before any cartridge is loaded
+                    if let Some(cartridge) = self.cartridges.get(self.current_cartridge) {
+                        screen.font(&cartridge.font_name);
+                    }
+                }
                 self.sound_internal.lock().unwrap().resume();
             }
             UnicornState::RUN => {
@@ -1100,24 +1773,29 @@ impl Unicorn {
                 screen.save();
                 self.cartridges[self.current_cartridge].font_name = screen.get_font();
                 self.sound_internal.lock().unwrap().pause();
+                self.cartdata.lock().unwrap().flush();
 
                 screen.font("pico-8");
 
                 self.pause_menu.reset();
+                self.paused_state = UnicornState::RUN;
                 self.state = UnicornState::PAUSE;
             }
             UnicornState::INTERACTIVE => {
                 self.pause_menu.reset();
+                self.paused_state = UnicornState::INTERACTIVE;
                 self.state = UnicornState::PAUSE;
                 screen.save();
                 self.sound_internal.lock().unwrap().pause();
             }
             UnicornState::EDITOR => {
                 self.pause_menu.reset();
+                self.paused_state = UnicornState::EDITOR;
                 self.state = UnicornState::PAUSE;
                 screen.save();
                 self.sound_internal.lock().unwrap().stop();
             }
+            UnicornState::ERROR => (),
         }
         info!("[Unicorn] End Switch pause");
     }
@@ -1136,6 +1814,50 @@ impl Unicorn {
 
         info!("[Unicorn] Setup screen {:?}", cartridge);
 
+        let config = cartridge.cartridge.config.clone();
+        self.screen
+            .lock()
+            .unwrap()
+            .resize(config.width, config.height);
+
+        {
+            let mut screen = self.screen.lock().unwrap();
+
+            screen.clear_fonts();
+            for lines in &cartridge.cartridge.font.fonts {
+                match gfx::BitmapFont::from_bdf(lines) {
+                    Ok(font) => screen.add_font(font),
+                    Err(e) => warn!("[Unicorn] Invalid font in the cartridge: {}", e),
+                }
+            }
+            for &(ref name, ref data) in &cartridge.cartridge.ttf.fonts {
+                match gfx::TtfFont::from_bytes(name, data.clone()) {
+                    Ok(font) => screen.add_ttf(font),
+                    Err(e) => warn!("[Unicorn] {}", e),
+                }
+            }
+
+            screen.font(&config.font);
+        }
+
+        // The palette chosen in the options replaces the one of the cartridge
+        let palette = {
+            let settings = self.settings.lock().unwrap();
+            if settings.palette.is_empty() {
+                config.palette.clone()
+            } else {
+                settings.palette.clone()
+            }
+        };
+
+        if self.palettes.lock().unwrap().palettes.contains_key(&palette) {
+            self.palettes.lock().unwrap().switch_to_palette(&palette);
+        } else {
+            warn!("[Unicorn] Unknown palette {:?}", palette);
+        }
+
+        self.info.lock().unwrap().set_config(config);
+
         self.screen
             .lock()
             .unwrap()
@@ -1154,6 +1876,33 @@ impl Unicorn {
         self.palettes.lock().unwrap().set_colors(cartridge.cartridge.palette.colors.clone());
     }
 
+    /// Back to the settings of the console, for the cartridges menu
+    pub fn _reset_screen(&mut self) {
+        self.screen.lock().unwrap().resize(SCREEN_WIDTH, SCREEN_HEIGHT);
+        self.info.lock().unwrap().set_config(CartridgeConfig::empty());
+    }
+
+    /// Apply the options of the pause menu, except the display which is
+    /// polled by the frontend
+    pub fn _apply_settings(&mut self) {
+        let settings = self.settings.lock().unwrap().clone();
+
+        self.sound.lock().unwrap().music_volume(settings.volume);
+        self.configuration.lock().unwrap().show_info_overlay = settings.info_overlay;
+
+        let palette = if settings.palette.is_empty() {
+            self.info.lock().unwrap().config.palette.clone()
+        } else {
+            settings.palette
+        };
+
+        if self.palettes.lock().unwrap().palettes.contains_key(&palette) {
+            self.palettes.lock().unwrap().switch_to_palette(&palette);
+        }
+    }
+
+    /// The errors of the code are shown by the ERROR state, unless the
+    /// cartridge is opened in the editor
     pub fn _load_cartridge(&mut self,
                            cartridge: &mut UnicornCartridge,
                            editor: bool)
@@ -1162,7 +1911,11 @@ impl Unicorn {
 
         let data = cartridge.get_code();
 
-        let mut ret: bool = false;
+        let ret;
+
+        // A new cartridge has to call cartdata() and menuitem() again
+        self.cartdata.lock().unwrap().close();
+        self.menuitems.lock().unwrap().clear();
 
         match cartridge.get_code_type() {
             Code::LUA => {
@@ -1174,7 +1927,14 @@ impl Unicorn {
                           self.info.clone(),
                           self.screen.clone(),
                           self.noise.clone(),
-                          self.sound.clone());
+                          self.random.clone(),
+                          self.sound.clone(),
+                          self.cartdata.clone(),
+                          self.menuitems.clone());
+
+                if let CartridgeFormat::Pico8Format = cartridge.cartridge.format {
+                    cartridge.lua_plugin.load_pico8_compat();
+                }
 
                 ret = cartridge.lua_plugin.load_code(data.clone());
             }
@@ -1187,7 +1947,10 @@ impl Unicorn {
                           self.info.clone(),
                           self.screen.clone(),
                           self.noise.clone(),
-                          self.sound.clone());
+                          self.random.clone(),
+                          self.sound.clone(),
+                          self.cartdata.clone(),
+                          self.menuitems.clone());
 
                 ret = cartridge.javascript_plugin.load_code(data.clone());
             }
@@ -1202,12 +1965,16 @@ impl Unicorn {
                           self.screen.clone(),
                           self.sound.clone(),
                           self.noise.clone(),
-                          self.configuration.clone());
+                          self.random.clone(),
+                          self.configuration.clone(),
+                          self.cartdata.clone(),
+                          self.menuitems.clone());
 
                 ret = cartridge.python_plugin.load_code(data.clone());
             }
             _ => {
                 info!("[Unicorn] Unknown cartridge type {:?}", cartridge.get_code_type());
+                ret = Err(format!("unknown code type {:?}", cartridge.get_code_type()));
             },
         }
 
@@ -1218,6 +1985,17 @@ impl Unicorn {
         #[cfg(feature = "editor")]
         {
             if editor {
+                let config = cartridge.cartridge.config;
+                if !edit::edit::Editor::supports_resolution(config.width, config.height) {
+                    let error = format!("The editor needs a resolution of at least {}x{}, the cartridge has {}x{}",
+                                        edit::edit::MIN_WIDTH,
+                                        edit::edit::MIN_HEIGHT,
+                                        config.width,
+                                        config.height);
+                    self.set_error(&cartridge.filename, error);
+                    return false;
+                }
+
                 self.editor
                     .init(self.configuration.clone(),
                           self.palettes.clone(),
@@ -1229,7 +2007,50 @@ impl Unicorn {
             }
         }
 
-        ret
+        match ret {
+            Ok(()) => true,
+            Err(e) => {
+                let filename = cartridge.filename.clone();
+                self.set_error(&filename, format!("{}: {}", filename, e));
+                false
+            }
+        }
+    }
+
+    fn set_error(&mut self, filename: &str, error: String) {
+        error!("[Unicorn] Impossible to load the cartridge {:?}: {}", filename, error);
+
+        self.error = Some(error);
+        self.state = UnicornState::ERROR;
+    }
+
+    fn draw_error(&mut self) {
+        let screen = &mut self.screen.lock().unwrap();
+        screen.cls(-1);
+
+        screen.print("Impossible to load the cartridge".to_string(), 4, 4, 8);
+
+        // Cut each line of the message to the width of the screen, 4 pixels
+        // per character
+        let columns = max(screen.width / 4, 3) - 2;
+        let message = match self.error {
+            Some(ref e) => e.clone(),
+            None => String::new(),
+        };
+
+        let mut y = 16;
+        for line in message.lines() {
+            let line: Vec<char> = line.chars().collect();
+            for part in line.chunks(columns) {
+                screen.print(part.iter().cloned().collect(), 4, y, 7);
+                y += 8;
+            }
+        }
+
+        if self.interactive {
+            let height = screen.height as i32;
+            screen.print("Press a button to go back".to_string(), 4, height - 12, 6);
+        }
     }
 
     pub fn load_cartridge(&mut self, filename: &str, full_filename: &str, editor: bool) -> bool {
@@ -1243,23 +2064,12 @@ impl Unicorn {
         }
 
         let cartridge;
-        if filename.contains(".uni") {
-            match Cartridge::from_unicorn_file(full_filename) {
-                Ok(c) => cartridge = c,
-                Err(e) => panic!("[Unicorn] Impossible to load the unicorn cartridge {:?}", e),
-            }
-        }  else if filename.contains(".uc") {
-            match Cartridge::from_unicorn_splitted_file(full_filename) {
-                Ok(c) => cartridge = c,
-                Err(e) => panic!("[Unicorn] Impossible to load the unicorn splitted cartridge {:?}", e),
-            }
-        } else if filename.contains(".duc") {
-            match Cartridge::from_dunicorn_file(full_filename) {
-                Ok(c) => cartridge = c,
-                Err(e) => panic!("[Unicorn] Impossible to load the dUnicorn cartridge {:?}", e),
+        match Cartridge::from_file(full_filename) {
+            Ok(c) => cartridge = c,
+            Err(e) => {
+                self.set_error(filename, format!("{}: {}", filename, e));
+                return false;
             }
-        } else {
-            panic!("[Unicorn] Unknown file format !");
         }
 
         let mut unicorn_cartridge = UnicornCartridge::new(cartridge, filename.to_string());
@@ -1311,13 +2121,12 @@ impl Unicorn {
                               -> bool {
         let cartridge;
 
-        if filename.contains(".uni") {
-            match Cartridge::from_uni_raw(filename, data) {
-                Ok(c) => cartridge = c,
-                Err(e) => panic!("Impossible to load the p8 cartridge {:?}", e),
+        match Cartridge::from_data(filename, data) {
+            Ok(c) => cartridge = c,
+            Err(e) => {
+                self.set_error(filename, format!("{}: {}", filename, e));
+                return false;
             }
-        } else {
-            panic!("[Unicorn] Unknown file");
         }
 
         let mut unicorn_cartridge = UnicornCartridge::new(cartridge, filename.to_string());
@@ -1361,17 +2170,14 @@ impl Unicorn {
                 let data = self.cartridges[idx].get_code();
                 let code_type = self.cartridges[idx].get_code_type();
 
-                match code_type {
-                    Code::LUA => {
-                        self.cartridges[idx].lua_plugin.load_code(data);
-                    }
-                    Code::JAVASCRIPT => {
-                        self.cartridges[idx].javascript_plugin.load_code(data);
-                    }
-                    Code::PYTHON => {
-                        self.cartridges[idx].python_plugin.load_code(data);
-                    }
-                    _ => (),
+                let result = match code_type {
+                    Code::LUA => self.cartridges[idx].lua_plugin.load_code(data),
+                    Code::JAVASCRIPT => self.cartridges[idx].javascript_plugin.load_code(data),
+                    Code::PYTHON => self.cartridges[idx].python_plugin.load_code(data),
+                    _ => Ok(()),
+                };
+                if let Err(e) = result {
+                    warn!("[Unicorn] The code can't be reloaded: {}", e);
                 }
 
                 self.editing = false;
@@ -1379,6 +2185,20 @@ impl Unicorn {
                 self.reset();
             } else {
                 info!("[Unicorn] Switch run to editor");
+
+                let (width, height) = {
+                    let mut screen = self.screen.lock().unwrap();
+                    (screen.mode_width(), screen.mode_height())
+                };
+                if !edit::edit::Editor::supports_resolution(width, height) {
+                    warn!("[Unicorn] The editor needs a resolution of at least {}x{}, the cartridge has {}x{}",
+                          edit::edit::MIN_WIDTH,
+                          edit::edit::MIN_HEIGHT,
+                          width,
+                          height);
+                    return;
+                }
+
                 info!("[Unicorn] Back to {:?}/{:?}", self.current_cartridge, self.cartridges.len());
                 let filename = self.cartridges[self.current_cartridge].filename.clone();
                 let full_filename = self.cartridges[self.current_cartridge].full_filename.clone();
@@ -1445,6 +2265,20 @@ impl Unicorn {
         }
     }
 
+    /// Callback of the entry `index` added to the pause menu by the cartridge
+    pub fn call_menuitem(&mut self, index: u32) {
+        info!("[Unicorn] Menu item {:?}", index);
+
+        match self.current_code_type {
+            Code::LUA => self.cartridges[self.current_cartridge].lua_plugin.menuitem(index),
+            Code::JAVASCRIPT => {
+                self.cartridges[self.current_cartridge].javascript_plugin.menuitem(index)
+            }
+            Code::PYTHON => self.cartridges[self.current_cartridge].python_plugin.menuitem(index),
+            _ => (),
+        }
+    }
+
     pub fn call_update(&mut self) {
         match self.current_code_type {
             Code::LUA => {
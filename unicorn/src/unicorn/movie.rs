@@ -0,0 +1,373 @@
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use config::Players;
+use unicorn::golden::{InputEvent, Timeline};
+
+/* MOVIE FORMAT
+
+# comment
+cartridge games/demo.uni
+seed 1234
+frame 16666666          <- starts a frame, duration in nanoseconds
+down 0 right            <- inputs of the frame, see golden::Timeline
+key Right
+frame 16666666
+text a\nb               <- '\\', '\n' and '\r' are escaped in the text
+
+*/
+
+/// Inputs applied before one update of the cartridge
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub dt: Duration,
+    pub events: Vec<InputEvent>,
+}
+
+/// Every input of a play session, from the start of the cartridge, to
+/// replay it frame by frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub cartridge: String,
+    /// Seed of the console random generators when the cartridge started
+    pub seed: u32,
+    pub frames: Vec<Frame>,
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut result = String::new();
+
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+fn nanoseconds(dt: Duration) -> u64 {
+    dt.as_secs() * 1_000_000_000 + dt.subsec_nanos() as u64
+}
+
+impl Movie {
+    pub fn new(cartridge: &str, seed: u32) -> Movie {
+        Movie {
+            cartridge: cartridge.to_string(),
+            seed: seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn parse(data: &str) -> Result<Movie, String> {
+        let mut movie = Movie::new("", 0);
+
+        for (line_idx, line) in data.lines().enumerate() {
+            // The text can be anything, including '#' or spaces
+            if line.starts_with("text ") {
+                match movie.frames.last_mut() {
+                    Some(frame) => frame.events.push(InputEvent::Text(unescape_text(&line[5..]))),
+                    None => return Err(format!("line {}: input before the first frame", line_idx + 1)),
+                }
+                continue;
+            }
+
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            let mut iter = line.split_whitespace();
+            let command = match iter.next() {
+                Some(command) => command,
+                None => continue,
+            };
+            let args: Vec<&str> = iter.collect();
+
+            match command {
+                "cartridge" => {
+                    movie.cartridge = line.trim()["cartridge".len()..].trim().to_string();
+                }
+                "seed" => {
+                    movie.seed = match args.get(0).map(|v| v.parse::<u32>()) {
+                        Some(Ok(seed)) => seed,
+                        _ => return Err(format!("line {}: bad seed {:?}", line_idx + 1, line.trim())),
+                    };
+                }
+                "frame" => {
+                    let dt = match args.get(0).map(|v| v.parse::<u64>()) {
+                        Some(Ok(dt)) => dt,
+                        _ => return Err(format!("line {}: bad frame {:?}", line_idx + 1, line.trim())),
+                    };
+
+                    movie.frames.push(Frame {
+                        dt: Duration::new(dt / 1_000_000_000, (dt % 1_000_000_000) as u32),
+                        events: Vec::new(),
+                    });
+                }
+                _ => {
+                    let event = match Timeline::parse_event(command, &args) {
+                        Some(event) => event,
+                        None => return Err(format!("line {}: bad event {:?}", line_idx + 1, line.trim())),
+                    };
+
+                    match movie.frames.last_mut() {
+                        Some(frame) => frame.events.push(event),
+                        None => return Err(format!("line {}: input before the first frame", line_idx + 1)),
+                    }
+                }
+            }
+        }
+
+        if movie.cartridge.is_empty() {
+            return Err("no cartridge in the movie".to_string());
+        }
+
+        Ok(movie)
+    }
+
+    pub fn from_file(filename: &str) -> Result<Movie, String> {
+        let mut data = String::new();
+        match File::open(filename) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_string(&mut data) {
+                    return Err(format!("{}: {}", filename, e));
+                }
+            }
+            Err(e) => return Err(format!("{}: {}", filename, e)),
+        }
+
+        Movie::parse(&data).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut data = String::new();
+
+        data.push_str("# Unicorn movie\n");
+        data.push_str(&format!("cartridge {}\n", self.cartridge));
+        data.push_str(&format!("seed {}\n", self.seed));
+
+        for frame in self.frames.iter() {
+            data.push_str(&format!("frame {}\n", nanoseconds(frame.dt)));
+            for event in frame.events.iter() {
+                match *event {
+                    InputEvent::Text(ref text) => {
+                        data.push_str(&format!("text {}\n", escape_text(text)))
+                    }
+                    _ => data.push_str(&format!("{}\n", event)),
+                }
+            }
+        }
+
+        data
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut output = try!(File::create(&Path::new(filename)));
+        output.write_all(self.to_text().as_bytes())
+    }
+}
+
+/// Movie recorded or replayed by `Unicorn`, one frame per update of the
+/// running cartridge.
+pub struct MovieState {
+    pub recording: bool,
+    pub replaying: bool,
+    pub filename: String,
+    pub movie: Movie,
+    /// Frame being recorded or replayed
+    pub frame: usize,
+    // the current frame waits for its duration, see frame_time
+    pending: bool,
+}
+
+impl MovieState {
+    pub fn new() -> MovieState {
+        MovieState {
+            recording: false,
+            replaying: false,
+            filename: "".to_string(),
+            movie: Movie::new("", 0),
+            frame: 0,
+            pending: false,
+        }
+    }
+
+    pub fn start_record(&mut self, filename: &str, movie: Movie, players: &mut Players) {
+        info!("[Unicorn][MOVIE] Record {:?} in {:?}", movie.cartridge, filename);
+
+        self.recording = true;
+        self.replaying = false;
+        self.filename = filename.to_string();
+        self.movie = movie;
+        self.frame = 0;
+        self.pending = false;
+
+        players.start_journal();
+    }
+
+    pub fn start_replay(&mut self, filename: &str, movie: Movie, players: &mut Players) {
+        info!("[Unicorn][MOVIE] Replay {:?}, {:?} frames",
+              filename,
+              movie.frames.len());
+
+        self.recording = false;
+        self.replaying = true;
+        self.filename = filename.to_string();
+        self.movie = movie;
+        self.frame = 0;
+        self.pending = false;
+
+        players.set_replaying(true);
+    }
+
+    /// Stop the record or the replay, a recorded movie is saved
+    pub fn stop(&mut self, players: &mut Players) -> io::Result<()> {
+        let recording = self.recording;
+
+        if self.replaying {
+            info!("[Unicorn][MOVIE] Stop the replay at frame {:?}", self.frame);
+            players.set_replaying(false);
+        }
+
+        self.recording = false;
+        self.replaying = false;
+        self.pending = false;
+
+        if recording {
+            info!("[Unicorn][MOVIE] Save {:?} frames in {:?}",
+                  self.movie.frames.len(),
+                  self.filename);
+
+            players.stop_journal();
+            return self.movie.save(&self.filename);
+        }
+
+        Ok(())
+    }
+
+    /// Called before each update of the console. Only the frames where the
+    /// cartridge runs are part of the movie, the inputs given to the menus
+    /// are dropped.
+    pub fn update(&mut self, players: &mut Players, elapsed: f64, running: bool) {
+        if self.recording {
+            let events = players.take_journal();
+            if running {
+                self.movie.frames.push(Frame {
+                    dt: Duration::new(0, 0),
+                    events: events,
+                });
+                self.pending = true;
+            }
+        } else if self.replaying && running {
+            match self.movie.frames.get(self.frame) {
+                Some(frame) => {
+                    players.replay(&frame.events, elapsed);
+                    self.pending = true;
+                }
+                None => {
+                    info!("[Unicorn][MOVIE] End of {:?} after {:?} frames",
+                          self.filename,
+                          self.frame);
+                    self.replaying = false;
+                    players.set_replaying(false);
+                }
+            }
+        }
+    }
+
+    /// Duration of the frame that was just updated. It is saved when
+    /// recording, and replaced by the saved one when replaying, so the
+    /// cartridge sees the same time as during the record.
+    pub fn frame_time(&mut self, dt: Duration) -> Duration {
+        if !self.pending {
+            return dt;
+        }
+        self.pending = false;
+
+        if self.recording {
+            if let Some(frame) = self.movie.frames.last_mut() {
+                frame.dt = dt;
+            }
+            self.frame += 1;
+            dt
+        } else if self.replaying {
+            let dt = self.movie.frames[self.frame].dt;
+            self.frame += 1;
+            dt
+        } else {
+            dt
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use config::scancode::{Mod, Scancode};
+
+    #[test]
+    fn test_movie_record_replay() {
+        let mut players = Players::new();
+        let mut state = MovieState::new();
+        state.start_record("test.movie", Movie::new("games/demo.uni", 42), &mut players);
+
+        let dt = Duration::new(0, 16_666_666);
+
+        players.key_down(Mod::NONE, Scancode::Right);
+        players.set_text("#\n\\n".to_string());
+        state.update(&mut players, 0.0, true);
+        assert_eq!(state.frame_time(dt), dt);
+
+        players.set_axis(1, 4, 0.5);
+        state.update(&mut players, 0.0, true);
+        state.frame_time(dt * 2);
+
+        let movie = Movie::parse(&state.movie.to_text()).unwrap();
+        assert_eq!(movie, state.movie);
+        assert_eq!(movie.seed, 42);
+        assert_eq!(movie.frames.len(), 2);
+        assert_eq!(movie.frames[1].dt, dt * 2);
+        assert!(movie.frames[0].events.contains(&InputEvent::ScancodeDown(Scancode::Right)));
+        assert!(movie.frames[0].events.contains(&InputEvent::Text("#\n\\n".to_string())));
+
+        let mut replayed = Players::new();
+        let mut state = MovieState::new();
+        state.start_replay("test.movie", movie, &mut replayed);
+
+        // The live inputs are ignored during the replay
+        replayed.key_down(Mod::NONE, Scancode::Left);
+
+        state.update(&mut replayed, 0.0, true);
+        assert_eq!(state.frame_time(Duration::new(1, 0)), dt);
+        assert!(replayed.btn_raw(Scancode::Right));
+        assert!(!replayed.btn_raw(Scancode::Left));
+        assert_eq!(replayed.get_text(), "#\n\\n");
+        assert!(replayed.btn(0, 1));
+
+        state.update(&mut replayed, 0.0, true);
+        state.frame_time(dt);
+        assert_eq!(replayed.axis(1, 4), players.axis(1, 4));
+
+        state.update(&mut replayed, 0.0, true);
+        assert!(!state.replaying);
+        assert!(!replayed.is_replaying());
+    }
+}
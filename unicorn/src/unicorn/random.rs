@@ -0,0 +1,80 @@
+use rand;
+
+/// Pseudo random generator of the cartridges (xorshift64*). It is owned by
+/// the console, so it can be seeded by a movie and saved in a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Random {
+    seed: u32,
+    state: u64,
+}
+
+impl Random {
+    pub fn new() -> Random {
+        info!("[Unicorn][Random] new");
+
+        let mut random = Random { seed: 0, state: 0 };
+        random.set_seed(rand::random::<u32>());
+        random
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        debug!("[Unicorn][Random] Change seed to {:?}", seed);
+
+        // splitmix64 of the seed, close seeds give different sequences
+        let mut z = (seed as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+
+        self.seed = seed;
+        // xorshift is stuck on 0
+        self.state = if z == 0 { 0x9e3779b97f4a7c15 } else { z };
+    }
+
+    pub fn get_seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+
+        x.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Value in [0, x)
+    pub fn rnd(&mut self, x: f64) -> f64 {
+        let value = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        value * x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_seed() {
+        let mut a = Random::new();
+        let mut b = Random::new();
+        a.set_seed(42);
+        b.set_seed(42);
+
+        for _ in 0..100 {
+            let value = a.rnd(10.0);
+            assert!(value >= 0.0 && value < 10.0);
+            assert_eq!(value, b.rnd(10.0));
+        }
+
+        // The state is enough to continue the sequence
+        let mut c = a.clone();
+        assert_eq!(a.next_u64(), c.next_u64());
+
+        b.set_seed(43);
+        assert!(a.next_u64() != b.next_u64());
+        assert_eq!(b.get_seed(), 43);
+    }
+}
@@ -0,0 +1,20 @@
+use config::PlayersState;
+use gfx::ScreenState;
+use unicorn::Palette;
+use unicorn::info::Info;
+use unicorn::random::Random;
+
+/// Whole console state at one frame, kept in memory by `Unicorn::save_state`.
+pub struct Snapshot {
+    /// Cartridge the snapshot was taken from, it can only be restored on it
+    pub filename: String,
+    pub screen: ScreenState,
+    pub palette: Palette,
+    pub palette_name: String,
+    /// Inputs of the players, without their bindings
+    pub players: PlayersState,
+    pub info: Info,
+    pub random: Random,
+    /// Serialized globals of the scripting VM, when the backend supports it
+    pub script: Option<String>,
+}